@@ -0,0 +1,159 @@
+//! A QMP server that pretends to be QEMU, for trying `winjet vm --qmp` and the QMP client
+//! without a VM.
+//!
+//! ```sh
+//! cargo run --example fake_qmp -- 127.0.0.1:7101
+//! ```
+
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:7101".into());
+
+    let listener = TcpListener::bind(&address).await?;
+    println!("Fake QMP listening on {}", listener.local_addr()?);
+
+    // Shared like a single QEMU would, so a `stop` from one connection shows in the next
+    let status = Arc::new(Mutex::new("running"));
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let status = status.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = serve(stream, status).await {
+                eprintln!("{peer}: {err}");
+            }
+        });
+    }
+}
+
+async fn serve(stream: TcpStream, status: Arc<Mutex<&'static str>>) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    send(
+        &mut write,
+        json!({
+            "QMP": {
+                "version": { "qemu": { "major": 9, "minor": 2, "micro": 0 }, "package": "fake" },
+                "capabilities": ["oob"]
+            }
+        }),
+    )
+    .await?;
+
+    let mut negotiated = false;
+
+    while let Some(line) = lines.next_line().await? {
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                send(&mut write, error("GenericError", &err.to_string())).await?;
+                continue;
+            }
+        };
+        let command = request["execute"].as_str().unwrap_or_default();
+
+        if !negotiated && command != "qmp_capabilities" {
+            send(
+                &mut write,
+                error("CommandNotFound", "Expecting capabilities negotiation"),
+            )
+            .await?;
+            continue;
+        }
+
+        let mut status = status.lock().await;
+        let (events, res) = match command {
+            "qmp_capabilities" => {
+                negotiated = true;
+                (vec![], json!({}))
+            }
+            "query-status" => (
+                vec![],
+                json!({ "status": *status, "running": *status == "running", "singlestep": false }),
+            ),
+            "stop" => {
+                *status = "paused";
+                (vec![event("STOP", json!({}))], json!({}))
+            }
+            "cont" => {
+                *status = "running";
+                (vec![event("RESUME", json!({}))], json!({}))
+            }
+            "system_powerdown" => {
+                *status = "shutdown";
+                (
+                    vec![
+                        event("POWERDOWN", json!({})),
+                        event(
+                            "SHUTDOWN",
+                            json!({ "guest": true, "reason": "guest-shutdown" }),
+                        ),
+                    ],
+                    json!({}),
+                )
+            }
+            "system_reset" => {
+                *status = "running";
+                (
+                    vec![event(
+                        "RESET",
+                        json!({ "guest": false, "reason": "host-qmp-system-reset" }),
+                    )],
+                    json!({}),
+                )
+            }
+            command => (
+                vec![],
+                error(
+                    "CommandNotFound",
+                    &format!("The command {command} has not been found"),
+                ),
+            ),
+        };
+        drop(status);
+
+        // QEMU often emits the events before the return, clients have to skip them
+        for event in events {
+            send(&mut write, event).await?;
+        }
+
+        match res.get("error") {
+            Some(_) => send(&mut write, res).await?,
+            None => send(&mut write, json!({ "return": res })).await?,
+        }
+    }
+
+    Ok(())
+}
+
+fn event(name: &str, data: Value) -> Value {
+    json!({
+        "event": name,
+        "data": data,
+        "timestamp": { "seconds": 0, "microseconds": 0 }
+    })
+}
+
+fn error(class: &str, desc: &str) -> Value {
+    json!({ "error": { "class": class, "desc": desc } })
+}
+
+async fn send(write: &mut (impl AsyncWriteExt + Unpin), msg: Value) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(&msg)?;
+    line.push(b'\n');
+
+    write.write_all(&line).await
+}
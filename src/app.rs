@@ -1,18 +1,42 @@
 mod main_screen;
 mod setup_screen;
 
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
-use color_eyre::Result;
+use bollard::secret::ContainerStateStatusEnum;
+use color_eyre::{Result, eyre::eyre};
 use directories::ProjectDirs;
+use surrealdb::RecordId;
+use winjet_gt_proto::InstalledApp;
 
 use crate::{
-    app::{main_screen::MainScreen, setup_screen::SetupScreen},
+    app::{
+        main_screen::{
+            MainScreen,
+            app_catalogue::{AppCatalogue, AppCatalogueMsg},
+            create_service_screen::{CreateServiceMsg, CreateServiceScreen},
+        },
+        setup_screen::{SetupScreen, engine_profiles::EngineProfileMsg},
+    },
     controller::{
-        docker::{ContainerData, DockerContainerExt, DockerController, DockerModule},
+        desktop::{DesktopController, DesktopModule},
+        docker::{
+            Connection, ContainerAction, ContainerData, ContainerEvent, DockerContainerExt,
+            DockerController, DockerModule, LogEvent, PullProgress, ShutdownProgress,
+        },
+        guest::{GuestClient, guest_address},
+        host::HostResources,
         kvm::{KVMController, KVMModule},
-        state::{DockerServiceState, StateController, StateModule},
+        preflight::{Preflight, PreflightController, PreflightModule},
+        qemu::{VmCommand, VmStatus},
+        rdp::{RdpController, RdpModule, SessionEvent, SessionKey},
+        state::{
+            DockerServiceState, Imported, LoadedServices, ShutdownRecord, StateController,
+            StateModule,
+        },
+        vnc::{self, VncEvent, VncSession},
     },
+    util::{Arced, open_url},
 };
 
 pub type AppTask = iced::Task<AppMsg>;
@@ -30,6 +54,9 @@ pub struct App {
     state: StateController,
     docker: DockerController,
     kvm: KVMController,
+    preflight: PreflightController,
+    rdp: RdpController,
+    desktop: DesktopController,
 }
 
 impl App {
@@ -37,16 +64,20 @@ impl App {
         let res = Self {
             project_dirs: dirs,
 
-            screen: AppScreen::Setup(SetupScreen),
+            screen: AppScreen::Setup(SetupScreen::default()),
 
             state: StateController::default(),
             docker: DockerController::default(),
             kvm: KVMController::default(),
+            preflight: PreflightController::default(),
+            rdp: RdpController::default(),
+            desktop: DesktopController::default(),
         };
         let task = AppTask::batch([
             AppTask::done(AppMsg::InitState),
-            AppTask::done(AppMsg::InitDocker),
             AppTask::done(AppMsg::InitKVM),
+            AppTask::done(AppMsg::InitRdp),
+            AppTask::done(AppMsg::InitDesktop),
         ]);
 
         (res, task)
@@ -60,17 +91,128 @@ impl App {
                     .load(self.project_dirs.clone(), AppMsg::InitStateRes);
             }
             AppMsg::InitStateRes(res) => {
-                return self
-                    .state
-                    .loaded(res, || AppTask::done(AppMsg::LoadDockerServiceState));
+                // Docker waits for the state, it may name the endpoint
+                let task = self.state.loaded(res, || {
+                    AppTask::batch([
+                        AppTask::done(AppMsg::LoadDockerServices),
+                        AppTask::done(AppMsg::InitDocker),
+                    ])
+                });
+
+                if let (AppScreen::Setup(setup), Some(state)) = (&mut self.screen, &*self.state) {
+                    setup.endpoint = state.docker_endpoint.clone().unwrap_or_default();
+                }
+
+                return task;
             }
 
-            AppMsg::InitDocker => return self.docker.load((), AppMsg::InitDockerRes),
+            AppMsg::InitDocker => {
+                let connection = self.state.as_ref().and_then(StateModule::connection);
+                return self.docker.load(connection, AppMsg::InitDockerRes);
+            }
             AppMsg::InitDockerRes(res) => return self.docker.loaded(res, AppTask::none),
+            AppMsg::DockerEndpoint(endpoint) => {
+                if let AppScreen::Setup(setup) = &mut self.screen {
+                    setup.endpoint = endpoint;
+                }
+            }
+            AppMsg::ConnectDocker => {
+                let AppScreen::Setup(setup) = &self.screen else {
+                    return AppTask::none();
+                };
+                let endpoint = Some(setup.endpoint.trim())
+                    .filter(|e| !e.is_empty())
+                    .map(String::from);
+
+                let save = match self.state.as_mut() {
+                    Some(state) => state.set_docker_endpoint(endpoint.clone()),
+                    None => AppTask::none(),
+                };
+
+                return AppTask::batch([
+                    save,
+                    self.reconnect_docker(endpoint.map(Connection::new)),
+                ]);
+            }
+            AppMsg::EngineProfileForm(msg) => {
+                if let AppScreen::Setup(setup) = &mut self.screen {
+                    setup.profiles.update(msg);
+                }
+            }
+            AppMsg::AddEngineProfile => {
+                let (AppScreen::Setup(setup), Some(state)) = (&mut self.screen, &mut *self.state)
+                else {
+                    return AppTask::none();
+                };
+                let Some(profile) = setup.profiles.take_profile(state) else {
+                    return AppTask::none();
+                };
+
+                let connection = profile.connection();
+                let save = state.save_profile(profile);
+                return AppTask::batch([save, self.reconnect_docker(Some(connection))]);
+            }
+            AppMsg::UseEngineProfile(id) => {
+                let Some(state) = self.state.as_mut() else {
+                    return AppTask::none();
+                };
+                let Some(connection) = state.find_profile(&id).map(|p| p.connection()) else {
+                    return AppTask::none();
+                };
+
+                let save = state.select_profile(id);
+                return AppTask::batch([save, self.reconnect_docker(Some(connection))]);
+            }
+            AppMsg::RemoveEngineProfile(id) => {
+                let Some(state) = self.state.as_mut() else {
+                    return AppTask::none();
+                };
+                let in_use = state.profile.as_ref() == Some(&id);
+
+                let save = state.remove_profile(id);
+                let connection = state.connection();
+
+                return match in_use {
+                    true => AppTask::batch([save, self.reconnect_docker(connection)]),
+                    false => save,
+                };
+            }
 
             AppMsg::InitKVM => return self.kvm.load((), AppMsg::InitKVMRes),
             AppMsg::InitKVMRes(res) => return self.kvm.loaded(res, AppTask::none),
 
+            AppMsg::InitPreflight => {
+                let service = self
+                    .state
+                    .as_ref()
+                    .and_then(|s| s.service())
+                    .cloned()
+                    .unwrap_or_default();
+
+                return self
+                    .preflight
+                    .load(Preflight::new(service), AppMsg::InitPreflightRes);
+            }
+            AppMsg::InitPreflightRes(res) => return self.preflight.loaded(res, AppTask::none),
+
+            AppMsg::InitRdp => return self.rdp.load((), AppMsg::InitRdpRes),
+            AppMsg::InitRdpRes(res) => return self.rdp.loaded(res, AppTask::none),
+
+            AppMsg::InitDesktop => {
+                return self
+                    .desktop
+                    .load(self.project_dirs.clone(), AppMsg::InitDesktopRes);
+            }
+            AppMsg::InitDesktopRes(res) => {
+                let task = self.desktop.loaded(res, AppTask::none);
+                return AppTask::batch([task, self.sync_desktop_entries()]);
+            }
+            AppMsg::SyncDesktopEntriesRes(res) => {
+                if let Err(err) = res.as_ref() {
+                    tracing::error!("Failed to update desktop entries: {err}");
+                }
+            }
+
             AppMsg::RetryInit => {
                 let mut tasks = vec![];
 
@@ -78,53 +220,597 @@ impl App {
                     tasks.push(AppTask::done(AppMsg::InitState));
                 }
 
-                if self.docker.is_none() {
+                // Otherwise the state initializes it once it's loaded
+                if self.docker.is_none() && self.state.is_some() {
                     tasks.push(AppTask::done(AppMsg::InitDocker));
                 }
 
-                if self.kvm.is_none() {
+                // Unusable KVM may have been fixed meanwhile
+                if !self.kvm.as_ref().is_some_and(KVMModule::usable) {
                     tasks.push(AppTask::done(AppMsg::InitKVM));
                 }
 
+                // The host may have been fixed meanwhile
+                tasks.push(AppTask::done(AppMsg::InitPreflight));
+
+                if self.rdp.is_none() {
+                    tasks.push(AppTask::done(AppMsg::InitRdp));
+                }
+
+                if self.desktop.is_none() {
+                    tasks.push(AppTask::done(AppMsg::InitDesktop));
+                }
+
                 if !tasks.is_empty() {
                     return AppTask::batch(tasks);
                 }
             }
             AppMsg::DoneSetup => {
-                self.screen = AppScreen::Main(MainScreen);
+                self.screen = AppScreen::Main(MainScreen::default());
             }
 
             AppMsg::CreateDockerServiceStateFromExisting(data) => {
-                self.state.as_mut().unwrap().service =
-                    Some(Arc::into_inner(data).unwrap().into_service());
-                return AppTask::done(AppMsg::UpdateDockerServiceState);
+                if let Some(main) = self.main_screen_mut() {
+                    main.adding = false;
+                }
+
+                return self
+                    .state
+                    .as_mut()
+                    .unwrap()
+                    .save_service(Arc::unwrap_or_clone(data).into_service());
             }
-            AppMsg::CreatedDockerServiceState(state) => {
-                self.state.as_mut().unwrap().service = Some(Arc::into_inner(state).unwrap());
-                return AppTask::done(AppMsg::UpdateDockerServiceState);
+            AppMsg::RefreshDockerContainers => {
+                return self.docker.as_ref().unwrap().refresh_containers();
             }
+            AppMsg::RefreshDockerContainersRes(res) => {
+                self.docker.as_mut().unwrap().set_containers(res)
+            }
+
+            AppMsg::DockerContainerEvent(event) => self.docker.as_mut().unwrap().apply_event(event),
 
-            AppMsg::LoadDockerServiceState => {
-                return self.state.as_mut().unwrap().try_load_service();
+            AppMsg::DockerLogEvent(container_name, event) => self
+                .docker
+                .as_mut()
+                .unwrap()
+                .apply_log(container_name, event),
+            AppMsg::ToggleBootLog => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.show_log = !main.service.show_log;
+                }
             }
-            AppMsg::LoadDockerServiceStateRes(res) => {
-                self.state.as_mut().unwrap().check_set_service(res)
+
+            AppMsg::SelectDockerService(id) => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.adding = false;
+                    main.service.confirm_apply = false;
+                }
+
+                let query = self.query_vm_status(&id);
+                return AppTask::batch([self.state.as_mut().unwrap().select_service(id), query]);
             }
+            AppMsg::AddDockerService => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.adding = true;
+                }
+            }
+            AppMsg::CancelAddDockerService => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.adding = false;
+                }
+            }
+            AppMsg::RemoveDockerService(id) => {
+                let task = self.state.as_mut().unwrap().remove_service(id);
+                return AppTask::batch([task, self.sync_desktop_entries()]);
+            }
+
+            AppMsg::OpenCreateDockerService => {
+                let host = self.host_resources();
 
-            AppMsg::UpdateDockerServiceState => {
-                return self.state.as_mut().unwrap().update_service_db();
+                if let Some(main) = self.main_screen_mut() {
+                    main.create_service = Some(CreateServiceScreen::new(Default::default(), host));
+                }
             }
+            AppMsg::CreateDockerServiceForm(msg) => {
+                if let Some(form) = self.create_service_screen_mut() {
+                    form.update(msg);
+                }
+            }
+            AppMsg::CancelCreateDockerService => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.create_service = None;
+                }
+            }
+            AppMsg::CreateDockerService(service) => {
+                if let Some(form) = self.create_service_screen_mut() {
+                    form.creating = true;
+                }
+
+                return self
+                    .docker
+                    .as_mut()
+                    .unwrap()
+                    .create_service(Arc::unwrap_or_clone(service));
+            }
+            AppMsg::CreateDockerServiceRes(res) => {
+                match Arc::into_inner(res).expect("Logic error!") {
+                    Ok(service) => {
+                        if let Some(main) = self.main_screen_mut() {
+                            main.create_service = None;
+                        }
+
+                        return AppTask::batch([
+                            AppTask::done(AppMsg::CreatedDockerServiceState(service.arced())),
+                            AppTask::done(AppMsg::RefreshDockerContainers),
+                        ]);
+                    }
+                    Err(err) => {
+                        tracing::error!("Failed to create docker service: {err}");
+
+                        if let Some(form) = self.create_service_screen_mut() {
+                            form.creating = false;
+                            form.error = Some(err.to_string());
+                        }
+                    }
+                }
+            }
+            AppMsg::EditDockerService => {
+                let service = self.state.as_ref().and_then(|s| s.service().cloned());
+                let host = self.host_resources();
+
+                if let (Some(main), Some(service)) = (self.main_screen_mut(), service) {
+                    main.create_service = Some(CreateServiceScreen::edit(service, host));
+                }
+            }
+            AppMsg::SaveDockerService(service) => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.create_service = None;
+                }
+
+                return self
+                    .state
+                    .as_mut()
+                    .unwrap()
+                    .save_service(Arc::unwrap_or_clone(service));
+            }
+            AppMsg::ConfirmApplyDockerService => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.confirm_apply = true;
+                }
+            }
+            AppMsg::CancelApplyDockerService => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.confirm_apply = false;
+                }
+            }
+
+            AppMsg::ImportCompose => return self.state.as_ref().unwrap().import_compose(),
+            AppMsg::ImportComposeRes(res) => {
+                let host = self.host_resources();
+                let Some(main) = self.main_screen_mut() else {
+                    return AppTask::none();
+                };
+
+                match Arc::into_inner(res).expect("Logic error!") {
+                    // A single one goes through the form, so it can be reviewed before creating it
+                    Ok(Some(mut services)) if services.len() == 1 => {
+                        main.compose_status = None;
+                        main.create_service = services
+                            .pop()
+                            .map(|service| CreateServiceScreen::new(service, host));
+                    }
+                    Ok(Some(services)) => {
+                        main.compose_status = None;
+                        return self.state.as_mut().unwrap().save_imported(services);
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::error!("Failed to import compose file: {err:?}");
+                        main.compose_status = Some(Err(format!("{err:#}")));
+                    }
+                }
+            }
+            AppMsg::ComposeImportedRes(res) => {
+                self.state.as_mut().unwrap().updated(res.as_ref());
+
+                let Some(main) = self.main_screen_mut() else {
+                    return AppTask::none();
+                };
+
+                main.compose_status = Some(match res.as_ref() {
+                    Ok(imported) => {
+                        let (added, skipped) =
+                            imported.iter().partition::<Vec<_>, _>(|x| x.imported);
+                        let names = |x: Vec<&Imported>| {
+                            x.iter()
+                                .map(|x| x.name.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        };
+
+                        Ok(match (added.is_empty(), skipped.is_empty()) {
+                            (false, true) => format!("Imported {}", names(added)),
+                            (false, false) => format!(
+                                "Imported {}, skipped {} as already managed",
+                                names(added),
+                                names(skipped)
+                            ),
+                            (true, _) => format!("Skipped {}, already managed", names(skipped)),
+                        })
+                    }
+                    Err(err) => Err(format!("Failed to import: {err}")),
+                });
+            }
+            AppMsg::ExportCompose => return self.state.as_ref().unwrap().export_compose(),
+            AppMsg::ExportComposeRes(res) => {
+                let Some(main) = self.main_screen_mut() else {
+                    return AppTask::none();
+                };
+
+                match res.as_ref() {
+                    Ok(Some(path)) => {
+                        main.compose_status = Some(Ok(format!("Exported to {}", path.display())))
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::error!("Failed to export compose file: {err}");
+                        main.compose_status = Some(Err(format!("Failed to export: {err}")));
+                    }
+                }
+            }
+
+            AppMsg::CreatedDockerServiceState(service) => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.adding = false;
+                }
+
+                return self
+                    .state
+                    .as_mut()
+                    .unwrap()
+                    .save_service(Arc::unwrap_or_clone(service));
+            }
+
+            AppMsg::DockerServiceAction(id, action) => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.confirm_apply = false;
+                }
+
+                let Some(service) = self.state.as_ref().and_then(|s| s.find_service(&id)) else {
+                    return AppTask::none();
+                };
+
+                return self.docker.as_mut().unwrap().run_action(service, action);
+            }
+            AppMsg::DockerServiceActionRes(container_name, action, res) => {
+                return self.docker.as_mut().unwrap().action_done(
+                    container_name,
+                    action,
+                    res.as_ref(),
+                );
+            }
+            AppMsg::VmCommand(id, command) => {
+                let Some(service) = self.state.as_ref().and_then(|s| s.find_service(&id)) else {
+                    return AppTask::none();
+                };
+
+                return self
+                    .docker
+                    .as_mut()
+                    .unwrap()
+                    .run_vm_command(service, command);
+            }
+            AppMsg::VmCommandRes(container_name, command, res) => {
+                self.docker.as_mut().unwrap().vm_command_done(
+                    container_name,
+                    command,
+                    res.as_ref(),
+                );
+            }
+            AppMsg::QueryVmStatus(id) => return self.query_vm_status(&id),
+            AppMsg::VmStatusRes(container_name, res) => {
+                self.docker
+                    .as_mut()
+                    .unwrap()
+                    .set_vm_status(container_name, res.as_ref());
+            }
+            AppMsg::ShutdownProgress(container_name, progress) => {
+                if let Some(docker) = self.docker.as_mut() {
+                    docker.shutdowns.insert(container_name, progress);
+                }
+            }
+            AppMsg::PullProgress(container_name, progress) => {
+                if let Some(docker) = self.docker.as_mut() {
+                    docker.pulls.insert(container_name, progress);
+                }
+            }
+            AppMsg::ShutdownRes(container_name, res) => {
+                let task = self.docker.as_mut().unwrap().action_done(
+                    container_name,
+                    ContainerAction::Stop,
+                    res.as_ref(),
+                );
+
+                return match (self.state.as_mut(), res.as_ref()) {
+                    (Some(state), Ok(record)) => {
+                        AppTask::batch([task, state.record_shutdown(record.clone())])
+                    }
+                    _ => task,
+                };
+            }
+
+            AppMsg::ConnectRdp(id) => {
+                let (Some(rdp), Some(service)) = (
+                    self.rdp.as_mut(),
+                    self.state.as_ref().and_then(|s| s.find_service(&id)),
+                ) else {
+                    return AppTask::none();
+                };
+                let container = self
+                    .docker
+                    .as_ref()
+                    .and_then(|d| d.container(&service.container_name));
+
+                return rdp.connect(service, container);
+            }
+            AppMsg::DisconnectRdp(key) => {
+                if let Some(rdp) = self.rdp.as_mut() {
+                    rdp.disconnect(&key);
+                }
+            }
+            AppMsg::RdpSessionEvent(key, event) => {
+                if let Some(rdp) = self.rdp.as_mut() {
+                    rdp.apply_event(key, event);
+                }
+            }
+
+            AppMsg::OpenConsole(id) => {
+                let url = self
+                    .service_container(&id)
+                    .ok_or_else(|| eyre!("The container doesn't exist"))
+                    .and_then(vnc::console_url);
+
+                return match url {
+                    Ok(url) => AppTask::perform(async move { open_url(&url).await }, |res| {
+                        AppMsg::OpenConsoleRes(res.arced())
+                    }),
+                    Err(err) => AppTask::done(AppMsg::OpenConsoleRes(Err(err).arced())),
+                };
+            }
+            AppMsg::OpenConsoleRes(res) => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.console_error = res.as_ref().as_ref().err().map(|e| e.to_string());
+                }
+            }
+            AppMsg::WatchConsole(id) => {
+                let Some(container) = self.service_container(&id) else {
+                    return AppTask::none();
+                };
+                let (session, task) = VncSession::connect(container);
+
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.console = Some(session);
+                    return task;
+                }
+            }
+            AppMsg::CloseConsole => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.console = None;
+                }
+            }
+            AppMsg::VncEvent(container_name, event) => {
+                if let Some(console) = self
+                    .main_screen_mut()
+                    .and_then(|main| main.service.console.as_mut())
+                    .filter(|c| c.container_name == container_name)
+                {
+                    console.apply_event(event);
+                }
+            }
+
+            AppMsg::AppCatalogueForm(msg) => {
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.catalogue.update(msg);
+                }
+            }
+            AppMsg::AddWindowsApp => {
+                let Some(service) = self.state.as_ref().and_then(|s| s.selected.clone()) else {
+                    return AppTask::none();
+                };
+                let Some(app) = self
+                    .main_screen_mut()
+                    .and_then(|main| main.service.catalogue.take_app(service))
+                else {
+                    return AppTask::none();
+                };
+
+                let task = self.state.as_mut().unwrap().save_app(app);
+                return AppTask::batch([task, self.sync_desktop_entries()]);
+            }
+            AppMsg::RemoveWindowsApp(id) => {
+                let task = self.state.as_mut().unwrap().remove_app(id);
+                return AppTask::batch([task, self.sync_desktop_entries()]);
+            }
+            AppMsg::DiscoverWindowsApps(id) => {
+                let token = self
+                    .state
+                    .as_ref()
+                    .and_then(|state| state.find_service(&id))
+                    .map(|service| service.guest_token.clone())
+                    .unwrap_or_default();
+                let address = self
+                    .service_container(&id)
+                    .ok_or_else(|| eyre!("The container doesn't exist"))
+                    .and_then(guest_address);
+
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.catalogue.discovering = true;
+                    main.service.catalogue.discovered = None;
+                }
+
+                return match address {
+                    Ok(address) => AppTask::perform(
+                        async move {
+                            GuestClient::connect(&address, &token)
+                                .await?
+                                .list_apps()
+                                .await
+                        },
+                        move |res| AppMsg::DiscoveredWindowsApps(id, res.arced()),
+                    ),
+                    Err(err) => AppTask::done(AppMsg::DiscoveredWindowsApps(id, Err(err).arced())),
+                };
+            }
+            AppMsg::DiscoveredWindowsApps(id, res) => match res.as_ref() {
+                Ok(found) => {
+                    return self
+                        .state
+                        .as_mut()
+                        .unwrap()
+                        .save_discovered(id, found.clone());
+                }
+                Err(err) => {
+                    tracing::error!("Failed to discover applications: {err}");
+
+                    if let Some(main) = self.main_screen_mut() {
+                        main.service.catalogue.discovering = false;
+                        main.service.catalogue.error = Some(err.to_string());
+                    }
+                }
+            },
+            AppMsg::DiscoverWindowsAppsRes(res) => {
+                self.state.as_mut().unwrap().updated(res.as_ref());
+
+                if let Some(main) = self.main_screen_mut() {
+                    main.service.catalogue.discovered(res.as_ref());
+                }
+
+                return self.sync_desktop_entries();
+            }
+            AppMsg::PickAppIcon => return AppCatalogue::pick_icon(),
+            AppMsg::OpenFileInWindowsApp(id) => {
+                return AppCatalogue::pick_file().and_then(move |file| {
+                    AppTask::done(AppMsg::LaunchWindowsApp(id.clone(), Some(file)))
+                });
+            }
+            AppMsg::LaunchWindowsApp(id, file) => {
+                let Some(state) = self.state.as_ref() else {
+                    return AppTask::none();
+                };
+                let (Some(rdp), Some(app)) = (self.rdp.as_mut(), state.find_app(&id)) else {
+                    return AppTask::none();
+                };
+                let Some(service) = state.find_service(&app.service) else {
+                    return AppTask::none();
+                };
+                let container = self
+                    .docker
+                    .as_ref()
+                    .and_then(|d| d.container(&service.container_name));
+
+                return rdp.launch_app(service, app, container, file.as_deref());
+            }
+
+            AppMsg::LoadDockerServices => {
+                return self.state.as_mut().unwrap().load_services();
+            }
+            AppMsg::LoadDockerServicesRes(res) => {
+                self.state.as_mut().unwrap().set_services(res);
+
+                let preflight = match self.screen {
+                    AppScreen::Setup(_) => AppTask::done(AppMsg::InitPreflight),
+                    AppScreen::Main(_) => AppTask::none(),
+                };
+                return AppTask::batch([self.sync_desktop_entries(), preflight]);
+            }
+
+            AppMsg::UpdateStateRes(res) => self.state.as_mut().unwrap().updated(res.as_ref()),
         }
 
         AppTask::none()
     }
 
+    /// Connects to another engine, desktop entries launch through it too.
+    fn reconnect_docker(&mut self, connection: Option<Connection>) -> AppTask {
+        // A failed connection mustn't leave the previous engine looking connected
+        *self.docker = None;
+
+        AppTask::batch([
+            self.sync_desktop_entries(),
+            self.docker.load(connection, AppMsg::InitDockerRes),
+        ])
+    }
+
+    /// Only running containers have a QEMU to ask.
+    fn query_vm_status(&self, id: &RecordId) -> AppTask {
+        let running = self
+            .service_container(id)
+            .is_some_and(|c| c.state_status() == Some(ContainerStateStatusEnum::RUNNING));
+
+        match (
+            self.state.as_ref().and_then(|s| s.find_service(id)),
+            self.docker.as_ref(),
+        ) {
+            (Some(service), Some(docker)) if running => docker.query_vm_status(service),
+            _ => AppTask::none(),
+        }
+    }
+
+    fn service_container(&self, id: &RecordId) -> Option<&ContainerData> {
+        let service = self.state.as_ref()?.find_service(id)?;
+        self.docker.as_ref()?.container(&service.container_name)
+    }
+
+    /// Entries follow the catalogue, whatever is initialized last triggers the first sync.
+    fn sync_desktop_entries(&self) -> AppTask {
+        match (self.desktop.as_ref(), self.state.as_ref()) {
+            (Some(desktop), Some(state)) if !state.services_loading => desktop.sync(state),
+            _ => AppTask::none(),
+        }
+    }
+
+    fn main_screen_mut(&mut self) -> Option<&mut MainScreen> {
+        match &mut self.screen {
+            AppScreen::Main(main) => Some(main),
+            _ => None,
+        }
+    }
+
+    /// What the machine running the containers has, this one until an engine answered.
+    fn host_resources(&self) -> HostResources {
+        self.docker
+            .as_ref()
+            .map_or_else(HostResources::detect, |docker| {
+                docker.engine.host_resources()
+            })
+    }
+
+    fn create_service_screen_mut(&mut self) -> Option<&mut CreateServiceScreen> {
+        self.main_screen_mut()
+            .and_then(|main| main.create_service.as_mut())
+    }
+
     pub fn subscription(&self) -> AppSubscription {
-        AppSubscription::none()
+        let services = self.state.iter().flat_map(|s| s.services.iter());
+
+        AppSubscription::batch(self.docker.iter().flat_map(|docker| {
+            std::iter::once(docker.subscription()).chain(
+                services
+                    .clone()
+                    .map(|s| docker.logs_subscription(&s.container_name)),
+            )
+        }))
     }
 
     pub fn view(&self) -> AppElement<'_> {
-        self.screen.view(&self.state, &self.docker, &self.kvm)
+        self.screen.view(
+            &self.state,
+            &self.docker,
+            &self.kvm,
+            &self.preflight,
+            &self.rdp,
+            &self.desktop,
+        )
     }
 
     pub fn theme(&self) -> AppTheme {
@@ -143,10 +829,15 @@ impl AppScreen {
         state: &'a StateController,
         docker: &'a DockerController,
         kvm: &'a KVMController,
+        preflight: &'a PreflightController,
+        rdp: &'a RdpController,
+        desktop: &'a DesktopController,
     ) -> AppElement<'a> {
         match self {
-            Self::Setup(setup_screen) => setup_screen.view(state, docker, kvm),
-            Self::Main(main_screen) => main_screen.view(state, docker),
+            Self::Setup(setup_screen) => {
+                setup_screen.view(state, docker, kvm, preflight, rdp, desktop)
+            }
+            Self::Main(main_screen) => main_screen.view(state, docker, rdp),
         }
     }
 }
@@ -158,18 +849,95 @@ pub enum AppMsg {
 
     InitDocker,
     InitDockerRes(Arc<Result<DockerModule>>),
+    DockerEndpoint(String),
+    ConnectDocker,
+    EngineProfileForm(EngineProfileMsg),
+    AddEngineProfile,
+    UseEngineProfile(RecordId),
+    RemoveEngineProfile(RecordId),
 
     InitKVM,
     InitKVMRes(Arc<Result<KVMModule>>),
 
+    InitPreflight,
+    InitPreflightRes(Arc<Result<PreflightModule>>),
+
+    InitRdp,
+    InitRdpRes(Arc<Result<RdpModule>>),
+
+    InitDesktop,
+    InitDesktopRes(Arc<Result<DesktopModule>>),
+    SyncDesktopEntriesRes(Arc<Result<()>>),
+
     RetryInit,
     DoneSetup,
 
-    LoadDockerServiceState,
-    LoadDockerServiceStateRes(Arc<Result<Option<DockerServiceState>>>),
+    LoadDockerServices,
+    LoadDockerServicesRes(Arc<Result<LoadedServices>>),
+    UpdateStateRes(Arc<Result<()>>),
+
+    SelectDockerService(RecordId),
+    AddDockerService,
+    CancelAddDockerService,
+    RemoveDockerService(RecordId),
+
+    RefreshDockerContainers,
+    RefreshDockerContainersRes(Arc<Result<Vec<ContainerData>>>),
+
+    DockerContainerEvent(Arc<Result<ContainerEvent>>),
+
+    DockerLogEvent(String, LogEvent),
+    ToggleBootLog,
+
+    OpenCreateDockerService,
+    CreateDockerServiceForm(CreateServiceMsg),
+    CancelCreateDockerService,
+    CreateDockerService(Arc<DockerServiceState>),
+    CreateDockerServiceRes(Arc<Result<DockerServiceState>>),
+
+    EditDockerService,
+    SaveDockerService(Arc<DockerServiceState>),
+    ConfirmApplyDockerService,
+    CancelApplyDockerService,
+
+    ImportCompose,
+    ImportComposeRes(Arc<Result<Option<Vec<DockerServiceState>>>>),
+    ComposeImportedRes(Arc<Result<Vec<Imported>>>),
+    ExportCompose,
+    ExportComposeRes(Arc<Result<Option<PathBuf>>>),
 
     CreateDockerServiceStateFromExisting(Arc<ContainerData>),
     CreatedDockerServiceState(Arc<DockerServiceState>),
 
-    UpdateDockerServiceState,
+    DockerServiceAction(RecordId, ContainerAction),
+    DockerServiceActionRes(String, ContainerAction, Arc<Result<()>>),
+    VmCommand(RecordId, VmCommand),
+    VmCommandRes(String, VmCommand, Arc<Result<VmStatus>>),
+    QueryVmStatus(RecordId),
+    VmStatusRes(String, Arc<Result<VmStatus>>),
+    ShutdownProgress(String, ShutdownProgress),
+    ShutdownRes(String, Arc<Result<ShutdownRecord>>),
+    /// How far pulling the image of a container got, while creating or recreating it.
+    PullProgress(String, PullProgress),
+
+    ConnectRdp(RecordId),
+    DisconnectRdp(SessionKey),
+    RdpSessionEvent(SessionKey, SessionEvent),
+
+    AppCatalogueForm(AppCatalogueMsg),
+    PickAppIcon,
+    AddWindowsApp,
+    RemoveWindowsApp(RecordId),
+    DiscoverWindowsApps(RecordId),
+    DiscoveredWindowsApps(RecordId, Arc<Result<Vec<InstalledApp>>>),
+    /// Names of the apps that weren't catalogued yet.
+    DiscoverWindowsAppsRes(Arc<Result<Vec<String>>>),
+    OpenConsole(RecordId),
+    OpenConsoleRes(Arc<Result<()>>),
+    WatchConsole(RecordId),
+    CloseConsole,
+    VncEvent(String, VncEvent),
+
+    OpenFileInWindowsApp(RecordId),
+    LaunchWindowsApp(RecordId, Option<PathBuf>),
 }
@@ -1,31 +1,52 @@
+pub mod app_catalogue;
+pub mod create_service_screen;
 mod no_docker_service_screen;
+pub mod service_screen;
+pub mod settings_panel;
+mod sidebar;
 
 use iced::{
     Length,
-    widget::{center, text},
+    widget::{center, column, row, text},
 };
 use iced_aw::Spinner;
 
 use crate::{
-    app::{AppElement, main_screen::no_docker_service_screen::NoDockerServiceScreen},
-    controller::{docker::DockerController, state::StateController},
+    app::{
+        AppElement,
+        main_screen::{
+            create_service_screen::CreateServiceScreen,
+            no_docker_service_screen::NoDockerServiceScreen, service_screen::ServiceScreen,
+            sidebar::Sidebar,
+        },
+    },
+    controller::{docker::DockerController, rdp::RdpController, state::StateController},
 };
 
-pub struct MainScreen;
+#[derive(Default)]
+pub struct MainScreen {
+    pub create_service: Option<CreateServiceScreen>,
+    pub service: ServiceScreen,
+    /// Showing the ways to add another service instead of the selected one.
+    pub adding: bool,
+    /// Outcome of the last compose import or export.
+    pub compose_status: Option<Result<String, String>>,
+}
 
 impl MainScreen {
     pub fn view<'a>(
         &'a self,
         state: &'a StateController,
         docker: &'a DockerController,
+        rdp: &'a RdpController,
     ) -> AppElement<'a> {
-        let state_module = state.as_ref().unwrap();
+        if let Some(create_service) = &self.create_service {
+            return create_service.view(rdp, docker);
+        }
 
-        let Some(service) = &state_module.service else {
-            return NoDockerServiceScreen.view(state, docker);
-        };
+        let state_module = state.as_ref().unwrap();
 
-        if !state_module.service_exists_db {
+        if state_module.services_loading {
             return center(
                 Spinner::new()
                     .width(Length::Fixed(50.0))
@@ -34,6 +55,30 @@ impl MainScreen {
             .into();
         }
 
-        center(text(format!("{} is ALIVE!", service.container_name))).into()
+        let content = match state_module.service() {
+            Some(service) if !self.adding => self.service.view(service, state_module, docker, rdp),
+            _ => NoDockerServiceScreen.view(state, docker),
+        };
+
+        let content = match state_module.services.is_empty() {
+            true => content,
+            false => row![Sidebar.view(state_module, docker, self.adding), content]
+                .spacing(10)
+                .padding(10)
+                .into(),
+        };
+
+        match &self.compose_status {
+            Some(status) => column![
+                match status {
+                    Ok(msg) => text(msg).style(text::success),
+                    Err(err) => text(err).style(text::danger),
+                },
+                content
+            ]
+            .padding(10)
+            .into(),
+            None => content,
+        }
     }
 }
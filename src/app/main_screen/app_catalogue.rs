@@ -0,0 +1,267 @@
+use std::{path::PathBuf, sync::Arc};
+
+use color_eyre::Result;
+use iced::{
+    Alignment, Length,
+    widget::{Space, button, column, row, text, text_input},
+};
+use iced_aw::Spinner;
+use iced_fonts::nerd;
+use rfd::AsyncFileDialog;
+use surrealdb::RecordId;
+
+use crate::{
+    app::{AppElement, AppMsg, AppTask},
+    controller::{
+        rdp::{RdpController, SessionKey, SessionStatus},
+        state::{DockerServiceState, StateModule, WindowsApp},
+    },
+};
+
+/// The RemoteApp programs of a service, and the form to add another one.
+#[derive(Default)]
+pub struct AppCatalogue {
+    pub name: String,
+    pub program: String,
+    pub args: String,
+    /// `;` separated, like the `MimeType` key of desktop entries.
+    pub mime_types: String,
+    pub icon: Option<Arc<Vec<u8>>>,
+    pub error: Option<String>,
+    /// Asking the guest tools what's installed.
+    pub discovering: bool,
+    /// What the last discovery added.
+    pub discovered: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AppCatalogueMsg {
+    Name(String),
+    Program(String),
+    Args(String),
+    MimeTypes(String),
+    /// `None` if the user canceled the dialog.
+    Icon(Option<Arc<Vec<u8>>>),
+}
+
+impl AppCatalogue {
+    pub fn update(&mut self, msg: AppCatalogueMsg) {
+        self.error = None;
+
+        match msg {
+            AppCatalogueMsg::Name(name) => self.name = name,
+            AppCatalogueMsg::Program(program) => self.program = program,
+            AppCatalogueMsg::Args(args) => self.args = args,
+            AppCatalogueMsg::MimeTypes(mime_types) => self.mime_types = mime_types,
+            AppCatalogueMsg::Icon(icon) => {
+                if icon.is_some() {
+                    self.icon = icon;
+                }
+            }
+        }
+    }
+
+    pub fn discovered(&mut self, res: &Result<Vec<String>>) {
+        self.discovering = false;
+
+        let names = match res {
+            Ok(names) => names,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return;
+            }
+        };
+
+        self.discovered = Some(match names.as_slice() {
+            [] => "Found no applications that aren't catalogued yet".into(),
+            [name] => format!("Added {name}"),
+            names => format!("Added {} applications", names.len()),
+        });
+    }
+
+    pub fn pick_icon() -> AppTask {
+        AppTask::perform(
+            async move {
+                let file = AsyncFileDialog::new()
+                    .add_filter("Icon", &["ico", "png", "bmp"])
+                    .pick_file()
+                    .await?;
+
+                Some(Arc::new(file.read().await))
+            },
+            |icon| AppMsg::AppCatalogueForm(AppCatalogueMsg::Icon(icon)),
+        )
+    }
+
+    /// Asks for a host file to open in an app, `None` if the user canceled.
+    pub fn pick_file() -> iced::Task<Option<PathBuf>> {
+        iced::Task::future(async move {
+            AsyncFileDialog::new()
+                .pick_file()
+                .await
+                .map(|file| file.path().to_path_buf())
+        })
+    }
+
+    /// Turns the form into an app of the service, clearing it if it's valid.
+    pub fn take_app(&mut self, service: RecordId) -> Option<WindowsApp> {
+        let app = WindowsApp {
+            icon: self.icon.as_deref().cloned(),
+            mime_types: self
+                .mime_types
+                .split(';')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(String::from)
+                .collect(),
+            ..WindowsApp::new(
+                service,
+                self.name.trim().to_string(),
+                self.program.trim().to_string(),
+                Some(self.args.trim().to_string()).filter(|x| !x.is_empty()),
+            )
+        };
+
+        match app.validate() {
+            Ok(()) => {
+                *self = Self {
+                    discovering: self.discovering,
+                    ..Self::default()
+                };
+                Some(app)
+            }
+            Err(err) => {
+                self.error = Some(err.to_string());
+                None
+            }
+        }
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        service: &'a DockerServiceState,
+        state: &'a StateModule,
+        rdp: &'a RdpController,
+        running: bool,
+    ) -> AppElement<'a> {
+        let apps = state
+            .apps_of(&service.id)
+            .fold(column![].spacing(5), |col, app| {
+                col.push(app_view(app, rdp, running))
+            });
+
+        let msg = AppMsg::AppCatalogueForm;
+
+        let discover = match self.discovering {
+            true => button(Spinner::new()),
+            false => button(text("Discover")).on_press_maybe(
+                (running && !state.services_updating)
+                    .then(|| AppMsg::DiscoverWindowsApps(service.id.clone())),
+            ),
+        };
+
+        let mut content = column![
+            row![
+                text("Applications").size(18),
+                Space::new(Length::Fill, Length::Shrink),
+                discover.style(button::secondary),
+            ]
+            .align_y(Alignment::Center),
+            apps,
+            row![
+                text_input("Name", &self.name)
+                    .on_input(move |x| msg(AppCatalogueMsg::Name(x)))
+                    .width(Length::FillPortion(2)),
+                text_input("C:\\Program Files\\...\\app.exe", &self.program)
+                    .on_input(move |x| msg(AppCatalogueMsg::Program(x)))
+                    .width(Length::FillPortion(4)),
+                text_input("Arguments", &self.args)
+                    .on_input(move |x| msg(AppCatalogueMsg::Args(x)))
+                    .width(Length::FillPortion(2)),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            row![
+                text_input("Opens (text/plain;application/pdf)", &self.mime_types)
+                    .on_input(move |x| msg(AppCatalogueMsg::MimeTypes(x)))
+                    .width(Length::Fill),
+                button(text(match self.icon {
+                    Some(_) => "Change Icon",
+                    None => "Pick Icon",
+                }))
+                .style(button::secondary)
+                .on_press(AppMsg::PickAppIcon),
+                button(nerd::fa_plus())
+                    .on_press_maybe((!state.services_updating).then_some(AppMsg::AddWindowsApp)),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+        ]
+        .spacing(10);
+
+        if let Some(discovered) = &self.discovered {
+            content = content.push(text(discovered).style(text::success));
+        }
+
+        if let Some(err) = &self.error {
+            content = content.push(text(err).style(text::danger));
+        }
+
+        content.into()
+    }
+}
+
+fn app_view<'a>(app: &'a WindowsApp, rdp: &'a RdpController, running: bool) -> AppElement<'a> {
+    let session = rdp
+        .as_ref()
+        .and_then(|r| r.session(&SessionKey::App(app.id.to_string())));
+
+    let mut line = row![
+        column![
+            text(&app.name),
+            text(&app.program).size(12).style(text::secondary)
+        ],
+        Space::new(Length::Fill, Length::Shrink),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    line = match session.map(|s| &s.status) {
+        Some(SessionStatus::Starting) => line.push(Spinner::new()),
+        Some(SessionStatus::Connected) => line.push(nerd::fa_check().style(text::success)).push(
+            button(text("Close"))
+                .style(button::danger)
+                .on_press(AppMsg::DisconnectRdp(SessionKey::App(app.id.to_string()))),
+        ),
+        disconnected => {
+            if let Some(SessionStatus::Disconnected { error: Some(err) }) = disconnected {
+                line = line.push(text(err).style(text::danger));
+            }
+
+            let launchable = running && rdp.is_some();
+
+            line.push(
+                button(text("Open File"))
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        launchable.then(|| AppMsg::OpenFileInWindowsApp(app.id.clone())),
+                    ),
+            )
+            .push(
+                button(text("Launch")).on_press_maybe(
+                    launchable.then(|| AppMsg::LaunchWindowsApp(app.id.clone(), None)),
+                ),
+            )
+        }
+    };
+
+    line.push(
+        button(nerd::fa_trash())
+            .style(button::danger)
+            .on_press_maybe(
+                (!session.is_some_and(|s| s.status.is_active()))
+                    .then(|| AppMsg::RemoveWindowsApp(app.id.clone())),
+            ),
+    )
+    .into()
+}
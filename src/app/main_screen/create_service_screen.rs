@@ -0,0 +1,570 @@
+use std::fmt::Display;
+
+use bollard::secret::{DeviceMapping, Port, PortTypeEnum, RestartPolicy, RestartPolicyNameEnum};
+use color_eyre::{Result, eyre::eyre};
+use iced::{
+    Length,
+    widget::{
+        Space, button, center, column, container, horizontal_rule, pick_list, rich_text, row,
+        scrollable, span, text, text_input, toggler,
+    },
+};
+use iced_aw::Spinner;
+use iced_fonts::{NERD_FONT, nerd};
+
+use crate::{
+    app::{
+        AppElement, AppMsg,
+        main_screen::{
+            service_screen::pull_view,
+            settings_panel::{SettingsMsg, SettingsPanel},
+        },
+    },
+    controller::{
+        docker::{DockerController, ImageRef},
+        host::HostResources,
+        rdp::{RdpClientKind, RdpController, RdpOptions},
+        state::{DockerServiceState, EnvVar, Environment},
+    },
+};
+
+const RESTART_POLICIES: [RestartPolicyNameEnum; 4] = [
+    RestartPolicyNameEnum::NO,
+    RestartPolicyNameEnum::ALWAYS,
+    RestartPolicyNameEnum::UNLESS_STOPPED,
+    RestartPolicyNameEnum::ON_FAILURE,
+];
+
+const PORT_TYPES: [PortTypeEnum; 3] = [PortTypeEnum::TCP, PortTypeEnum::UDP, PortTypeEnum::SCTP];
+
+/// Form for a brand new [`DockerServiceState`], prefilled with its defaults, or for editing the
+/// stored one.
+#[derive(Debug)]
+pub struct CreateServiceScreen {
+    base: DockerServiceState,
+    /// Editing only saves the service, the container is recreated separately.
+    editing: bool,
+
+    image: String,
+    container_name: String,
+    environment: Environment,
+    devices: Vec<String>,
+    cap_add: Vec<String>,
+    ports: Vec<PortDraft>,
+    volumes: Vec<String>,
+    restart: RestartPolicyNameEnum,
+    stop_grace_period: String,
+    rdp: RdpOptions,
+
+    host: HostResources,
+
+    pub creating: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct PortDraft {
+    private_port: String,
+    public_port: String,
+    typ: PortTypeEnum,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ListField {
+    Devices,
+    CapAdd,
+    Volumes,
+}
+
+#[derive(Debug, Clone)]
+pub enum CreateServiceMsg {
+    Image(String),
+    ContainerName(String),
+
+    AddEnv,
+    RemoveEnv(usize),
+    EnvKey(usize, String),
+    EnvValue(usize, String),
+    Settings(SettingsMsg),
+
+    AddItem(ListField),
+    RemoveItem(ListField, usize),
+    EditItem(ListField, usize, String),
+
+    AddPort,
+    RemovePort(usize),
+    PortPrivate(usize, String),
+    PortPublic(usize, String),
+    PortType(usize, PortTypeEnum),
+
+    Restart(RestartPolicyNameEnum),
+    StopGracePeriod(String),
+
+    Rdp(RdpMsg),
+}
+
+#[derive(Debug, Clone)]
+pub enum RdpMsg {
+    Client(RdpClientChoice),
+    Scale(u32),
+    Multimon(bool),
+    DynamicResolution(bool),
+    Clipboard(bool),
+    HomeDrive(bool),
+}
+
+/// `None` picks the best installed client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdpClientChoice(Option<RdpClientKind>);
+
+impl Display for RdpClientChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(kind) => kind.fmt(f),
+            None => f.write_str("Automatic"),
+        }
+    }
+}
+
+impl CreateServiceScreen {
+    /// `host` is what the machine running the engine has, bounding the settings.
+    pub fn new(service: DockerServiceState, host: HostResources) -> Self {
+        Self {
+            image: service.image.clone(),
+            container_name: service.container_name.clone(),
+            environment: service.environment.clone(),
+            devices: service
+                .devices
+                .iter()
+                .flat_map(|d| d.path_on_host.clone())
+                .collect(),
+            cap_add: service.cap_add.clone(),
+            ports: service
+                .ports
+                .iter()
+                .map(|port| PortDraft {
+                    private_port: port.private_port.to_string(),
+                    public_port: port.public_port.map(|p| p.to_string()).unwrap_or_default(),
+                    typ: port.typ.unwrap_or(PortTypeEnum::TCP),
+                })
+                .collect(),
+            volumes: service.volumes.clone(),
+            restart: service
+                .restart
+                .name
+                .unwrap_or(RestartPolicyNameEnum::ALWAYS),
+            stop_grace_period: service.stop_grace_period.to_string(),
+            rdp: service.rdp.clone(),
+
+            host,
+
+            base: service,
+            editing: false,
+
+            creating: false,
+            error: None,
+        }
+    }
+
+    pub fn edit(service: DockerServiceState, host: HostResources) -> Self {
+        Self {
+            editing: true,
+            ..Self::new(service, host)
+        }
+    }
+
+    pub fn update(&mut self, msg: CreateServiceMsg) {
+        self.error = None;
+
+        match msg {
+            CreateServiceMsg::Image(image) => self.image = image,
+            CreateServiceMsg::ContainerName(name) => self.container_name = name,
+
+            CreateServiceMsg::AddEnv => self.environment.push(EnvVar {
+                key: String::new(),
+                value: Some(String::new()),
+            }),
+            CreateServiceMsg::RemoveEnv(ind) => {
+                self.environment.remove(ind);
+            }
+            CreateServiceMsg::EnvKey(ind, key) => self.environment[ind].key = key,
+            CreateServiceMsg::EnvValue(ind, value) => self.environment[ind].value = Some(value),
+            CreateServiceMsg::Settings(msg) => SettingsPanel::update(&mut self.environment, msg),
+
+            CreateServiceMsg::AddItem(field) => self.list_mut(field).push(String::new()),
+            CreateServiceMsg::RemoveItem(field, ind) => {
+                self.list_mut(field).remove(ind);
+            }
+            CreateServiceMsg::EditItem(field, ind, value) => self.list_mut(field)[ind] = value,
+
+            CreateServiceMsg::AddPort => self.ports.push(PortDraft {
+                private_port: String::new(),
+                public_port: String::new(),
+                typ: PortTypeEnum::TCP,
+            }),
+            CreateServiceMsg::RemovePort(ind) => {
+                self.ports.remove(ind);
+            }
+            CreateServiceMsg::PortPrivate(ind, port) => self.ports[ind].private_port = port,
+            CreateServiceMsg::PortPublic(ind, port) => self.ports[ind].public_port = port,
+            CreateServiceMsg::PortType(ind, typ) => self.ports[ind].typ = typ,
+
+            CreateServiceMsg::Restart(restart) => self.restart = restart,
+            CreateServiceMsg::StopGracePeriod(period) => self.stop_grace_period = period,
+
+            CreateServiceMsg::Rdp(msg) => match msg {
+                RdpMsg::Client(choice) => self.rdp.client = choice.0,
+                RdpMsg::Scale(scale) => self.rdp.scale = scale,
+                RdpMsg::Multimon(on) => self.rdp.multimon = on,
+                RdpMsg::DynamicResolution(on) => self.rdp.dynamic_resolution = on,
+                RdpMsg::Clipboard(on) => self.rdp.clipboard = on,
+                RdpMsg::HomeDrive(on) => self.rdp.home_drive = on,
+            },
+        }
+    }
+
+    fn list(&self, field: ListField) -> &Vec<String> {
+        match field {
+            ListField::Devices => &self.devices,
+            ListField::CapAdd => &self.cap_add,
+            ListField::Volumes => &self.volumes,
+        }
+    }
+
+    fn list_mut(&mut self, field: ListField) -> &mut Vec<String> {
+        match field {
+            ListField::Devices => &mut self.devices,
+            ListField::CapAdd => &mut self.cap_add,
+            ListField::Volumes => &mut self.volumes,
+        }
+    }
+
+    /// Validates the form and builds the service it describes.
+    pub fn service(&self) -> Result<DockerServiceState> {
+        let non_empty = |name: &str, value: &str| match value.trim().is_empty() {
+            true => Err(eyre!("{name} can't be empty")),
+            false => Ok(value.trim().to_string()),
+        };
+        let parse_port = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|err| eyre!("Invalid port '{port}': {err}"))
+        };
+
+        let mut environment = self.environment.clone();
+        environment.retain(|var| !var.key.trim().is_empty());
+        environment
+            .iter_mut()
+            .for_each(|var| var.key = var.key.trim().into());
+        environment.validate(&self.host)?;
+
+        let ports = self
+            .ports
+            .iter()
+            .map(|port| {
+                Ok(Port {
+                    private_port: parse_port(&port.private_port)?,
+                    public_port: match port.public_port.trim().is_empty() {
+                        true => None,
+                        false => Some(parse_port(&port.public_port)?),
+                    },
+                    typ: Some(port.typ),
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let cleaned = |list: &Vec<String>| {
+            list.iter()
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        Ok(DockerServiceState {
+            image: non_empty("Image", &self.image)?
+                .parse::<ImageRef>()?
+                .to_string(),
+            container_name: non_empty("Container name", &self.container_name)?,
+            environment,
+            devices: cleaned(&self.devices)
+                .into_iter()
+                .map(|path| DeviceMapping {
+                    path_on_host: Some(path),
+                    ..Default::default()
+                })
+                .collect(),
+            cap_add: cleaned(&self.cap_add),
+            ports,
+            volumes: cleaned(&self.volumes),
+            restart: RestartPolicy {
+                name: Some(self.restart),
+                ..self.base.restart.clone()
+            },
+            stop_grace_period: self.stop_grace_period.parse()?,
+            rdp: self.rdp.clone(),
+            ..self.base.clone()
+        })
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        rdp: &'a RdpController,
+        docker: &'a DockerController,
+    ) -> AppElement<'a> {
+        let msg = AppMsg::CreateDockerServiceForm;
+
+        let env =
+            self.environment
+                .iter()
+                .enumerate()
+                .fold(column![].spacing(5), |col, (ind, var)| {
+                    col.push(
+                        row![
+                            text_input("KEY", &var.key)
+                                .on_input(move |x| msg(CreateServiceMsg::EnvKey(ind, x))),
+                            text_input("value", var.value.as_deref().unwrap_or_default())
+                                .on_input(move |x| msg(CreateServiceMsg::EnvValue(ind, x))),
+                            remove_button(msg(CreateServiceMsg::RemoveEnv(ind))),
+                        ]
+                        .spacing(5),
+                    )
+                });
+
+        let ports = self
+            .ports
+            .iter()
+            .enumerate()
+            .fold(column![].spacing(5), |col, (ind, port)| {
+                col.push(
+                    row![
+                        text_input("Host", &port.public_port)
+                            .on_input(move |x| msg(CreateServiceMsg::PortPublic(ind, x))),
+                        text(":"),
+                        text_input("Container", &port.private_port)
+                            .on_input(move |x| msg(CreateServiceMsg::PortPrivate(ind, x))),
+                        pick_list(PORT_TYPES, Some(port.typ), move |x| {
+                            msg(CreateServiceMsg::PortType(ind, x))
+                        }),
+                        remove_button(msg(CreateServiceMsg::RemovePort(ind))),
+                    ]
+                    .spacing(5),
+                )
+            });
+
+        let submit = match self.creating {
+            true => row![Spinner::new(), text(" Creating...")].into(),
+            false => AppElement::from(
+                button(text(match self.editing {
+                    true => "Save",
+                    false => "Create & Start",
+                }))
+                .on_press_maybe(self.service().ok().map(
+                    |x| match self.editing {
+                        true => AppMsg::SaveDockerService(x.into()),
+                        false => AppMsg::CreateDockerService(x.into()),
+                    },
+                )),
+            ),
+        };
+
+        // The container name is what ties the service to its container
+        let mut container_name = text_input("windows", &self.container_name);
+        if !self.editing {
+            container_name =
+                container_name.on_input(move |x| msg(CreateServiceMsg::ContainerName(x)));
+        }
+
+        let error = self
+            .service()
+            .err()
+            .map(|err| err.to_string())
+            .or_else(|| self.error.clone())
+            .map(|err| text(err).style(text::danger));
+
+        let mut content = column![
+            text(match self.editing {
+                true => "Edit Docker Service",
+                false => "Create New Docker Service",
+            })
+            .size(24),
+            horizontal_rule(2),
+            scrollable(
+                column![
+                    section(
+                        "Image",
+                        text_input("dockurr/windows", &self.image)
+                            .on_input(move |x| msg(CreateServiceMsg::Image(x)))
+                    ),
+                    section("Container Name", container_name),
+                    section(
+                        "Windows",
+                        SettingsPanel.view(&self.environment, &self.host, move |x| msg(
+                            CreateServiceMsg::Settings(x)
+                        ))
+                    ),
+                    section_list("Environment", env, msg(CreateServiceMsg::AddEnv)),
+                    self.list_view("Devices", ListField::Devices),
+                    self.list_view("Capabilities", ListField::CapAdd),
+                    section_list("Ports", ports, msg(CreateServiceMsg::AddPort)),
+                    self.list_view("Volumes", ListField::Volumes),
+                    section(
+                        "Restart Policy",
+                        pick_list(RESTART_POLICIES, Some(self.restart), move |x| {
+                            msg(CreateServiceMsg::Restart(x))
+                        })
+                    ),
+                    section(
+                        "Stop Grace Period",
+                        text_input("2m", &self.stop_grace_period)
+                            .on_input(move |x| msg(CreateServiceMsg::StopGracePeriod(x)))
+                    ),
+                    section("Remote Desktop", self.rdp_view(rdp)),
+                ]
+                .spacing(15)
+                .padding(10)
+            )
+            .height(Length::Fill),
+            horizontal_rule(2),
+        ]
+        .spacing(10)
+        .max_width(800);
+
+        if let Some(error) = error {
+            content = content.push(error);
+        }
+
+        // Only a service whose image isn't there yet gets to see a pull
+        if let Some(pull) = docker
+            .as_ref()
+            .and_then(|d| d.pulls.get(self.container_name.trim()))
+            .filter(|_| self.creating)
+        {
+            content = content.push(pull_view(pull));
+        }
+
+        center(
+            content.push(
+                row![
+                    button(text("Cancel")).on_press_maybe(
+                        (!self.creating).then_some(AppMsg::CancelCreateDockerService)
+                    ),
+                    Space::new(Length::Fill, Length::Shrink),
+                    submit,
+                ]
+                .spacing(10),
+            ),
+        )
+        .padding(20)
+        .into()
+    }
+
+    fn rdp_view<'a>(&'a self, rdp: &'a RdpController) -> AppElement<'a> {
+        let msg = |x| AppMsg::CreateDockerServiceForm(CreateServiceMsg::Rdp(x));
+
+        // Only offer what's installed, unless nothing was found at all
+        let clients = std::iter::once(RdpClientChoice(None))
+            .chain(
+                match rdp.as_ref() {
+                    Some(rdp) => rdp.clients.iter().map(|c| c.kind).collect(),
+                    None => RdpClientKind::ALL.to_vec(),
+                }
+                .into_iter()
+                .map(|kind| RdpClientChoice(Some(kind))),
+            )
+            .collect::<Vec<_>>();
+
+        let switch = |label: &'a str, on: bool, to_msg: fn(bool) -> RdpMsg| {
+            toggler(on).label(label).on_toggle(move |x| msg(to_msg(x)))
+        };
+
+        column![
+            row![
+                text("Client").width(Length::Fixed(120.0)),
+                pick_list(clients, Some(RdpClientChoice(self.rdp.client)), move |x| {
+                    msg(RdpMsg::Client(x))
+                }),
+            ]
+            .spacing(10),
+            row![
+                text("Scale").width(Length::Fixed(120.0)),
+                pick_list(RdpOptions::SCALES, Some(self.rdp.scale), move |x| {
+                    msg(RdpMsg::Scale(x))
+                }),
+                text("%"),
+            ]
+            .spacing(10),
+            switch("Span all monitors", self.rdp.multimon, RdpMsg::Multimon),
+            switch(
+                "Resize with the window",
+                self.rdp.dynamic_resolution,
+                RdpMsg::DynamicResolution
+            ),
+            switch("Share the clipboard", self.rdp.clipboard, RdpMsg::Clipboard),
+            switch(
+                "Share the home folder",
+                self.rdp.home_drive,
+                RdpMsg::HomeDrive
+            ),
+        ]
+        .spacing(10)
+        .into()
+    }
+
+    fn list_view(&self, title: &'static str, field: ListField) -> AppElement<'_> {
+        let msg = AppMsg::CreateDockerServiceForm;
+
+        let items =
+            self.list(field)
+                .iter()
+                .enumerate()
+                .fold(column![].spacing(5), |col, (ind, value)| {
+                    col.push(
+                        row![
+                            text_input("", value)
+                                .on_input(move |x| msg(CreateServiceMsg::EditItem(field, ind, x))),
+                            remove_button(msg(CreateServiceMsg::RemoveItem(field, ind))),
+                        ]
+                        .spacing(5),
+                    )
+                });
+
+        section_list(title, items, msg(CreateServiceMsg::AddItem(field)))
+    }
+}
+
+fn section<'a>(title: &'a str, content: impl Into<AppElement<'a>>) -> AppElement<'a> {
+    column![text(title).size(18), content.into()]
+        .spacing(5)
+        .into()
+}
+
+fn section_list<'a>(
+    title: &'a str,
+    content: impl Into<AppElement<'a>>,
+    on_add: AppMsg,
+) -> AppElement<'a> {
+    container(section(
+        title,
+        column![
+            content.into(),
+            button(rich_text![
+                span::<(), _>(nerd::advanced_text::fa_plus().0).font(NERD_FONT),
+                span(" Add")
+            ])
+            .on_press(on_add)
+        ]
+        .spacing(5),
+    ))
+    .padding(10)
+    .style(container::bordered_box)
+    .into()
+}
+
+fn remove_button<'a>(on_press: AppMsg) -> AppElement<'a> {
+    button(nerd::fa_trash())
+        .style(button::danger)
+        .on_press(on_press)
+        .into()
+}
@@ -2,7 +2,9 @@ use std::sync::Arc;
 
 use iced::{
     Length,
-    widget::{button, center, column, container, horizontal_rule, rich_text, span, table, text},
+    widget::{
+        button, center, column, container, horizontal_rule, rich_text, row, span, table, text,
+    },
 };
 use iced_fonts::{NERD_FONT, nerd};
 
@@ -24,14 +26,33 @@ impl NoDockerServiceScreen {
     ) -> AppElement<'a> {
         let state_module = state.as_ref().unwrap();
 
+        let mut actions = row![
+            button(rich_text![
+                span::<(), _>(nerd::advanced_text::fa_plus().0)
+                    .font(NERD_FONT)
+                    .size(20.0),
+                span(" Create New Docker Service")
+            ])
+            .on_press_maybe(
+                (!state_module.services_updating).then_some(AppMsg::OpenCreateDockerService)
+            ),
+            button(text("Import Compose File"))
+                .style(button::secondary)
+                .on_press_maybe((!state_module.services_updating).then_some(AppMsg::ImportCompose)),
+        ]
+        .spacing(10);
+
+        if !state_module.services.is_empty() {
+            actions = actions.push(
+                button(text("Cancel"))
+                    .style(button::secondary)
+                    .on_press(AppMsg::CancelAddDockerService),
+            );
+        }
+
         center(
             column![
-                button(rich_text![
-                    span::<(), _>(nerd::advanced_text::fa_plus().0)
-                        .font(NERD_FONT)
-                        .size(20.0),
-                    span(" Create New Docker Service")
-                ]),
+                actions,
                 horizontal_rule(2),
                 text("Choose One of The existing ones..."),
                 center(
@@ -40,15 +61,19 @@ impl NoDockerServiceScreen {
                             table::column(text("Select"), |container: &ContainerData| button(
                                 nerd::fa_check().size(20)
                             )
-                            .on_press_maybe((!state_module.service_updating).then(|| {
+                            .on_press_maybe((!state_module.services_updating).then(|| {
                                 AppMsg::CreateDockerServiceStateFromExisting(Arc::new(
                                     container.clone(),
                                 ))
                             }))),
                             ContainerData::name_column(),
                             ContainerData::image_column(),
+                            ContainerData::status_column(),
                         ],
-                        docker.iter().flat_map(|d| d.containers.iter())
+                        docker
+                            .iter()
+                            .flat_map(|d| d.containers.iter())
+                            .filter(|c| !state_module.manages(&c.name()))
                     ))
                     .width(Length::Shrink)
                     .height(Length::Shrink)
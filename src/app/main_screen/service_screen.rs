@@ -0,0 +1,525 @@
+use std::cmp::Ordering;
+
+use bollard::secret::{ContainerStateStatusEnum, HealthStatusEnum};
+use iced::{
+    Alignment, Font, Length,
+    widget::{
+        Space, button, center, column, container, horizontal_rule, image, progress_bar, row,
+        scrollable, text,
+    },
+};
+use iced_aw::Spinner;
+use iced_fonts::nerd;
+
+use crate::{
+    app::{AppElement, AppMsg, main_screen::app_catalogue::AppCatalogue},
+    controller::{
+        docker::{
+            BootLog, BootStage, ConfigChange, ContainerAction, DockerContainerExt,
+            DockerController, DockerModule, ImageRef, PullProgress, ShutdownProgress,
+        },
+        qemu::VmCommand,
+        rdp::{RdpController, SessionKey, SessionStatus},
+        state::{DockerServiceState, ShutdownOutcome, StateModule},
+        vnc::VncSession,
+    },
+};
+
+#[derive(Default)]
+pub struct ServiceScreen {
+    pub show_log: bool,
+    /// The user asked to apply the pending changes and has to confirm the recreation.
+    pub confirm_apply: bool,
+    pub catalogue: AppCatalogue,
+    /// VNC view of the selected service's installation.
+    pub console: Option<VncSession>,
+    pub console_error: Option<String>,
+}
+
+impl ServiceScreen {
+    pub fn view<'a>(
+        &'a self,
+        service: &'a DockerServiceState,
+        state: &'a StateModule,
+        docker: &'a DockerController,
+        rdp: &'a RdpController,
+    ) -> AppElement<'a> {
+        let Some(docker_module) = docker.as_ref() else {
+            return center(text("Docker is not available")).into();
+        };
+
+        let container = docker_module.container(&service.container_name);
+        let status = container.and_then(|c| c.state_status());
+        let running = docker_module.actions.get(&service.container_name).copied();
+
+        let actions = ContainerAction::ALL
+            .into_iter()
+            .fold(row![].spacing(10), |row, action| {
+                let btn = button(text(action.label())).on_press_maybe(
+                    (running.is_none() && action.available(status))
+                        .then(|| AppMsg::DockerServiceAction(service.id.clone(), action)),
+                );
+
+                row.push(match action {
+                    ContainerAction::Stop | ContainerAction::Remove => btn.style(button::danger),
+                    _ => btn,
+                })
+            })
+            .push(Space::new(Length::Fill, Length::Shrink))
+            .push(
+                // A digest always names the same image
+                button(text(ContainerAction::UpdateImage.label()))
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        (running.is_none()
+                            && ContainerAction::UpdateImage.available(status)
+                            && service
+                                .image
+                                .parse::<ImageRef>()
+                                .is_ok_and(|image| !image.pinned()))
+                        .then(|| {
+                            AppMsg::DockerServiceAction(
+                                service.id.clone(),
+                                ContainerAction::UpdateImage,
+                            )
+                        }),
+                    ),
+            )
+            .push(
+                button(text("Export"))
+                    .style(button::secondary)
+                    .on_press(AppMsg::ExportCompose),
+            )
+            .push(
+                button(text("Edit"))
+                    .style(button::secondary)
+                    .on_press_maybe(running.is_none().then_some(AppMsg::EditDockerService)),
+            )
+            .push(
+                button(text("Forget"))
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        running
+                            .is_none()
+                            .then(|| AppMsg::RemoveDockerService(service.id.clone())),
+                    ),
+            );
+
+        let mut content = column![
+            row![
+                text(&service.container_name).size(24),
+                Space::new(Length::Fill, Length::Shrink),
+                text(
+                    status
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| "Container not found".into())
+                ),
+            ]
+            .push(match container.and_then(|c| c.health()) {
+                Some(health) => text(format!(" ({health})")).style(match health {
+                    HealthStatusEnum::HEALTHY => text::success,
+                    HealthStatusEnum::UNHEALTHY => text::danger,
+                    _ => text::default,
+                }),
+                None => text(""),
+            }),
+            horizontal_rule(2),
+            actions,
+            vm_view(service, docker_module, status, running.is_none()),
+            desktop_view(service, status, rdp),
+            self.console_view(service, status),
+            self.catalogue.view(
+                service,
+                state,
+                rdp,
+                status == Some(ContainerStateStatusEnum::RUNNING)
+            ),
+        ]
+        .spacing(10)
+        .max_width(800);
+
+        let pull = docker_module.pulls.get(&service.container_name);
+
+        match (
+            running,
+            docker_module.shutdowns.get(&service.container_name),
+        ) {
+            (_, Some(progress)) => content = content.push(shutdown_view(progress)),
+            (Some(action), None) => {
+                content = content.push(row![
+                    Spinner::new(),
+                    text(format!(" {}...", action.progress()))
+                ]);
+
+                if let Some(pull) = pull {
+                    content = content.push(pull_view(pull));
+                }
+            }
+            (None, None) => {
+                if let Some(status) = pull.and_then(|p| p.status.as_ref()) {
+                    content = content.push(text(status).style(text::secondary));
+                }
+
+                if let Some(record) = state.last_shutdown(&service.id) {
+                    content =
+                        content.push(text(format!("Last stop: {}", record.describe())).style(
+                            match record.outcome {
+                                ShutdownOutcome::Clean => text::secondary,
+                                _ => text::warning,
+                            },
+                        ));
+                }
+            }
+        }
+
+        if let Some(err) = docker_module.action_errors.get(&service.container_name) {
+            content = content.push(text(err).style(text::danger));
+        }
+
+        let changes = docker_module.pending_changes(service);
+        if !changes.is_empty() {
+            content = content.push(horizontal_rule(2)).push(self.changes_view(
+                service,
+                &changes,
+                running.is_none(),
+            ));
+        }
+
+        if let Some(log) = docker_module.boot_logs.get(&service.container_name) {
+            content = content.push(horizontal_rule(2)).push(self.boot_view(log));
+        }
+
+        center(
+            container(content)
+                .padding(20)
+                .style(container::bordered_box),
+        )
+        .padding(20)
+        .into()
+    }
+
+    fn console_view<'a>(
+        &'a self,
+        service: &'a DockerServiceState,
+        status: Option<ContainerStateStatusEnum>,
+    ) -> AppElement<'a> {
+        let running = status == Some(ContainerStateStatusEnum::RUNNING);
+        let console = self
+            .console
+            .as_ref()
+            .filter(|c| c.container_name == service.container_name);
+
+        let watching = console.is_some_and(|c| c.is_active());
+
+        let mut controls = row![text("Console"), Space::new(Length::Fill, Length::Shrink)]
+            .spacing(10)
+            .align_y(Alignment::Center);
+
+        if let Some(err) = &self.console_error {
+            controls = controls.push(text(err).style(text::danger));
+        }
+
+        controls = controls
+            .push(
+                button(text("Open in Browser"))
+                    .style(button::secondary)
+                    .on_press_maybe(running.then(|| AppMsg::OpenConsole(service.id.clone()))),
+            )
+            .push(match watching {
+                true => button(text("Stop Watching"))
+                    .style(button::danger)
+                    .on_press(AppMsg::CloseConsole),
+                false => button(text("Watch"))
+                    .on_press_maybe(running.then(|| AppMsg::WatchConsole(service.id.clone()))),
+            });
+
+        let mut content = column![controls].spacing(10);
+
+        if let Some(console) = console {
+            if let Some(err) = &console.error {
+                content = content.push(text(err).style(text::danger));
+            }
+
+            content = match &console.frame {
+                Some(frame) => content.push(image(frame.clone()).width(Length::Fill)),
+                None if watching => content.push(row![Spinner::new(), text(" Connecting...")]),
+                None => content,
+            };
+        }
+
+        content.into()
+    }
+
+    fn changes_view<'a>(
+        &self,
+        service: &DockerServiceState,
+        changes: &[ConfigChange],
+        idle: bool,
+    ) -> AppElement<'a> {
+        let list = changes.iter().fold(column![].spacing(2), |col, change| {
+            col.push(
+                row![
+                    text(change.field.clone()).width(Length::Fixed(150.0)),
+                    text(change.live.clone()).style(text::danger),
+                    text("→"),
+                    text(change.stored.clone()).style(text::success),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+            )
+        });
+
+        let controls = match self.confirm_apply {
+            true => row![
+                text("The container will be stopped and recreated, its storage is kept."),
+                Space::new(Length::Fill, Length::Shrink),
+                button(text("Cancel"))
+                    .style(button::secondary)
+                    .on_press(AppMsg::CancelApplyDockerService),
+                button(text("Recreate"))
+                    .style(button::danger)
+                    .on_press_maybe(idle.then(|| AppMsg::DockerServiceAction(
+                        service.id.clone(),
+                        ContainerAction::Apply
+                    ))),
+            ],
+            false => row![
+                Space::new(Length::Fill, Length::Shrink),
+                button(text(ContainerAction::Apply.label()))
+                    .on_press_maybe(idle.then_some(AppMsg::ConfirmApplyDockerService)),
+            ],
+        }
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        column![text("Pending changes").size(18), list, controls]
+            .spacing(10)
+            .into()
+    }
+
+    fn boot_view<'a>(&'a self, log: &'a BootLog) -> AppElement<'a> {
+        let progress = &log.progress;
+
+        let stages = BootStage::ALL
+            .into_iter()
+            .fold(column![].spacing(5), |col, stage| {
+                let icon: AppElement<'a> = match stage.cmp(&progress.stage) {
+                    Ordering::Less => nerd::fa_check().style(text::success).into(),
+                    Ordering::Equal if stage == BootStage::Ready => {
+                        nerd::fa_check().style(text::success).into()
+                    }
+                    Ordering::Equal if progress.error.is_some() => {
+                        nerd::cod_error().style(text::danger).into()
+                    }
+                    Ordering::Equal => Spinner::new().into(),
+                    Ordering::Greater => nerd::fa_circle().into(),
+                };
+
+                let mut line = column![
+                    row![icon, text(progress.stage_label(stage))]
+                        .spacing(10)
+                        .align_y(Alignment::Center)
+                ];
+
+                if stage == BootStage::Downloading
+                    && progress.stage == BootStage::Downloading
+                    && let Some(percent) = progress.download
+                {
+                    line = line.push(
+                        row![
+                            progress_bar(0.0..=100.0, percent),
+                            text(format!("{percent:.0}%"))
+                        ]
+                        .spacing(10),
+                    );
+                }
+
+                col.push(line)
+            });
+
+        let mut content = column![stages].spacing(10);
+
+        if let Some(err) = &progress.error {
+            content = content.push(text(err).style(text::danger));
+        }
+
+        content = content.push(
+            button(text(match self.show_log {
+                true => "Hide log",
+                false => "Show log",
+            }))
+            .style(button::secondary)
+            .on_press(AppMsg::ToggleBootLog),
+        );
+
+        if self.show_log {
+            content = content.push(
+                container(
+                    scrollable(
+                        log.lines
+                            .iter()
+                            .fold(column![], |col, line| {
+                                col.push(text(line).font(Font::MONOSPACE).size(12))
+                            })
+                            .width(Length::Fill),
+                    )
+                    .anchor_bottom()
+                    .height(Length::Fixed(300.0)),
+                )
+                .padding(10)
+                .style(container::dark),
+            );
+        }
+
+        content.into()
+    }
+}
+
+fn desktop_view<'a>(
+    service: &'a DockerServiceState,
+    status: Option<ContainerStateStatusEnum>,
+    rdp: &'a RdpController,
+) -> AppElement<'a> {
+    let Some(rdp_module) = rdp.as_ref() else {
+        return text("Install FreeRDP to connect to the desktop")
+            .style(text::secondary)
+            .into();
+    };
+
+    let key = SessionKey::Desktop(service.container_name.clone());
+    let session = rdp_module.session(&key);
+    let client = session
+        .and_then(|s| s.client)
+        .map(|c| c.to_string())
+        .unwrap_or_default();
+
+    let mut content = row![].spacing(10).align_y(Alignment::Center);
+
+    content = match session.map(|s| &s.status) {
+        Some(SessionStatus::Starting) => content.push(Spinner::new()).push(text("Connecting...")),
+        Some(SessionStatus::Connected) => content
+            .push(nerd::fa_check().style(text::success))
+            .push(text(format!("Connected with {client}")))
+            .push(Space::new(Length::Fill, Length::Shrink))
+            .push(
+                button(text("Disconnect"))
+                    .style(button::danger)
+                    .on_press(AppMsg::DisconnectRdp(key.clone())),
+            ),
+        disconnected => {
+            let mut content = content.push(text("Remote desktop"));
+
+            if let Some(SessionStatus::Disconnected { error: Some(err) }) = disconnected {
+                content = content.push(text(err).style(text::danger));
+            }
+
+            content.push(Space::new(Length::Fill, Length::Shrink)).push(
+                button(text("Connect")).on_press_maybe(
+                    (status == Some(ContainerStateStatusEnum::RUNNING))
+                        .then(|| AppMsg::ConnectRdp(service.id.clone())),
+                ),
+            )
+        }
+    };
+
+    content.into()
+}
+
+fn shutdown_view(progress: &ShutdownProgress) -> AppElement<'_> {
+    let line = row![Spinner::new(), text(format!(" {progress}..."))].align_y(Alignment::Center);
+
+    match progress {
+        ShutdownProgress::Waiting {
+            elapsed,
+            grace_period,
+            ..
+        } => column![
+            line,
+            progress_bar(0.0..=grace_period.as_secs_f32(), elapsed.as_secs_f32()),
+        ]
+        .spacing(5)
+        .into(),
+        _ => line.into(),
+    }
+}
+
+/// Each layer of the image being pulled, listed like `docker pull` does.
+pub fn pull_view(progress: &PullProgress) -> AppElement<'_> {
+    let layers = progress
+        .layers
+        .iter()
+        .fold(column![].spacing(2), |col, layer| {
+            col.push(
+                text(layer.to_string())
+                    .font(Font::MONOSPACE)
+                    .size(12)
+                    .style(match layer.done() {
+                        true => text::secondary,
+                        false => text::default,
+                    }),
+            )
+        });
+
+    column![
+        row![
+            text(progress.to_string()),
+            Space::new(Length::Fill, Length::Shrink),
+            text(format!("{:.0}%", progress.fraction() * 100.0)),
+        ],
+        progress_bar(0.0..=1.0, progress.fraction()),
+        layers,
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// QEMU's own controls, only a running container has a VM to talk to.
+fn vm_view<'a>(
+    service: &'a DockerServiceState,
+    docker: &'a DockerModule,
+    status: Option<ContainerStateStatusEnum>,
+    idle: bool,
+) -> AppElement<'a> {
+    if status != Some(ContainerStateStatusEnum::RUNNING) {
+        return Space::new(Length::Shrink, Length::Shrink).into();
+    }
+
+    let vm_status = docker.vm_status.get(&service.container_name);
+    let running = docker.vm_commands.get(&service.container_name);
+    let idle = idle && running.is_none();
+
+    let label = match vm_status {
+        Some(Ok(status)) => text(format!("VM {status}")),
+        Some(Err(err)) => text(format!("VM status unknown: {err}")).style(text::danger),
+        None => text("VM status unknown").style(text::secondary),
+    };
+
+    let commands = VmCommand::ALL
+        .into_iter()
+        .fold(row![].spacing(10), |row, command| {
+            let available = matches!(vm_status, Some(Ok(status)) if command.available(status));
+
+            row.push(
+                button(text(command.label()))
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        (idle && available).then(|| AppMsg::VmCommand(service.id.clone(), command)),
+                    ),
+            )
+        });
+
+    row![
+        label,
+        Space::new(Length::Fill, Length::Shrink),
+        commands,
+        match running {
+            Some(_) => button(Spinner::new()),
+            None =>
+                button(nerd::fa_rotate_right()).on_press(AppMsg::QueryVmStatus(service.id.clone())),
+        }
+        .style(button::secondary),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
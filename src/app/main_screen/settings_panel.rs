@@ -0,0 +1,202 @@
+use std::fmt::Display;
+
+use iced::{
+    Alignment, Length,
+    widget::{column, pick_list, row, slider, text, text_input},
+};
+
+use crate::{
+    app::{AppElement, AppMsg},
+    controller::{
+        host::HostResources,
+        state::{DockurrVar, Environment, LANGUAGES, LOCALES, Size, VERSIONS},
+    },
+};
+
+/// Windows doesn't install with less.
+const MIN_RAM_MIB: u32 = 1024;
+const RAM_STEP_MIB: u32 = 256;
+
+#[derive(Debug, Clone)]
+pub enum SettingsMsg {
+    Set(DockurrVar, String),
+}
+
+/// Typed editor for the variables `dockurr/windows` understands, writing straight into the
+/// service's environment.
+pub struct SettingsPanel;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VersionChoice {
+    value: &'static str,
+    name: &'static str,
+}
+
+impl Display for VersionChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.value)
+    }
+}
+
+impl SettingsPanel {
+    pub fn update(env: &mut Environment, msg: SettingsMsg) {
+        match msg {
+            SettingsMsg::Set(var, value) => env.set(var.key(), value),
+        }
+    }
+
+    pub fn view<'a>(
+        &self,
+        env: &'a Environment,
+        host: &'a HostResources,
+        on_msg: impl Fn(SettingsMsg) -> AppMsg + Copy + 'a,
+    ) -> AppElement<'a> {
+        let value = |var: DockurrVar| {
+            env.get(var.key())
+                .or_else(|| var.image_default())
+                .unwrap_or_default()
+        };
+        let set = move |var: DockurrVar| move |x: String| on_msg(SettingsMsg::Set(var, x));
+
+        let versions = VERSIONS
+            .iter()
+            .map(|&(value, name)| VersionChoice { value, name })
+            .collect::<Vec<_>>();
+        let version = versions
+            .iter()
+            .find(|v| v.value == value(DockurrVar::Version))
+            .copied();
+
+        // In MiB, so sizes like 1536M are shown and kept as they are
+        let ram = value(DockurrVar::RamSize)
+            .parse::<Size>()
+            .map(|size| u32::try_from(size.mib()).unwrap_or(u32::MAX))
+            .unwrap_or(MIN_RAM_MIB);
+        let max_ram = u32::try_from(host.memory.mib())
+            .unwrap_or(u32::MAX)
+            .max(MIN_RAM_MIB);
+        let cpus = value(DockurrVar::CpuCores).parse::<u32>().unwrap_or(1);
+
+        let locale = |var: DockurrVar| {
+            pick_list(
+                LOCALES,
+                LOCALES.iter().find(|l| **l == value(var)),
+                move |x: &str| on_msg(SettingsMsg::Set(var, x.into())),
+            )
+        };
+
+        column![
+            field(
+                env,
+                host,
+                DockurrVar::Version,
+                column![
+                    pick_list(versions, version, move |x: VersionChoice| on_msg(
+                        SettingsMsg::Set(DockurrVar::Version, x.value.into())
+                    ))
+                    .width(Length::Fill),
+                    text_input("... or a link to a custom ISO", value(DockurrVar::Version))
+                        .on_input(set(DockurrVar::Version)),
+                ]
+                .spacing(5)
+            ),
+            field(
+                env,
+                host,
+                DockurrVar::RamSize,
+                row![
+                    slider(
+                        MIN_RAM_MIB..=max_ram,
+                        ram.clamp(MIN_RAM_MIB, max_ram),
+                        move |x| on_msg(SettingsMsg::Set(
+                            DockurrVar::RamSize,
+                            Size::from_mib(x.into()).to_string()
+                        ))
+                    )
+                    .step(RAM_STEP_MIB),
+                    text(format!("{} / {}", value(DockurrVar::RamSize), host.memory)),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center)
+            ),
+            field(
+                env,
+                host,
+                DockurrVar::CpuCores,
+                row![
+                    slider(1..=host.cpus, cpus.min(host.cpus), move |x| on_msg(
+                        SettingsMsg::Set(DockurrVar::CpuCores, x.to_string())
+                    )),
+                    text(format!("{cpus} / {}", host.cpus)),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center)
+            ),
+            field(
+                env,
+                host,
+                DockurrVar::DiskSize,
+                text_input("64G", value(DockurrVar::DiskSize)).on_input(set(DockurrVar::DiskSize))
+            ),
+            field(
+                env,
+                host,
+                DockurrVar::Language,
+                pick_list(
+                    LANGUAGES,
+                    LANGUAGES
+                        .iter()
+                        .find(|l| **l == value(DockurrVar::Language)),
+                    move |x: &str| on_msg(SettingsMsg::Set(DockurrVar::Language, x.into())),
+                )
+            ),
+            field(env, host, DockurrVar::Region, locale(DockurrVar::Region)),
+            field(
+                env,
+                host,
+                DockurrVar::Keyboard,
+                locale(DockurrVar::Keyboard)
+            ),
+            field(
+                env,
+                host,
+                DockurrVar::Username,
+                text_input("Docker", value(DockurrVar::Username))
+                    .on_input(set(DockurrVar::Username))
+            ),
+            field(
+                env,
+                host,
+                DockurrVar::Password,
+                text_input("admin", value(DockurrVar::Password))
+                    .secure(true)
+                    .on_input(set(DockurrVar::Password))
+            ),
+        ]
+        .spacing(10)
+        .into()
+    }
+}
+
+fn field<'a>(
+    env: &'a Environment,
+    host: &HostResources,
+    var: DockurrVar,
+    content: impl Into<AppElement<'a>>,
+) -> AppElement<'a> {
+    let mut col = column![
+        row![text(var.key()).width(Length::Fixed(120.0)), content.into()]
+            .spacing(10)
+            .align_y(Alignment::Center)
+    ]
+    .spacing(2);
+
+    if let Some(err) = env
+        .get(var.key())
+        .and_then(|value| var.validate(value, host).err())
+    {
+        col = col.push(text(err.to_string()).style(text::danger).size(12));
+    }
+
+    col.into()
+}
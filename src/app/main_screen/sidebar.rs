@@ -0,0 +1,93 @@
+use bollard::secret::ContainerStateStatusEnum;
+use iced::{
+    Alignment, Length,
+    widget::{Space, button, column, container, row, scrollable, text},
+};
+use iced_fonts::nerd;
+
+use crate::{
+    app::{AppElement, AppMsg},
+    controller::{
+        docker::{DockerContainerExt, DockerController},
+        state::StateModule,
+    },
+};
+
+/// List of the managed services, switching the one the main screen shows.
+pub struct Sidebar;
+
+impl Sidebar {
+    pub fn view<'a>(
+        &self,
+        state: &'a StateModule,
+        docker: &'a DockerController,
+        adding: bool,
+    ) -> AppElement<'a> {
+        let services = state
+            .services
+            .iter()
+            .fold(column![].spacing(5), |col, service| {
+                let status = docker
+                    .as_ref()
+                    .and_then(|d| d.container(&service.container_name))
+                    .and_then(|c| c.state_status());
+                let selected = !adding && state.selected.as_ref() == Some(&service.id);
+
+                let indicator = match status {
+                    Some(ContainerStateStatusEnum::RUNNING) => {
+                        nerd::fa_circle().style(text::success)
+                    }
+                    Some(
+                        ContainerStateStatusEnum::PAUSED | ContainerStateStatusEnum::RESTARTING,
+                    ) => nerd::fa_circle().style(text::warning),
+                    Some(_) => nerd::fa_circle(),
+                    None => nerd::fa_circle().style(text::danger),
+                };
+
+                col.push(
+                    button(
+                        row![
+                            indicator.size(10),
+                            column![
+                                text(&service.container_name),
+                                text(
+                                    status
+                                        .map(|s| s.to_string())
+                                        .unwrap_or_else(|| "missing".into())
+                                )
+                                .size(12),
+                            ]
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                    )
+                    .width(Length::Fill)
+                    .style(match selected {
+                        true => button::primary,
+                        false => button::text,
+                    })
+                    .on_press_maybe(
+                        (!selected).then(|| AppMsg::SelectDockerService(service.id.clone())),
+                    ),
+                )
+            });
+
+        container(
+            column![
+                scrollable(services).height(Length::Fill),
+                Space::new(Length::Shrink, Length::Fixed(10.0)),
+                button(text("Add Service"))
+                    .width(Length::Fill)
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        (!adding && !state.services_updating).then_some(AppMsg::AddDockerService)
+                    ),
+            ]
+            .width(Length::Fixed(200.0)),
+        )
+        .padding(10)
+        .height(Length::Fill)
+        .style(container::rounded_box)
+        .into()
+    }
+}
@@ -1,17 +1,35 @@
+pub mod engine_profiles;
+
 use iced::{
-    Length,
+    Alignment, Length,
     widget::{
         Space, button, center, column, container, horizontal_rule, rich_text, row, span, text,
+        text_input,
     },
 };
+use iced_aw::Spinner;
 use iced_fonts::{NERD_FONT, nerd};
 
 use crate::{
-    app::{AppElement, AppMsg},
-    controller::{docker::DockerController, kvm::KVMController, state::StateController},
+    app::{AppElement, AppMsg, setup_screen::engine_profiles::EngineProfiles},
+    controller::{
+        Controller, ControllerModule,
+        desktop::DesktopController,
+        docker::DockerController,
+        host::{Check, CheckLevel},
+        kvm::{KVMController, KVMModule},
+        preflight::PreflightController,
+        rdp::RdpController,
+        state::StateController,
+    },
 };
 
-pub struct SetupScreen;
+#[derive(Default)]
+pub struct SetupScreen {
+    /// Docker or Podman endpoint being typed in, empty to look for a socket.
+    pub endpoint: String,
+    pub profiles: EngineProfiles,
+}
 
 impl SetupScreen {
     pub fn view<'a>(
@@ -19,14 +37,28 @@ impl SetupScreen {
         state: &'a StateController,
         docker: &'a DockerController,
         kvm: &'a KVMController,
+        preflight: &'a PreflightController,
+        rdp: &'a RdpController,
+        desktop: &'a DesktopController,
     ) -> AppElement<'a> {
         center(
             column![
                 state.state_widget(),
                 horizontal_rule(2),
                 docker.state_widget(),
+                self.engine_view(state, docker),
+                horizontal_rule(2),
+                checks_view(kvm, |kvm| &kvm.checks),
                 horizontal_rule(2),
-                kvm.state_widget(),
+                checks_view(preflight, |preflight| &preflight.checks),
+                text("Checked against the default service, doesn't keep you from continuing")
+                    .size(12),
+                horizontal_rule(2),
+                rdp.state_widget(),
+                text("Optional, needed to connect to the desktop").size(12),
+                horizontal_rule(2),
+                desktop.state_widget(),
+                text("Optional, adds the Windows applications to the launcher").size(12),
                 horizontal_rule(2),
                 Space::new(Length::Shrink, Length::Fixed(40.0)),
                 row![
@@ -37,14 +69,19 @@ impl SetupScreen {
                         span(" Retry").size(20)
                     ])
                     .on_press_maybe(
-                        (!state.loading && !docker.loading && !kvm.loading)
+                        (!state.loading
+                            && !docker.loading
+                            && !kvm.loading
+                            && !preflight.loading
+                            && !rdp.loading
+                            && !desktop.loading)
                             .then_some(AppMsg::RetryInit)
                     ),
                     button(text("Next").size(20)).on_press_maybe(
                         ([
                             (state.loading, state.is_some()),
                             (docker.loading, docker.is_some()),
-                            (kvm.loading, kvm.is_some()),
+                            (kvm.loading, kvm.as_ref().is_some_and(KVMModule::usable)),
                         ]
                         .into_iter()
                         .all(|(loading, exists)| !loading && exists))
@@ -59,4 +96,89 @@ impl SetupScreen {
         .style(container::bordered_box)
         .into()
     }
+
+    /// The engine that answered, and where else to connect to.
+    fn engine_view<'a>(
+        &'a self,
+        state: &'a StateController,
+        docker: &'a DockerController,
+    ) -> AppElement<'a> {
+        let engine = match (docker.as_ref(), docker.loading) {
+            (Some(docker), _) => text(docker.engine.to_string()),
+            (None, true) => text("Connecting..."),
+            (None, false) => {
+                text("Neither Docker nor Podman answered, start one or set its endpoint")
+            }
+        };
+
+        let mut content = column![
+            engine.size(12).style(text::secondary),
+            row![
+                text_input("unix:///run/user/1000/podman/podman.sock", &self.endpoint)
+                    .on_input(AppMsg::DockerEndpoint)
+                    .on_submit(AppMsg::ConnectDocker),
+                button(text("Connect"))
+                    .on_press_maybe((!docker.loading).then_some(AppMsg::ConnectDocker)),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            text("Empty looks for Docker and Podman, rootless too, and honors DOCKER_HOST")
+                .size(12),
+        ]
+        .spacing(5);
+
+        if let Some(state) = state.as_ref() {
+            content = content.push(self.profiles.view(state, docker));
+        }
+
+        content.into()
+    }
+}
+
+/// Each check of the module with how to fix it, instead of a single mark.
+fn checks_view<'a, Module: ControllerModule>(
+    controller: &'a Controller<Module>,
+    checks: fn(&Module) -> &[Check],
+) -> AppElement<'a> {
+    let Some(checks) = controller.as_ref().map(checks) else {
+        return controller.state_widget();
+    };
+
+    let passed = checks.iter().all(|c| c.level != CheckLevel::Fail);
+    let status: AppElement = match (controller.loading, passed) {
+        (true, _) => Spinner::new().into(),
+        (false, true) => nerd::fa_check().style(text::success).into(),
+        (false, false) => nerd::cod_error().style(text::danger).into(),
+    };
+    let header = row![
+        text(Module::NAME),
+        Space::new(Length::Fill, Length::Shrink),
+        status
+    ]
+    .width(Length::Fill);
+
+    checks
+        .iter()
+        .fold(column![header].spacing(5), |col, check| {
+            col.push(check_view(check))
+        })
+        .into()
+}
+
+fn check_view(check: &Check) -> AppElement<'_> {
+    let icon = match check.level {
+        CheckLevel::Pass => nerd::fa_check().style(text::success),
+        CheckLevel::Warn => nerd::fa_circle().style(text::warning),
+        CheckLevel::Fail => nerd::cod_error().style(text::danger),
+    };
+
+    let mut details = column![text(format!("{}: {}", check.name, check.detail)).size(14)];
+    if let Some(remedy) = &check.remedy {
+        details = details.push(text(remedy).size(12).style(text::secondary));
+    }
+
+    row![icon, details]
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .into()
 }
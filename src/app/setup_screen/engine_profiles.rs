@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use iced::{
+    Alignment, Length,
+    widget::{Space, button, column, row, text, text_input},
+};
+use iced_fonts::nerd;
+
+use crate::{
+    app::{AppElement, AppMsg},
+    controller::{
+        docker::DockerController,
+        state::{EngineProfile, StateModule},
+    },
+};
+
+/// Remote engines to switch to, and the form to add another one.
+#[derive(Default)]
+pub struct EngineProfiles {
+    pub name: String,
+    pub endpoint: String,
+    /// Folder of the TLS certificates, empty without TLS.
+    pub cert_path: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum EngineProfileMsg {
+    Name(String),
+    Endpoint(String),
+    CertPath(String),
+}
+
+impl EngineProfiles {
+    pub fn update(&mut self, msg: EngineProfileMsg) {
+        self.error = None;
+
+        match msg {
+            EngineProfileMsg::Name(name) => self.name = name,
+            EngineProfileMsg::Endpoint(endpoint) => self.endpoint = endpoint,
+            EngineProfileMsg::CertPath(cert_path) => self.cert_path = cert_path,
+        }
+    }
+
+    /// Turns the form into a profile, clearing it if it's valid.
+    pub fn take_profile(&mut self, state: &StateModule) -> Option<EngineProfile> {
+        let profile = EngineProfile::new(
+            self.name.trim().to_string(),
+            self.endpoint.trim().to_string(),
+            Some(self.cert_path.trim())
+                .filter(|x| !x.is_empty())
+                .map(PathBuf::from),
+        );
+
+        let res = match state.profiles.iter().any(|p| p.name == profile.name) {
+            true => Err(format!("There already is a profile named {}", profile.name)),
+            false => profile.validate().map_err(|err| err.to_string()),
+        };
+
+        match res {
+            Ok(()) => {
+                *self = Self::default();
+                Some(profile)
+            }
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        state: &'a StateModule,
+        docker: &'a DockerController,
+    ) -> AppElement<'a> {
+        let idle = !docker.loading && !state.services_updating;
+
+        let profiles = state
+            .profiles
+            .iter()
+            .fold(column![].spacing(5), |col, profile| {
+                let in_use = state.profile.as_ref() == Some(&profile.id);
+
+                col.push(profile_view(profile, in_use, idle))
+            });
+
+        let msg = AppMsg::EngineProfileForm;
+
+        let mut content = column![
+            text("Remote engines").size(14),
+            profiles,
+            row![
+                text_input("Name", &self.name)
+                    .on_input(move |x| msg(EngineProfileMsg::Name(x)))
+                    .width(Length::FillPortion(2)),
+                text_input("tcp://workstation:2376 or ssh://user@workstation", &self.endpoint)
+                    .on_input(move |x| msg(EngineProfileMsg::Endpoint(x)))
+                    .width(Length::FillPortion(4)),
+                text_input("TLS certificates folder", &self.cert_path)
+                    .on_input(move |x| msg(EngineProfileMsg::CertPath(x)))
+                    .width(Length::FillPortion(3)),
+                button(nerd::fa_plus()).on_press_maybe(idle.then_some(AppMsg::AddEngineProfile)),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            text("Containers publish their ports on the remote machine, RDP and the console connect there")
+                .size(12),
+        ]
+        .spacing(5);
+
+        if let Some(err) = &self.error {
+            content = content.push(text(err).style(text::danger));
+        }
+
+        content.into()
+    }
+}
+
+fn profile_view(profile: &EngineProfile, in_use: bool, idle: bool) -> AppElement<'_> {
+    let mut line = row![
+        column![
+            text(&profile.name),
+            text(&profile.endpoint).size(12).style(text::secondary)
+        ],
+        Space::new(Length::Fill, Length::Shrink),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    line = match in_use {
+        true => line.push(nerd::fa_check().style(text::success)),
+        false => line.push(
+            button(text("Use"))
+                .on_press_maybe(idle.then(|| AppMsg::UseEngineProfile(profile.id.clone()))),
+        ),
+    };
+
+    line.push(
+        button(nerd::fa_trash())
+            .style(button::danger)
+            .on_press_maybe(idle.then(|| AppMsg::RemoveEngineProfile(profile.id.clone()))),
+    )
+    .into()
+}
@@ -0,0 +1,1024 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use bollard::secret::ContainerStateStatusEnum;
+use clap::{Parser, Subcommand};
+use color_eyre::{
+    Result,
+    eyre::{OptionExt, WrapErr, bail, eyre},
+};
+use directories::ProjectDirs;
+use iced::futures::{StreamExt, channel::mpsc};
+use serde::Serialize;
+
+use crate::controller::{
+    ControllerModule,
+    desktop::LaunchSnapshot,
+    docker::{
+        Connection, ContainerAction, DockerContainerExt, DockerModule, Engine, ShutdownProgress,
+    },
+    guest::GuestClient,
+    host::{Check, CheckLevel},
+    kvm::KVMModule,
+    preflight::Preflight,
+    qemu::{Monitor, VmCommand, VmStatus},
+    rdp::{RDP_PORT, RdpModule, clean_exit},
+    state::{
+        ComposeFile, DockerServiceState, EngineProfile, ShutdownRecord, StateModule, WindowsApp,
+    },
+    vnc::WEB_PORT,
+};
+
+/// Windows in a container, from the desktop or the shell. Opens the GUI without a command.
+///
+/// The state database can only be opened once, so commands need the GUI closed. `launch` is the
+/// exception, it falls back to what the desktop entries were last written with.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Print JSON instead of text.
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Show the services, their containers and whether KVM is usable.
+    Status,
+    /// List the services and their applications.
+    List,
+    /// Start the container of a service, the default one without a name.
+    Start { service: Option<String> },
+    /// Stop the container of a service, giving Windows its grace period.
+    Stop { service: Option<String> },
+    /// Pull the image of a service again, recreating its container if a newer one came down.
+    Update { service: Option<String> },
+    /// Run a catalogued application by name or id, opening the file with it.
+    Launch { app: String, file: Option<PathBuf> },
+    /// Write all services as a compose file, to stdout without a path.
+    Export { path: Option<PathBuf> },
+    /// Add the dockurr/windows services of a compose file, skipping containers that are
+    /// already managed.
+    Import { path: PathBuf },
+    /// Catalogue the applications the guest tools find in Windows.
+    Discover {
+        /// The service to add them to, the default one without a name.
+        service: Option<String>,
+        /// Reach the guest tools at this address instead of the service's container.
+        #[arg(long)]
+        address: Option<String>,
+        /// The token the guest tools at the address expect, the service's without it.
+        #[arg(long, requires = "address")]
+        token: Option<String>,
+    },
+    /// Talk to the guest tools inside Windows.
+    Guest {
+        /// The service whose guest tools to reach, the default one without a name.
+        #[arg(long)]
+        service: Option<String>,
+        /// Reach the guest tools at this address instead, `host:port`.
+        #[arg(long, conflicts_with = "service")]
+        address: Option<String>,
+        /// The token the guest tools at the address expect.
+        #[arg(long, requires = "address")]
+        token: Option<String>,
+
+        #[command(subcommand)]
+        request: GuestRequest,
+    },
+    /// Control the VM through QEMU's monitor, without involving Windows.
+    Vm {
+        /// The service whose VM to control, the default one without a name.
+        #[arg(long)]
+        service: Option<String>,
+        /// Reach QMP at this address instead, `host:port`.
+        #[arg(long, conflicts_with = "service")]
+        qmp: Option<String>,
+
+        #[command(subcommand)]
+        request: VmRequest,
+    },
+    /// Show the Docker or Podman engine in use, or change where to reach it.
+    Engine {
+        /// Reach it at `unix:///path/to/engine.sock` or `tcp://host:port`, saved once it answers.
+        #[arg(long)]
+        set: Option<String>,
+        /// Forget the endpoint and look for Docker and Podman on their usual sockets.
+        #[arg(long, conflicts_with = "set")]
+        discover: bool,
+        /// Switch to the remote engine of the named profile.
+        #[arg(long, conflicts_with_all = ["set", "discover"])]
+        profile: Option<String>,
+    },
+    /// Manage the profiles of remote engines.
+    Profile {
+        #[command(subcommand)]
+        request: ProfileRequest,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileRequest {
+    /// Show the profiles and which one is in use.
+    List,
+    /// Add a remote engine and switch to it, once it answers.
+    Add {
+        name: String,
+        /// `tcp://host:2376` or `ssh://user@host`.
+        endpoint: String,
+        /// Folder with the `ca.pem`, `cert.pem` and `key.pem` of TLS.
+        #[arg(long)]
+        cert_path: Option<PathBuf>,
+    },
+    /// Forget a profile, switching back to the local engine if it was in use.
+    Remove { name: String },
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum VmRequest {
+    /// Show whether the VM runs or is suspended.
+    Status,
+    /// Freeze the VM, QEMU keeps its memory.
+    Suspend,
+    /// Unfreeze a suspended VM.
+    Resume,
+    /// Press the power button.
+    Powerdown,
+    /// Reset the VM like its reset button would.
+    Reset,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GuestRequest {
+    /// Check that the guest tools answer.
+    Ping,
+    /// List the applications installed in Windows.
+    Apps,
+    /// List who is logged in to Windows.
+    Sessions,
+    /// Start a program in Windows, without showing it on the host.
+    Launch {
+        program: String,
+        args: Option<String>,
+    },
+    /// Shut Windows down.
+    Shutdown {
+        /// Close programs without letting them save.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the token the guest tools expect, to put next to them when there's no shared folder.
+    Token,
+}
+
+impl Command {
+    pub fn run(self, dirs: ProjectDirs, json: bool) -> Result<()> {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(async move {
+                match self {
+                    Self::Status => status(dirs, json).await,
+                    Self::List => list(dirs, json).await,
+                    Self::Start { service } => {
+                        action(dirs, json, service, ContainerAction::Start).await
+                    }
+                    Self::Stop { service } => {
+                        action(dirs, json, service, ContainerAction::Stop).await
+                    }
+                    Self::Update { service } => update(dirs, json, service).await,
+                    Self::Launch { app, file } => launch(dirs, app, file).await,
+                    Self::Export { path } => export(dirs, json, path).await,
+                    Self::Import { path } => import(dirs, json, path).await,
+                    Self::Discover {
+                        service,
+                        address,
+                        token,
+                    } => discover(dirs, json, service, address, token).await,
+                    Self::Guest {
+                        service,
+                        address,
+                        token,
+                        request,
+                    } => guest(dirs, json, service, address, token, request).await,
+                    Self::Vm {
+                        service,
+                        qmp,
+                        request,
+                    } => vm(dirs, json, service, qmp, request).await,
+                    Self::Engine {
+                        set,
+                        discover,
+                        profile,
+                    } => engine(dirs, json, set, discover, profile).await,
+                    Self::Profile { request } => profile(dirs, json, request).await,
+                }
+            })
+    }
+}
+
+#[derive(Serialize)]
+struct Status {
+    kvm: bool,
+    /// What keeps KVM from being usable or costs features, empty if all passed.
+    kvm_problems: Vec<Check>,
+    /// What the host lacks for the default service.
+    host_problems: Vec<Check>,
+    docker: bool,
+    engine: Option<Engine>,
+    services: Vec<ServiceStatus>,
+}
+
+#[derive(Serialize)]
+struct ServiceStatus {
+    name: String,
+    image: String,
+    default: bool,
+    /// `None` if the container doesn't exist.
+    state: Option<String>,
+    health: Option<String>,
+    rdp: Option<String>,
+    console: Option<String>,
+}
+
+async fn status(dirs: ProjectDirs, json: bool) -> Result<()> {
+    let state = load_state(dirs).await?;
+    let kvm = KVMModule::init_impl(()).await?;
+    let docker = DockerModule::init_impl(state.connection())
+        .await
+        .inspect_err(|err| tracing::warn!("Docker is not available: {err}"))
+        .ok();
+
+    let services = state
+        .services
+        .iter()
+        .map(|service| {
+            let container = docker
+                .as_ref()
+                .and_then(|d| d.container(&service.container_name));
+            let address = |port| {
+                container
+                    .and_then(|c| c.published_tcp(port))
+                    .map(|(host, port)| format!("{host}:{port}"))
+            };
+
+            ServiceStatus {
+                name: service.container_name.clone(),
+                image: service.image.clone(),
+                default: state.selected.as_ref() == Some(&service.id),
+                state: container
+                    .and_then(|c| c.state_status())
+                    .map(|s| s.to_string()),
+                health: container.and_then(|c| c.health()).map(|h| h.to_string()),
+                rdp: address(RDP_PORT),
+                console: address(WEB_PORT).map(|address| format!("http://{address}")),
+            }
+        })
+        .collect();
+
+    let status = Status {
+        kvm: kvm.usable(),
+        kvm_problems: problems(kvm.checks),
+        host_problems: problems(Preflight::new(state.service().cloned().unwrap_or_default()).run()),
+        docker: docker.is_some(),
+        engine: docker.map(|d| d.engine),
+        services,
+    };
+
+    print(json, &status, |status| {
+        let yes_no = |x| if x { "available" } else { "unavailable" };
+
+        let mut out = vec![format!("KVM: {}", yes_no(status.kvm))];
+
+        push_problems(&mut out, &status.kvm_problems);
+        match &status.engine {
+            Some(engine) => out.push(format!("Docker: {engine}")),
+            None => out.push(format!("Docker: {}", yes_no(status.docker))),
+        }
+        if !status.host_problems.is_empty() {
+            out.push("Host:".into());
+            push_problems(&mut out, &status.host_problems);
+        }
+
+        for service in &status.services {
+            let mut line = format!(
+                "{}{}: {}",
+                service.name,
+                if service.default { " (default)" } else { "" },
+                service.state.as_deref().unwrap_or("missing"),
+            );
+
+            if let Some(health) = &service.health {
+                line.push_str(&format!(", {health}"));
+            }
+            if let Some(rdp) = &service.rdp {
+                line.push_str(&format!(", RDP on {rdp}"));
+            }
+            if let Some(console) = &service.console {
+                line.push_str(&format!(", console at {console}"));
+            }
+
+            out.push(line);
+        }
+
+        out.join("\n")
+    })
+}
+
+/// Warnings and failures, worst first.
+fn problems(mut checks: Vec<Check>) -> Vec<Check> {
+    checks.retain(|c| c.level != CheckLevel::Pass);
+    checks.sort_by_key(|c| std::cmp::Reverse(c.level));
+    checks
+}
+
+fn push_problems(out: &mut Vec<String>, checks: &[Check]) {
+    for check in checks {
+        out.push(format!(
+            "  {} {}: {}",
+            check.level, check.name, check.detail
+        ));
+        if let Some(remedy) = &check.remedy {
+            out.push(format!("    {remedy}"));
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ServiceInfo {
+    name: String,
+    image: String,
+    apps: Vec<AppInfo>,
+}
+
+#[derive(Serialize)]
+struct AppInfo {
+    /// What `launch` and the desktop entries use.
+    id: String,
+    name: String,
+    program: String,
+}
+
+async fn list(dirs: ProjectDirs, json: bool) -> Result<()> {
+    let state = load_state(dirs).await?;
+
+    let services = state
+        .services
+        .iter()
+        .map(|service| ServiceInfo {
+            name: service.container_name.clone(),
+            image: service.image.clone(),
+            apps: state
+                .apps_of(&service.id)
+                .map(|app| AppInfo {
+                    id: app.slug(),
+                    name: app.name.clone(),
+                    program: app.program.clone(),
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    print(json, &services, |services| {
+        services
+            .iter()
+            .flat_map(|service| {
+                std::iter::once(format!("{} ({})", service.name, service.image)).chain(
+                    service
+                        .apps
+                        .iter()
+                        .map(|app| format!("  {} [{}] {}", app.name, app.id, app.program)),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+#[derive(Serialize)]
+struct ActionResult {
+    service: String,
+    action: String,
+    /// How Windows went down, for stops.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shutdown: Option<ShutdownRecord>,
+}
+
+async fn action(
+    dirs: ProjectDirs,
+    json: bool,
+    service: Option<String>,
+    action: ContainerAction,
+) -> Result<()> {
+    let mut state = load_state(dirs).await?;
+    let service = find_service(&state, service.as_deref())?.clone();
+    let docker = DockerModule::init_impl(state.connection()).await?;
+
+    let status = docker
+        .container(&service.container_name)
+        .and_then(|c| c.state_status());
+    if !action.available(status) {
+        bail!(
+            "Can't {action} {}, its container is {}",
+            service.container_name,
+            status
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "missing".into())
+        );
+    }
+
+    let shutdown = match action {
+        ContainerAction::Stop => {
+            let record = graceful_stop(&docker, &service)
+                .await
+                .wrap_err_with(|| format!("Failed to stop {}", service.container_name))?;
+            state.store_shutdown(record.clone()).await?;
+
+            Some(record)
+        }
+        _ => {
+            docker
+                .execute(&service, action)
+                .await
+                .wrap_err_with(|| format!("Failed to {action} {}", service.container_name))?;
+
+            None
+        }
+    };
+
+    let res = ActionResult {
+        service: service.container_name.clone(),
+        action: action.to_string(),
+        shutdown,
+    };
+
+    print(json, &res, |res| match &res.shutdown {
+        Some(record) => format!("{} {}: {}", res.action, res.service, record.describe()),
+        None => format!("{} {}: done", res.action, res.service),
+    })
+}
+
+#[derive(Serialize)]
+struct UpdateResult {
+    service: String,
+    image: String,
+    /// Whether a newer image came down and the container was recreated from it.
+    recreated: bool,
+}
+
+async fn update(dirs: ProjectDirs, json: bool, service: Option<String>) -> Result<()> {
+    let state = load_state(dirs).await?;
+    let service = find_service(&state, service.as_deref())?.clone();
+    let docker = DockerModule::init_impl(state.connection()).await?;
+
+    if docker.container(&service.container_name).is_none() {
+        bail!(
+            "Can't update {}, its container is missing",
+            service.container_name
+        );
+    }
+
+    let (progress, mut events) = mpsc::channel(2);
+
+    let (res, ()) = tokio::join!(docker.update_image(&service, Some(progress)), async {
+        // Layers report every chunk, a line whenever one finishes is enough here
+        let mut last_done = None;
+
+        while let Some(event) = events.next().await {
+            if last_done == Some(event.done()) {
+                continue;
+            }
+            last_done = Some(event.done());
+
+            eprintln!("{event}...");
+        }
+    });
+
+    let res = UpdateResult {
+        recreated: res.wrap_err_with(|| {
+            format!("Failed to update the image of {}", service.container_name)
+        })?,
+        service: service.container_name,
+        image: service.image,
+    };
+
+    print(json, &res, |res| match res.recreated {
+        true => format!(
+            "update {}: recreated from the newer {}",
+            res.service, res.image
+        ),
+        false => format!("update {}: {} is up to date", res.service, res.image),
+    })
+}
+
+/// Stops the container like the GUI does, with the progress on stderr.
+async fn graceful_stop(
+    docker: &DockerModule,
+    service: &DockerServiceState,
+) -> Result<ShutdownRecord> {
+    let (progress, mut events) = mpsc::channel(2);
+
+    let (res, ()) = tokio::join!(docker.graceful_stop(service, progress), async {
+        // Waiting is reported every second, every ten are enough here
+        let mut last_waited = None;
+
+        while let Some(event) = events.next().await {
+            if let ShutdownProgress::Waiting { elapsed, .. } = &event {
+                let waited = Some(elapsed.as_secs() / 10);
+
+                if waited == last_waited {
+                    continue;
+                }
+                last_waited = waited;
+            }
+
+            eprintln!("{event}...");
+        }
+    });
+
+    res
+}
+
+async fn launch(dirs: ProjectDirs, query: String, file: Option<PathBuf>) -> Result<()> {
+    let (app, service, connection) = match StateModule::init_impl(dirs.clone()).await {
+        Ok(mut state) => {
+            state.reload().await?;
+
+            let app = find_app(&state, &query)?;
+            let service = state
+                .find_service(&app.service)
+                .ok_or_eyre("The application's service is gone")?;
+
+            (app.clone(), service.clone(), state.connection())
+        }
+        // The GUI holds the database, desktop entries launch through their snapshot
+        Err(err) => {
+            let snapshot = LaunchSnapshot::read(&dirs, &query).map_err(|_| {
+                err.wrap_err("Failed to open the state, launch the app by its id while winjet runs")
+            })?;
+
+            (snapshot.app, snapshot.service, snapshot.connection)
+        }
+    };
+
+    let docker = DockerModule::init_impl(connection).await?;
+    let container = docker
+        .container(&service.container_name)
+        .filter(|c| c.state_status() == Some(ContainerStateStatusEnum::RUNNING))
+        .ok_or_else(|| eyre!("{} isn't running, start it first", service.container_name))?;
+
+    let rdp = RdpModule::init_impl(()).await?;
+    let command = rdp.app_command(&service, &app, Some(container), file.as_deref())?;
+    let kind = command.client.kind;
+
+    let status = command
+        .spawn(Stdio::inherit())
+        .await
+        .wrap_err_with(|| format!("Failed to start {kind}"))?
+        .wait()
+        .await?;
+
+    if !clean_exit(status) {
+        bail!("{kind} exited with {status}");
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Exported {
+    path: PathBuf,
+    services: usize,
+}
+
+async fn export(dirs: ProjectDirs, json: bool, path: Option<PathBuf>) -> Result<()> {
+    let state = load_state(dirs).await?;
+    let yaml = ComposeFile::from_services(&state.services).to_yaml()?;
+
+    let Some(path) = path else {
+        std::io::stdout().write_all(yaml.as_bytes())?;
+        return Ok(());
+    };
+
+    tokio::fs::write(&path, yaml).await?;
+
+    let res = Exported {
+        path,
+        services: state.services.len(),
+    };
+
+    print(json, &res, |res| {
+        format!(
+            "Exported {} services to {}",
+            res.services,
+            res.path.display()
+        )
+    })
+}
+
+async fn import(dirs: ProjectDirs, json: bool, path: PathBuf) -> Result<()> {
+    let mut state = load_state(dirs).await?;
+
+    let path = tokio::fs::canonicalize(&path).await?;
+    let yaml = tokio::fs::read_to_string(&path).await?;
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    let services = ComposeFile::parse(&yaml)?.into_services(dir)?;
+
+    let res = state.store_imported(services).await?;
+
+    print(json, &res, |res| {
+        res.iter()
+            .map(|x| match x.imported {
+                true => format!("Imported {}", x.name),
+                false => format!("Skipped {}, it's already managed", x.name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GuestAnswer {
+    Pong {
+        agent: String,
+        millis: u128,
+    },
+    Apps {
+        apps: Vec<winjet_gt_proto::InstalledApp>,
+    },
+    Sessions {
+        sessions: Vec<winjet_gt_proto::SessionInfo>,
+    },
+    Launched {
+        pid: u32,
+    },
+    ShuttingDown,
+    Token {
+        token: String,
+    },
+}
+
+async fn guest(
+    dirs: ProjectDirs,
+    json: bool,
+    service: Option<String>,
+    address: Option<String>,
+    token: Option<String>,
+    request: GuestRequest,
+) -> Result<()> {
+    let mut client = match address {
+        Some(address) => {
+            let token =
+                token.ok_or_eyre("Pass the --token the guest tools at the address expect")?;
+            GuestClient::connect(&address, &token).await?
+        }
+        None => {
+            let state = load_state(dirs).await?;
+            let service = find_service(&state, service.as_deref())?;
+
+            // Nothing to ask the guest tools, they might not even be installed yet
+            if let GuestRequest::Token = request {
+                let answer = GuestAnswer::Token {
+                    token: service.guest_token.clone(),
+                };
+                return print(json, &answer, |_| service.guest_token.clone());
+            }
+
+            connect_guest(&state, service).await?
+        }
+    };
+
+    let answer = match request {
+        GuestRequest::Ping => {
+            let rtt = client.ping().await?;
+            GuestAnswer::Pong {
+                agent: client.agent.clone(),
+                millis: rtt.as_millis(),
+            }
+        }
+        GuestRequest::Apps => GuestAnswer::Apps {
+            apps: client.list_apps().await?,
+        },
+        GuestRequest::Sessions => GuestAnswer::Sessions {
+            sessions: client.sessions().await?,
+        },
+        GuestRequest::Launch { program, args } => GuestAnswer::Launched {
+            pid: client.launch_app(&program, args.as_deref()).await?,
+        },
+        GuestRequest::Shutdown { force } => {
+            client.shutdown(force).await?;
+            GuestAnswer::ShuttingDown
+        }
+        GuestRequest::Token => bail!("Only services have a token, pick one with --service"),
+    };
+
+    print(json, &answer, |answer| match answer {
+        GuestAnswer::Pong { agent, millis } => format!("{agent} answered in {millis}ms"),
+        GuestAnswer::Apps { apps } => apps
+            .iter()
+            .map(|app| format!("{}: {}", app.name, app.program))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        GuestAnswer::Sessions { sessions } => sessions
+            .iter()
+            .map(|session| {
+                format!(
+                    "{} on {}{}",
+                    session.user,
+                    session.name.as_deref().unwrap_or("no session"),
+                    if session.active { " (active)" } else { "" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        GuestAnswer::Launched { pid } => format!("Started as process {pid}"),
+        GuestAnswer::ShuttingDown => "Windows is shutting down".into(),
+        GuestAnswer::Token { token } => token.clone(),
+    })
+}
+
+#[derive(Serialize)]
+struct VmAnswer {
+    /// `None` if the command was sent but the status couldn't be read after.
+    status: Option<String>,
+}
+
+async fn vm(
+    dirs: ProjectDirs,
+    json: bool,
+    service: Option<String>,
+    qmp: Option<String>,
+    request: VmRequest,
+) -> Result<()> {
+    let monitor = match qmp {
+        Some(address) => Monitor::Qmp(address),
+        None => {
+            let state = load_state(dirs).await?;
+            let service = find_service(&state, service.as_deref())?;
+            let docker = DockerModule::init_impl(state.connection()).await?;
+
+            if docker
+                .container(&service.container_name)
+                .and_then(|c| c.state_status())
+                != Some(ContainerStateStatusEnum::RUNNING)
+            {
+                bail!("{} isn't running, start it first", service.container_name);
+            }
+
+            docker.monitor(&service.container_name)
+        }
+    };
+
+    let command = match request {
+        VmRequest::Status => None,
+        VmRequest::Suspend => Some(VmCommand::Stop),
+        VmRequest::Resume => Some(VmCommand::Cont),
+        VmRequest::Powerdown => Some(VmCommand::SystemPowerdown),
+        VmRequest::Reset => Some(VmCommand::SystemReset),
+    };
+
+    let status = match command {
+        None => Some(monitor.status().await?),
+        Some(command) => {
+            monitor.execute(command).await?;
+            monitor
+                .status()
+                .await
+                .inspect_err(|err| tracing::warn!("Failed to read the VM status: {err}"))
+                .ok()
+        }
+    };
+
+    let answer = VmAnswer {
+        status: status.as_ref().map(VmStatus::to_string),
+    };
+
+    print(json, &answer, |answer| match &answer.status {
+        Some(status) => format!("The VM is {status}"),
+        None => "Sent, the VM status is unknown".into(),
+    })
+}
+
+#[derive(Serialize)]
+struct Discovered {
+    service: String,
+    found: usize,
+    /// Names of the apps that weren't catalogued yet.
+    added: Vec<String>,
+}
+
+async fn discover(
+    dirs: ProjectDirs,
+    json: bool,
+    service: Option<String>,
+    address: Option<String>,
+    token: Option<String>,
+) -> Result<()> {
+    let mut state = load_state(dirs).await?;
+    let service = find_service(&state, service.as_deref())?.clone();
+
+    let mut client = match address {
+        Some(address) => {
+            let token = token.as_deref().unwrap_or(&service.guest_token);
+            GuestClient::connect(&address, token).await?
+        }
+        None => connect_guest(&state, &service).await?,
+    };
+    let found = client.list_apps().await?;
+
+    let res = Discovered {
+        service: service.container_name,
+        found: found.len(),
+        added: state.store_discovered(service.id, found).await?,
+    };
+
+    print(json, &res, |res| {
+        std::iter::once(format!(
+            "Found {} applications in {}, {} are new",
+            res.found,
+            res.service,
+            res.added.len()
+        ))
+        .chain(res.added.iter().map(|name| format!("  {name}")))
+        .collect::<Vec<_>>()
+        .join("\n")
+    })
+}
+
+/// The guest tools of the service, whose container has to be running.
+async fn connect_guest(state: &StateModule, service: &DockerServiceState) -> Result<GuestClient> {
+    let docker = DockerModule::init_impl(state.connection()).await?;
+    let container = docker
+        .container(&service.container_name)
+        .filter(|c| c.state_status() == Some(ContainerStateStatusEnum::RUNNING))
+        .ok_or_else(|| eyre!("{} isn't running, start it first", service.container_name))?;
+
+    GuestClient::connect_to(container, service).await
+}
+
+/// Connects before saving, so a wrong endpoint or profile isn't remembered.
+async fn engine(
+    dirs: ProjectDirs,
+    json: bool,
+    set: Option<String>,
+    discover: bool,
+    profile: Option<String>,
+) -> Result<()> {
+    let mut state = load_state(dirs).await?;
+    let profile = profile
+        .map(|name| find_profile(&state, &name).cloned())
+        .transpose()?;
+
+    let connection = match (&set, discover, &profile) {
+        (Some(endpoint), _, _) => Some(Connection::new(endpoint.clone())),
+        (None, true, _) => None,
+        (None, false, Some(profile)) => Some(profile.connection()),
+        (None, false, None) => state.connection(),
+    };
+
+    let docker = DockerModule::init_impl(connection).await?;
+
+    match (set, discover, profile) {
+        (Some(endpoint), _, _) => state.store_docker_endpoint(Some(endpoint)).await?,
+        (None, true, _) => state.store_docker_endpoint(None).await?,
+        (None, false, Some(profile)) => state.store_selected_profile(profile.id).await?,
+        (None, false, None) => {}
+    }
+
+    print(json, &docker.engine, |engine| engine.to_string())
+}
+
+#[derive(Serialize)]
+struct ProfileInfo {
+    name: String,
+    endpoint: String,
+    cert_path: Option<PathBuf>,
+    in_use: bool,
+}
+
+async fn profile(dirs: ProjectDirs, json: bool, request: ProfileRequest) -> Result<()> {
+    let mut state = load_state(dirs).await?;
+
+    match request {
+        ProfileRequest::List => {}
+        ProfileRequest::Add {
+            name,
+            endpoint,
+            cert_path,
+        } => {
+            if state.profiles.iter().any(|p| p.name == name) {
+                bail!("There already is a profile named {name}");
+            }
+
+            let profile = EngineProfile::new(name, endpoint, cert_path);
+            profile.validate()?;
+
+            DockerModule::init_impl(Some(profile.connection())).await?;
+            state.store_profile(profile).await?;
+        }
+        ProfileRequest::Remove { name } => {
+            let id = find_profile(&state, &name)?.id.clone();
+            state.delete_profile(id).await?;
+        }
+    }
+
+    let profiles = state
+        .profiles
+        .iter()
+        .map(|profile| ProfileInfo {
+            name: profile.name.clone(),
+            endpoint: profile.endpoint.clone(),
+            cert_path: profile.cert_path.clone(),
+            in_use: state.profile.as_ref() == Some(&profile.id),
+        })
+        .collect::<Vec<_>>();
+
+    print(json, &profiles, |profiles| {
+        if profiles.is_empty() {
+            return "No profiles, the local engine is in use".into();
+        }
+
+        profiles
+            .iter()
+            .map(|profile| {
+                format!(
+                    "{}{}: {}{}",
+                    profile.name,
+                    if profile.in_use { " (in use)" } else { "" },
+                    profile.endpoint,
+                    match &profile.cert_path {
+                        Some(certs) => format!(", TLS with {}", certs.display()),
+                        None => String::new(),
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+async fn load_state(dirs: ProjectDirs) -> Result<StateModule> {
+    let mut state = StateModule::init_impl(dirs).await.wrap_err(
+        "Failed to open the state, close the winjet window while using the command line",
+    )?;
+    state.reload().await?;
+
+    Ok(state)
+}
+
+/// By container name, the default service without one.
+fn find_service<'a>(state: &'a StateModule, name: Option<&str>) -> Result<&'a DockerServiceState> {
+    match name {
+        Some(name) => state
+            .services
+            .iter()
+            .find(|s| s.container_name == name)
+            .ok_or_else(|| eyre!("No service manages a container named {name}")),
+        None => state
+            .service()
+            .ok_or_eyre("There are no services, add one first"),
+    }
+}
+
+fn find_profile<'a>(state: &'a StateModule, name: &str) -> Result<&'a EngineProfile> {
+    state
+        .profiles
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| eyre!("No profile is named {name}"))
+}
+
+/// By id, or by name if that's unambiguous.
+fn find_app<'a>(state: &'a StateModule, query: &str) -> Result<&'a WindowsApp> {
+    if let Some(app) = state.apps.iter().find(|app| app.slug() == query) {
+        return Ok(app);
+    }
+
+    let matches = state
+        .apps
+        .iter()
+        .filter(|app| app.name.eq_ignore_ascii_case(query))
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [app] => Ok(app),
+        [] => bail!("No application named {query}"),
+        _ => bail!("Several applications are named {query}, use the id from `winjet list`"),
+    }
+}
+
+fn print<T: Serialize>(json: bool, value: &T, text: impl FnOnce(&T) -> String) -> Result<()> {
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(value)?),
+        false => println!("{}", text(value)),
+    }
+
+    Ok(())
+}
@@ -1,6 +1,13 @@
+pub mod desktop;
 pub mod docker;
+pub mod guest;
+pub mod host;
 pub mod kvm;
+pub mod preflight;
+pub mod qemu;
+pub mod rdp;
 pub mod state;
+pub mod vnc;
 
 use std::sync::Arc;
 
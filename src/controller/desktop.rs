@@ -0,0 +1,299 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use color_eyre::{Result, eyre::OptionExt};
+use directories::{BaseDirs, ProjectDirs};
+use iced::futures::FutureExt;
+use image::{GenericImageView, ImageFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{AppMsg, AppTask},
+    controller::{
+        Controller, ControllerModule,
+        docker::Connection,
+        state::{DockerServiceState, StateModule, WindowsApp},
+    },
+    util::{Arced, find_executable},
+};
+
+pub type DesktopController = Controller<DesktopModule>;
+
+/// Prefix of the entries we own in the shared applications directory.
+const ENTRY_PREFIX: &str = "winjet-";
+/// Launchers don't show anything bigger.
+const ICON_SIZE: u32 = 256;
+
+/// Keeps a freedesktop `.desktop` entry for every catalogued [`WindowsApp`].
+#[derive(Debug)]
+pub struct DesktopModule {
+    /// `$XDG_DATA_HOME/applications`, where launchers look for entries.
+    pub applications: PathBuf,
+    /// Converted app icons, inside our own data dir.
+    pub icons: PathBuf,
+    /// [`LaunchSnapshot`]s of the entries.
+    pub launchers: PathBuf,
+    /// What the entries run, `winjet launch <app>`.
+    pub exe: PathBuf,
+}
+
+impl ControllerModule for DesktopModule {
+    const NAME: &str = "Desktop Entries";
+
+    type Init = ProjectDirs;
+
+    async fn init_impl(dirs: ProjectDirs) -> Result<Self> {
+        let base = BaseDirs::new().ok_or_eyre("Failed to find the XDG data directory")?;
+
+        let applications = base.data_dir().join("applications");
+        let icons = dirs.data_dir().join("icons");
+        let launchers = launchers_dir(&dirs);
+
+        tokio::fs::create_dir_all(&applications).await?;
+        tokio::fs::create_dir_all(&icons).await?;
+        tokio::fs::create_dir_all(&launchers).await?;
+
+        Ok(Self {
+            applications,
+            icons,
+            launchers,
+            exe: std::env::current_exe()?,
+        })
+    }
+}
+
+impl DesktopModule {
+    /// Rewrites the entries of all catalogued apps and removes the ones left from removed apps.
+    pub fn sync(&self, state: &StateModule) -> AppTask {
+        let entries = state
+            .apps
+            .iter()
+            .filter_map(|app| {
+                let service = state.find_service(&app.service)?;
+                Some(LaunchSnapshot {
+                    app: app.clone(),
+                    service: service.clone(),
+                    connection: state.connection(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let applications = self.applications.clone();
+        let icons = self.icons.clone();
+        let launchers = self.launchers.clone();
+        let exe = self.exe.clone();
+
+        AppTask::perform(
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    write_entries(&applications, &icons, &launchers, &exe, &entries)
+                })
+                .await?
+            }
+            .map(Arced::arced),
+            AppMsg::SyncDesktopEntriesRes,
+        )
+    }
+}
+
+/// What an entry launches, so it works while the GUI holds the state database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchSnapshot {
+    pub app: WindowsApp,
+    pub service: DockerServiceState,
+    /// The engine the service runs on.
+    #[serde(default)]
+    pub connection: Option<Connection>,
+}
+
+impl LaunchSnapshot {
+    /// The snapshot the desktop entry of the app was last written with.
+    pub fn read(dirs: &ProjectDirs, slug: &str) -> Result<Self> {
+        let json = fs::read_to_string(launchers_dir(dirs).join(format!("{slug}.json")))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        let app = WindowsApp {
+            icon: None,
+            ..self.app.clone()
+        };
+        let snapshot = Self {
+            app,
+            service: self.service.clone(),
+            connection: self.connection.clone(),
+        };
+
+        // The service's environment has the Windows password in it
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // The mode only applies to new files, older snapshots were readable by everyone
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(serde_json::to_string(&snapshot)?.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn render(&self, exe: &Path, icon: Option<&Path>) -> String {
+        let app = &self.app;
+
+        let mut exec = format!("{} launch {}", exec_arg(&exe.to_string_lossy()), app.slug());
+        if !app.mime_types.is_empty() {
+            exec.push_str(" %f");
+        }
+
+        let mut lines = vec![
+            "[Desktop Entry]".to_string(),
+            "Type=Application".into(),
+            format!("Name={}", escape_value(&app.name)),
+            format!(
+                "Comment={}",
+                escape_value(&format!("Runs on {}", self.service.container_name))
+            ),
+            format!("Exec={}", escape_value(&exec)),
+            format!("StartupWMClass={}", escape_value(&app.name)),
+            "Terminal=false".into(),
+        ];
+
+        if let Some(icon) = icon {
+            lines.push(format!("Icon={}", escape_value(&icon.to_string_lossy())));
+        }
+
+        if !app.mime_types.is_empty() {
+            lines.push(format!("MimeType={};", app.mime_types.join(";")));
+        }
+
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+fn launchers_dir(dirs: &ProjectDirs) -> PathBuf {
+    dirs.data_dir().join("launchers")
+}
+
+fn write_entries(
+    applications: &Path,
+    icons: &Path,
+    launchers: &Path,
+    exe: &Path,
+    entries: &[LaunchSnapshot],
+) -> Result<()> {
+    let mut kept = HashSet::new();
+
+    for entry in entries {
+        let slug = entry.app.slug();
+
+        let icon = entry.app.icon.as_deref().and_then(|bytes| {
+            let path = icons.join(format!("{slug}.png"));
+
+            // A broken icon shouldn't cost the app its entry
+            convert_icon(bytes, &path)
+                .inspect_err(|err| {
+                    tracing::warn!("Failed to convert the icon of {}: {err}", entry.app.name)
+                })
+                .ok()
+                .map(|_| path)
+        });
+
+        let file = format!("{ENTRY_PREFIX}{slug}.desktop");
+        fs::write(applications.join(&file), entry.render(exe, icon.as_deref()))?;
+        entry.write(&launchers.join(format!("{slug}.json")))?;
+
+        kept.insert(file);
+        kept.insert(format!("{slug}.json"));
+        if icon.is_some() {
+            kept.insert(format!("{slug}.png"));
+        }
+    }
+
+    remove_stale(applications, &kept, |name| {
+        name.starts_with(ENTRY_PREFIX) && name.ends_with(".desktop")
+    })?;
+    remove_stale(icons, &kept, |name| name.ends_with(".png"))?;
+    remove_stale(launchers, &kept, |name| name.ends_with(".json"))?;
+
+    update_mime_cache(applications);
+
+    Ok(())
+}
+
+/// Decodes whatever the guest gave us (ICO, PNG, BMP) into a PNG launchers can show.
+fn convert_icon(bytes: &[u8], path: &Path) -> Result<()> {
+    let mut icon = image::load_from_memory(bytes)?;
+
+    let (width, height) = icon.dimensions();
+    if width > ICON_SIZE || height > ICON_SIZE {
+        icon = icon.thumbnail(ICON_SIZE, ICON_SIZE);
+    }
+
+    icon.save_with_format(path, ImageFormat::Png)?;
+
+    Ok(())
+}
+
+fn remove_stale(dir: &Path, kept: &HashSet<String>, owned: impl Fn(&str) -> bool) -> Result<()> {
+    for file in fs::read_dir(dir)? {
+        let file = file?;
+        let name = file.file_name().to_string_lossy().to_string();
+
+        if owned(&name) && !kept.contains(&name) {
+            tracing::debug!("Removing stale {}", file.path().display());
+            fs::remove_file(file.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Registers the `MimeType` keys of the entries, the desktop picks them up from the cache.
+fn update_mime_cache(applications: &Path) {
+    let Some(tool) = find_executable("update-desktop-database") else {
+        tracing::debug!("update-desktop-database not found, skipping the MIME cache");
+        return;
+    };
+
+    match Command::new(tool).arg(applications).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => tracing::warn!("update-desktop-database exited with {status}"),
+        Err(err) => tracing::warn!("Failed to run update-desktop-database: {err}"),
+    }
+}
+
+/// Escapes a string value of a desktop entry.
+fn escape_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+/// Quotes an argument of the `Exec` key, before the value itself gets escaped.
+fn exec_arg(arg: &str) -> String {
+    let mut quoted = String::from('"');
+
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
@@ -1,68 +1,768 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, sync::Arc};
 
 use bollard::{
     Docker,
-    query_parameters::{InspectContainerOptions, ListContainersOptionsBuilder},
-    secret::{ContainerInspectResponse, ContainerSummary, DeviceMapping, Port, RestartPolicy},
+    query_parameters::{
+        InspectContainerOptions, ListContainersOptionsBuilder, RemoveContainerOptionsBuilder,
+        RestartContainerOptionsBuilder, StartContainerOptions, StopContainerOptions,
+        StopContainerOptionsBuilder,
+    },
+    secret::{
+        ContainerCreateBody, ContainerInspectResponse, ContainerStateStatusEnum, ContainerSummary,
+        DeviceMapping, HealthStatusEnum, HostConfig, Port, PortBinding, PortTypeEnum,
+        RestartPolicy, RestartPolicyNameEnum,
+    },
 };
 use color_eyre::Result;
 use derive_more::AsRef;
+use iced::futures::{FutureExt, SinkExt, StreamExt, channel::mpsc, future::BoxFuture};
 use tokio::task::JoinSet;
 
 use crate::{
-    app::{AppMsg, AppRenderer, AppTheme},
-    controller::{Controller, ControllerModule, state::DockerServiceState},
+    app::{AppMsg, AppRenderer, AppSubscription, AppTask, AppTheme},
+    controller::{
+        Controller, ControllerModule,
+        guest::guest_address,
+        qemu::{Monitor, VmCommand, VmStatus},
+        state::{DockerServiceState, Environment, GracePeriod, ShutdownRecord},
+    },
+    util::Arced,
 };
 
+mod apply;
+mod engine;
+mod events;
+mod exec;
+mod logs;
+mod pull;
+mod shutdown;
+
+pub use apply::ConfigChange;
+pub use engine::{Connection, Engine, EngineKind};
+pub use events::{ContainerEvent, is_windows_image};
+pub use exec::exec;
+pub use logs::{BootLog, BootProgress, BootStage, LogEvent};
+pub use pull::{ImageRef, LayerProgress, PullProgress};
+pub use shutdown::ShutdownProgress;
+
 pub type DockerController = Controller<DockerModule>;
 
 #[derive(Debug)]
 pub struct DockerModule {
     client: Docker,
+    pub engine: Engine,
 
     pub containers: Vec<ContainerData>,
+
+    /// Running actions by container name.
+    pub actions: HashMap<String, ContainerAction>,
+    pub action_errors: HashMap<String, String>,
+    /// How far the graceful stops of containers got, by container name.
+    pub shutdowns: HashMap<String, ShutdownProgress>,
+    /// The last image pulls of containers by container name, kept once done for their outcome.
+    pub pulls: HashMap<String, PullProgress>,
+    /// Running QEMU commands by container name.
+    pub vm_commands: HashMap<String, VmCommand>,
+    /// What QEMU last said about its VM, by container name.
+    pub vm_status: HashMap<String, Result<VmStatus, String>>,
+
+    pub boot_logs: HashMap<String, BootLog>,
 }
 
 impl ControllerModule for DockerModule {
     const NAME: &str = "Docker";
 
-    type Init = ();
-
-    async fn init_impl(_: Self::Init) -> Result<Self> {
-        let client = Docker::connect_with_defaults()?;
-
-        let containers = client
-            .list_containers(Some(
-                ListContainersOptionsBuilder::new()
-                    .all(true)
-                    .filters(&HashMap::from_iter([("ancestor", vec!["dockurr/windows"])]))
-                    .build(),
-            ))
-            .await?
-            .into_iter()
-            .fold(JoinSet::new(), |mut join_set, summary| {
-                let client = client.clone();
-
-                join_set.spawn(async move {
-                    let specs = client
-                        .inspect_container(&summary.name(), Option::<InspectContainerOptions>::None)
-                        .await?;
-
-                    Result::Ok(ContainerData { summary, specs })
-                });
-                join_set
+    /// The engine to use, a well-known socket without one.
+    type Init = Option<Connection>;
+
+    async fn init_impl(connection: Self::Init) -> Result<Self> {
+        let (client, engine) = engine::connect(connection.as_ref()).await?;
+        let containers = list_containers(&client, engine.remote_host.as_deref()).await?;
+
+        tracing::info!("Connected to {engine}");
+
+        Ok(Self {
+            client,
+            engine,
+            containers,
+
+            actions: HashMap::new(),
+            action_errors: HashMap::new(),
+            shutdowns: HashMap::new(),
+            pulls: HashMap::new(),
+            vm_commands: HashMap::new(),
+            vm_status: HashMap::new(),
+
+            boot_logs: HashMap::new(),
+        })
+    }
+}
+
+impl DockerModule {
+    pub fn refresh_containers(&self) -> AppTask {
+        let client = self.client.clone();
+        let remote_host = self.engine.remote_host.clone();
+
+        AppTask::perform(
+            async move {
+                list_containers(&client, remote_host.as_deref())
+                    .await
+                    .arced()
+            },
+            AppMsg::RefreshDockerContainersRes,
+        )
+    }
+
+    pub fn subscription(&self) -> AppSubscription {
+        events::subscription(self.client.clone(), self.engine.clone())
+    }
+
+    /// Follows the log of the named container, restarting whenever the container does.
+    pub fn logs_subscription(&self, container_name: &str) -> AppSubscription {
+        let Some(started_at) = self
+            .container(container_name)
+            .and_then(|c| c.specs.state.as_ref())
+            .and_then(|s| s.started_at.clone())
+        else {
+            return AppSubscription::none();
+        };
+
+        logs::subscription(self.client.clone(), container_name.into(), started_at)
+    }
+
+    pub fn apply_log(&mut self, container_name: String, event: LogEvent) {
+        self.boot_logs
+            .entry(container_name)
+            .or_default()
+            .apply(event);
+    }
+
+    pub fn apply_event(&mut self, event: Arc<Result<ContainerEvent>>) {
+        let event = match Arc::into_inner(event).expect("Logic error!") {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::error!("Failed to process docker event: {err}");
+                return;
+            }
+        };
+
+        match event {
+            ContainerEvent::Updated(data) => match self
+                .containers
+                .iter_mut()
+                .find(|c| c.summary.id == data.summary.id)
+            {
+                Some(existing) => *existing = data,
+                None => self.containers.push(data),
+            },
+            ContainerEvent::Removed(id) => self
+                .containers
+                .retain(|c| c.summary.id.as_deref() != Some(id.as_str())),
+        }
+    }
+
+    pub fn set_containers(&mut self, res: Arc<Result<Vec<ContainerData>>>) {
+        match Arc::into_inner(res).expect("Logic error!") {
+            Ok(containers) => self.containers = containers,
+            Err(err) => tracing::error!("Failed to refresh containers: {err}"),
+        }
+    }
+
+    /// Creates the container described by `service` and starts it right away, pulling its image
+    /// first if the engine doesn't have it yet.
+    pub fn create_service(&mut self, service: DockerServiceState) -> AppTask {
+        let client = self.client.clone();
+        let engine = self.engine.clone();
+        let container_name = service.container_name.clone();
+        let (progress, events) = mpsc::channel(2);
+
+        self.pulls.remove(&container_name);
+
+        with_progress(
+            async move {
+                pull::ensure_image(&client, &service.image, Some(progress)).await?;
+                apply::create_and_start(&client, &engine, &service, vec![]).await?;
+
+                Result::Ok(service)
+            },
+            events,
+            move |event| AppMsg::PullProgress(container_name.clone(), event),
+            AppMsg::CreateDockerServiceRes,
+        )
+    }
+}
+
+impl DockerModule {
+    pub fn container(&self, name: &str) -> Option<&ContainerData> {
+        self.containers.iter().find(|c| c.name() == name)
+    }
+
+    /// The QEMU monitor of the container.
+    pub fn monitor(&self, container_name: &str) -> Monitor {
+        let ports = self
+            .container(container_name)
+            .map(|c| c.ports())
+            .unwrap_or_default();
+
+        Monitor::new(
+            self.client.clone(),
+            container_name.into(),
+            &ports,
+            self.engine.remote_host.as_deref(),
+        )
+    }
+
+    /// What applying `service` would change about its container.
+    pub fn pending_changes(&self, service: &DockerServiceState) -> Vec<ConfigChange> {
+        apply::pending_changes(
+            self.container(&service.container_name),
+            service,
+            &self.engine,
+        )
+    }
+
+    pub fn run_action(&mut self, service: &DockerServiceState, action: ContainerAction) -> AppTask {
+        let container_name = service.container_name.clone();
+
+        self.actions.insert(container_name.clone(), action);
+        self.action_errors.remove(&container_name);
+
+        match action {
+            ContainerAction::Stop => return self.shut_down(service),
+            ContainerAction::Apply | ContainerAction::UpdateImage => {
+                return self.replace(service, action);
+            }
+            _ => {}
+        }
+
+        AppTask::perform(
+            self.execute(service, action).map(Arced::arced),
+            move |res| AppMsg::DockerServiceActionRes(container_name, action, res),
+        )
+    }
+
+    /// Sends the command to QEMU and asks how the VM is doing afterwards.
+    pub fn run_vm_command(&mut self, service: &DockerServiceState, command: VmCommand) -> AppTask {
+        let container_name = service.container_name.clone();
+        let monitor = self.monitor(&container_name);
+
+        self.vm_commands.insert(container_name.clone(), command);
+        self.action_errors.remove(&container_name);
+
+        AppTask::perform(
+            async move {
+                monitor.execute(command).await?;
+                monitor.status().await
+            }
+            .map(Arced::arced),
+            move |res| AppMsg::VmCommandRes(container_name, command, res),
+        )
+    }
+
+    pub fn query_vm_status(&self, service: &DockerServiceState) -> AppTask {
+        let container_name = service.container_name.clone();
+        let monitor = self.monitor(&container_name);
+
+        AppTask::perform(
+            async move { monitor.status().await }.map(Arced::arced),
+            move |res| AppMsg::VmStatusRes(container_name, res),
+        )
+    }
+
+    pub fn vm_command_done(
+        &mut self,
+        container_name: String,
+        command: VmCommand,
+        res: &Result<VmStatus>,
+    ) {
+        self.vm_commands.remove(&container_name);
+
+        if let Err(err) = res {
+            tracing::error!("Failed to {command} {container_name}: {err}");
+            self.action_errors.insert(
+                container_name.clone(),
+                format!("Failed to {command} the VM: {err}"),
+            );
+        }
+
+        self.set_vm_status(container_name, res);
+    }
+
+    pub fn set_vm_status(&mut self, container_name: String, res: &Result<VmStatus>) {
+        let status = match res {
+            Ok(status) => Ok(status.clone()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        self.vm_status.insert(container_name, status);
+    }
+
+    /// Stops the container gracefully, reporting progress until the shutdown is recorded.
+    fn shut_down(&mut self, service: &DockerServiceState) -> AppTask {
+        let container_name = service.container_name.clone();
+        let (progress, events) = mpsc::channel(2);
+        let stop = self.graceful_stop(service, progress);
+
+        with_progress(
+            stop,
+            events,
+            {
+                let container_name = container_name.clone();
+                move |event| AppMsg::ShutdownProgress(container_name.clone(), event)
+            },
+            move |res| AppMsg::ShutdownRes(container_name, res),
+        )
+    }
+
+    /// Recreates the container for [`ContainerAction::Apply`] and
+    /// [`ContainerAction::UpdateImage`], reporting the pull of its image.
+    fn replace(&mut self, service: &DockerServiceState, action: ContainerAction) -> AppTask {
+        let container_name = service.container_name.clone();
+        let (progress, events) = mpsc::channel(2);
+
+        self.pulls.remove(&container_name);
+
+        let task: BoxFuture<'static, Result<()>> = match action {
+            ContainerAction::UpdateImage => self
+                .update_image(service, Some(progress))
+                .map(|res| res.map(|_| ()))
+                .boxed(),
+            _ => self.recreate(service, Some(progress)).boxed(),
+        };
+
+        with_progress(
+            task,
+            events,
+            {
+                let container_name = container_name.clone();
+                move |event| AppMsg::PullProgress(container_name.clone(), event)
+            },
+            move |res| AppMsg::DockerServiceActionRes(container_name, action, res),
+        )
+    }
+
+    /// Asks Windows to shut down before stopping the container, see [`ShutdownProgress`].
+    pub fn graceful_stop(
+        &self,
+        service: &DockerServiceState,
+        progress: mpsc::Sender<ShutdownProgress>,
+    ) -> impl Future<Output = Result<ShutdownRecord>> + Send + 'static {
+        let guest = self
+            .container(&service.container_name)
+            .and_then(|c| guest_address(c).ok());
+        let monitor = self.monitor(&service.container_name);
+        // Forced stops still give the container its grace period
+        let client = self
+            .client
+            .clone()
+            .with_timeout(self.client.timeout() + *service.stop_grace_period);
+
+        shutdown::graceful_stop(client, service.clone(), guest, monitor, progress)
+    }
+
+    /// Recreates the container from `service`, pulling its image first if the engine lacks it.
+    pub fn recreate(
+        &self,
+        service: &DockerServiceState,
+        progress: Option<mpsc::Sender<PullProgress>>,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        let (client, engine, service, live) = self.recreation(service);
+
+        async move { apply::recreate(&client, &engine, &service, live, progress).await }
+    }
+
+    /// Pulls the image of `service` again, recreating the container if a newer one came down.
+    /// Resolves to whether it did.
+    pub fn update_image(
+        &self,
+        service: &DockerServiceState,
+        progress: Option<mpsc::Sender<PullProgress>>,
+    ) -> impl Future<Output = Result<bool>> + Send + 'static {
+        let (client, engine, service, live) = self.recreation(service);
+
+        async move { apply::update_image(&client, &engine, &service, live, progress).await }
+    }
+
+    /// What recreating the container of `service` takes, its client waits out the stop.
+    fn recreation(
+        &self,
+        service: &DockerServiceState,
+    ) -> (Docker, Engine, DockerServiceState, Option<ContainerData>) {
+        let client = self
+            .client
+            .clone()
+            .with_timeout(self.client.timeout() + *service.stop_grace_period);
+
+        (
+            client,
+            self.engine.clone(),
+            service.clone(),
+            self.container(&service.container_name).cloned(),
+        )
+    }
+
+    /// Runs the action without tracking it in [`DockerModule::actions`].
+    pub fn execute(
+        &self,
+        service: &DockerServiceState,
+        action: ContainerAction,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        let replace = match action {
+            ContainerAction::Apply => Some(self.recreate(service, None).boxed()),
+            ContainerAction::UpdateImage => Some(
+                self.update_image(service, None)
+                    .map(|res| res.map(|_| ()))
+                    .boxed(),
+            ),
+            _ => None,
+        };
+        let name = service.container_name.clone();
+        let grace_period = service.stop_grace_period;
+        let stop_signal = service.stop_signal.clone();
+        // Docker only answers a stop request once the container is down
+        let client = self
+            .client
+            .clone()
+            .with_timeout(self.client.timeout() + *grace_period);
+
+        async move {
+            match action {
+                ContainerAction::Start => {
+                    client
+                        .start_container(&name, Option::<StartContainerOptions>::None)
+                        .await?
+                }
+                ContainerAction::Stop => {
+                    client
+                        .stop_container(
+                            &name,
+                            Some(stop_options(grace_period, stop_signal.as_deref())),
+                        )
+                        .await?
+                }
+                ContainerAction::Restart => {
+                    client
+                        .restart_container(
+                            &name,
+                            Some({
+                                let builder =
+                                    RestartContainerOptionsBuilder::new().t(grace_period.secs());
+
+                                match &stop_signal {
+                                    Some(signal) => builder.signal(signal),
+                                    None => builder,
+                                }
+                                .build()
+                            }),
+                        )
+                        .await?
+                }
+                ContainerAction::Pause => client.pause_container(&name).await?,
+                ContainerAction::Unpause => client.unpause_container(&name).await?,
+                ContainerAction::Remove => {
+                    client
+                        .remove_container(&name, Some(RemoveContainerOptionsBuilder::new().build()))
+                        .await?
+                }
+                ContainerAction::Apply | ContainerAction::UpdateImage => {
+                    replace.expect("Logic error!").await?
+                }
+            }
+
+            Result::Ok(())
+        }
+    }
+
+    pub fn action_done<T>(
+        &mut self,
+        container_name: String,
+        action: ContainerAction,
+        res: &Result<T>,
+    ) -> AppTask {
+        self.actions.remove(&container_name);
+        self.shutdowns.remove(&container_name);
+        // QEMU restarted or went away with the container
+        self.vm_status.remove(&container_name);
+
+        if let Err(err) = res {
+            tracing::error!("Failed to {action} container {container_name}: {err}");
+            self.action_errors.insert(
+                container_name,
+                format!("Failed to {action} container: {err}"),
+            );
+        }
+
+        self.refresh_containers()
+    }
+}
+
+fn stop_options(grace_period: GracePeriod, signal: Option<&str>) -> StopContainerOptions {
+    let builder = StopContainerOptionsBuilder::new().t(grace_period.secs());
+
+    match signal {
+        Some(signal) => builder.signal(signal),
+        None => builder,
+    }
+    .build()
+}
+
+/// Runs `task` to its end, turning what it reports on the way into messages.
+fn with_progress<P, T>(
+    task: impl Future<Output = Result<T>> + Send + 'static,
+    mut events: mpsc::Receiver<P>,
+    progress: impl Fn(P) -> AppMsg + Send + Sync + 'static,
+    done: impl FnOnce(Arc<Result<T>>) -> AppMsg + Send + 'static,
+) -> AppTask
+where
+    P: Send + 'static,
+    T: Send + Sync + 'static,
+{
+    AppTask::run(
+        iced::stream::channel(4, move |mut output| async move {
+            let (res, ()) = tokio::join!(task, async {
+                while let Some(event) = events.next().await {
+                    let _ = output.send(progress(event)).await;
+                }
+            });
+
+            let _ = output.send(done(res.arced())).await;
+        }),
+        |m| m,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerAction {
+    Start,
+    Stop,
+    Restart,
+    Pause,
+    Unpause,
+    Remove,
+    /// Recreates the container from the stored service, see [`DockerModule::pending_changes`].
+    Apply,
+    /// Pulls the image again and recreates the container if it changed.
+    UpdateImage,
+}
+
+impl ContainerAction {
+    /// The lifecycle actions, `Apply` is offered separately once there's something to apply and
+    /// `UpdateImage` next to the service's own controls.
+    pub const ALL: [Self; 6] = [
+        Self::Start,
+        Self::Stop,
+        Self::Restart,
+        Self::Pause,
+        Self::Unpause,
+        Self::Remove,
+    ];
+
+    /// Whether the action makes sense for a container in the given state.
+    pub fn available(&self, status: Option<ContainerStateStatusEnum>) -> bool {
+        use ContainerStateStatusEnum as S;
+
+        let Some(status) = status else {
+            return false;
+        };
+
+        match self {
+            Self::Start | Self::Remove => matches!(status, S::CREATED | S::EXITED | S::DEAD),
+            Self::Stop => matches!(status, S::RUNNING | S::PAUSED | S::RESTARTING),
+            Self::Restart | Self::Pause => status == S::RUNNING,
+            Self::Unpause => status == S::PAUSED,
+            Self::Apply | Self::UpdateImage => true,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Start => "Start",
+            Self::Stop => "Stop",
+            Self::Restart => "Restart",
+            Self::Pause => "Pause",
+            Self::Unpause => "Unpause",
+            Self::Remove => "Remove",
+            Self::Apply => "Apply changes",
+            Self::UpdateImage => "Update image",
+        }
+    }
+
+    pub fn progress(&self) -> &'static str {
+        match self {
+            Self::Start => "Starting",
+            Self::Stop => "Stopping",
+            Self::Restart => "Restarting",
+            Self::Pause => "Pausing",
+            Self::Unpause => "Resuming",
+            Self::Remove => "Removing",
+            Self::Apply => "Recreating",
+            Self::UpdateImage => "Updating the image",
+        }
+    }
+}
+
+impl Display for ContainerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Restart => "restart",
+            Self::Pause => "pause",
+            Self::Unpause => "unpause",
+            Self::Remove => "remove",
+            Self::Apply => "recreate",
+            Self::UpdateImage => "update the image of",
+        };
+
+        f.write_str(name)
+    }
+}
+
+pub const WINDOWS_IMAGE: &str = "dockurr/windows";
+
+/// Where the host reaches a TCP port the container publishes, `remote_host` is the machine of a
+/// remote engine.
+pub fn published_tcp(
+    ports: &[Port],
+    private_port: u16,
+    remote_host: Option<&str>,
+) -> Option<(String, u16)> {
+    ports
+        .iter()
+        .filter(|port| matches!(port.typ, None | Some(PortTypeEnum::TCP)))
+        .find(|port| port.private_port == private_port && port.public_port.is_some())
+        .and_then(|port| Some((host_address(port, remote_host), port.public_port?)))
+}
+
+/// Ports bound to every interface are reached through loopback, or the machine of a remote
+/// engine.
+pub fn host_address(port: &Port, remote_host: Option<&str>) -> String {
+    match (port.ip.as_deref(), remote_host) {
+        (None | Some("" | "0.0.0.0" | "::"), Some(host)) => host.into(),
+        (None | Some("" | "0.0.0.0" | "::"), None) => "127.0.0.1".into(),
+        (Some(ip), _) if ip.contains(':') => format!("[{ip}]"),
+        (Some(ip), _) => ip.into(),
+    }
+}
+
+async fn list_containers(client: &Docker, remote_host: Option<&str>) -> Result<Vec<ContainerData>> {
+    load_containers(client, remote_host, HashMap::new()).await
+}
+
+async fn load_containers(
+    client: &Docker,
+    remote_host: Option<&str>,
+    filters: HashMap<&str, Vec<&str>>,
+) -> Result<Vec<ContainerData>> {
+    // `ancestor` only matches the image its name points to now, which leaves out containers of
+    // pinned tags and of images that were pulled again since
+    Ok(client
+        .list_containers(Some(
+            ListContainersOptionsBuilder::new()
+                .all(true)
+                .filters(&filters)
+                .build(),
+        ))
+        .await?
+        .into_iter()
+        // Containers of an image whose name moved on only show its id
+        .filter(|summary| {
+            summary
+                .image
+                .as_deref()
+                .is_some_and(|image| is_windows_image(image) || image.starts_with("sha256:"))
+        })
+        .fold(JoinSet::new(), |mut join_set, summary| {
+            let client = client.clone();
+            let remote_host = remote_host.map(String::from);
+
+            join_set.spawn(async move {
+                let specs = client
+                    .inspect_container(&summary.name(), Option::<InspectContainerOptions>::None)
+                    .await?;
+
+                let data = ContainerData {
+                    summary,
+                    specs,
+                    remote_host,
+                };
+
+                Result::Ok(is_windows_image(&data.image()).then_some(data))
+            });
+            join_set
+        })
+        .join_all()
+        .await
+        .into_iter()
+        .flat_map(|r| {
+            r.inspect_err(|err: &color_eyre::Report| {
+                tracing::error!("Failed to load container information: {err}")
             })
-            .join_all()
-            .await
-            .into_iter()
-            .flat_map(|r| {
-                r.inspect_err(|err: &color_eyre::Report| {
-                    tracing::error!("Failed to load container information: {err}")
-                })
+        })
+        .flatten()
+        .collect())
+}
+
+impl From<&DockerServiceState> for ContainerCreateBody {
+    fn from(service: &DockerServiceState) -> Self {
+        let port_key = |port: &Port| {
+            format!(
+                "{}/{}",
+                port.private_port,
+                port.typ.unwrap_or(PortTypeEnum::TCP)
+            )
+        };
+
+        let exposed_ports = service
+            .ports
+            .iter()
+            .map(|port| (port_key(port), HashMap::new()))
+            .collect();
+        let port_bindings = service.ports.iter().fold(
+            HashMap::<String, Option<Vec<PortBinding>>>::new(),
+            |mut bindings, port| {
+                bindings
+                    .entry(port_key(port))
+                    .or_default()
+                    .get_or_insert_default()
+                    .push(PortBinding {
+                        host_ip: port.ip.clone(),
+                        host_port: port.public_port.map(|p| p.to_string()),
+                    });
+                bindings
+            },
+        );
+
+        // Mirror `docker run --device`: the container path defaults to the host one
+        let devices = service
+            .devices
+            .iter()
+            .cloned()
+            .map(|device| DeviceMapping {
+                path_in_container: device
+                    .path_in_container
+                    .or_else(|| device.path_on_host.clone()),
+                cgroup_permissions: device.cgroup_permissions.or_else(|| Some("rwm".into())),
+                ..device
             })
             .collect();
 
-        Ok(Self { client, containers })
+        ContainerCreateBody {
+            image: Some(service.image.clone()),
+            env: Some(service.environment.entries()),
+            stop_timeout: Some(service.stop_grace_period.secs().into()),
+            stop_signal: service.stop_signal.clone(),
+            exposed_ports: Some(exposed_ports),
+            host_config: Some(HostConfig {
+                devices: Some(devices),
+                cap_add: Some(service.cap_add.clone()),
+                port_bindings: Some(port_bindings),
+                binds: Some(service.volumes.clone()),
+                restart_policy: Some(service.restart.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
     }
 }
 
@@ -100,17 +800,38 @@ pub struct ContainerData {
     summary: ContainerSummary,
     #[as_ref]
     specs: ContainerInspectResponse,
+    /// The machine of the engine if it's remote.
+    remote_host: Option<String>,
+}
+
+impl ContainerData {
+    /// Where its published ports are, `None` for this machine.
+    pub fn remote_host(&self) -> Option<&str> {
+        self.remote_host.as_deref()
+    }
+
+    /// Where the host reaches a TCP port the container publishes.
+    pub fn published_tcp(&self, private_port: u16) -> Option<(String, u16)> {
+        published_tcp(&self.ports(), private_port, self.remote_host())
+    }
 }
 
 pub trait DockerContainerExt {
-    column_fn! { name, image }
+    column_fn! { name, image, status }
+
+    fn state_status(&self) -> Option<ContainerStateStatusEnum>;
+    fn health(&self) -> Option<HealthStatusEnum>;
 
-    fn env(&self) -> serde_json::Map<String, serde_json::Value>;
+    fn env(&self) -> Environment;
     fn devices(&self) -> Vec<DeviceMapping>;
     fn cap_add(&self) -> Vec<String>;
     fn ports(&self) -> Vec<Port>;
     fn volumes(&self) -> Vec<String>;
     fn restart(&self) -> RestartPolicy;
+    fn stop_grace_period(&self) -> GracePeriod;
+    fn stop_signal(&self) -> Option<String>;
+    /// Address on the container's first network, the host can reach it on a local bridge.
+    fn ip_address(&self) -> Option<String>;
 
     fn into_service(self) -> DockerServiceState;
 }
@@ -124,30 +845,45 @@ where
         AsRef::<ContainerSummary>::as_ref(&self).name()
     }
 
+    /// The name the container was created from, the summary only has the id once it moved on.
     fn image(&self) -> String {
+        AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .config
+            .as_ref()
+            .and_then(|c| c.image.clone())
+            .or_else(|| AsRef::<ContainerSummary>::as_ref(&self).image.clone())
+            .unwrap_or_else(|| "Unknown".into())
+    }
+
+    fn status(&self) -> String {
         AsRef::<ContainerSummary>::as_ref(&self)
-            .image
+            .status
             .clone()
             .unwrap_or_else(|| "Unknown".into())
     }
 
-    fn env(&self) -> serde_json::Map<String, serde_json::Value> {
-        serde_json::Map::from_iter(
+    fn state_status(&self) -> Option<ContainerStateStatusEnum> {
+        AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .state
+            .as_ref()
+            .and_then(|s| s.status)
+    }
+
+    fn health(&self) -> Option<HealthStatusEnum> {
+        AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .state
+            .as_ref()
+            .and_then(|s| s.health.as_ref())
+            .and_then(|h| h.status)
+            .filter(|h| !matches!(h, HealthStatusEnum::EMPTY | HealthStatusEnum::NONE))
+    }
+
+    fn env(&self) -> Environment {
+        Environment::from_entries(
             AsRef::<ContainerInspectResponse>::as_ref(&self)
                 .config
                 .iter()
-                .flat_map(|c| c.env.iter())
-                .flat_map(|x| {
-                    x.iter().map(|x| {
-                        let mut split = x.split("=");
-
-                        Some((
-                            split.next()?.into(),
-                            serde_json::from_str(split.next()?).ok()?,
-                        ))
-                    })
-                })
-                .flatten(),
+                .flat_map(|c| c.env.iter().flatten()),
         )
     }
 
@@ -169,18 +905,49 @@ where
     }
 
     fn ports(&self) -> Vec<Port> {
-        AsRef::<ContainerSummary>::as_ref(&self)
-            .ports
-            .clone()
-            .unwrap_or_default()
+        // The summary only lists ports of running containers, the host config always has them
+        let Some(bindings) = AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .host_config
+            .as_ref()
+            .and_then(|x| x.port_bindings.as_ref())
+        else {
+            return AsRef::<ContainerSummary>::as_ref(&self)
+                .ports
+                .clone()
+                .unwrap_or_default();
+        };
+
+        bindings
+            .iter()
+            .flat_map(|(key, bindings)| {
+                let (private, typ) = key.split_once('/').unwrap_or((key, "tcp"));
+                let private_port = private.parse().unwrap_or_default();
+                let typ = typ.parse().ok();
+
+                bindings.iter().flatten().map(move |binding| Port {
+                    ip: binding.host_ip.clone().filter(|ip| !ip.is_empty()),
+                    private_port,
+                    public_port: binding.host_port.as_ref().and_then(|p| p.parse().ok()),
+                    typ,
+                })
+            })
+            .collect()
     }
 
     fn volumes(&self) -> Vec<String> {
-        AsRef::<ContainerInspectResponse>::as_ref(&self)
-            .config
+        let specs = AsRef::<ContainerInspectResponse>::as_ref(&self);
+
+        specs
+            .host_config
             .as_ref()
-            .and_then(|x| x.volumes.as_ref())
-            .map(|x| x.keys().cloned().collect())
+            .and_then(|x| x.binds.clone())
+            .or_else(|| {
+                specs
+                    .config
+                    .as_ref()
+                    .and_then(|x| x.volumes.as_ref())
+                    .map(|x| x.keys().cloned().collect())
+            })
             .unwrap_or_default()
     }
 
@@ -189,7 +956,39 @@ where
             .host_config
             .as_ref()
             .and_then(|x| x.restart_policy.clone())
-            .unwrap_or_else(|| serde_json::from_str("always").unwrap())
+            .unwrap_or_else(|| RestartPolicy {
+                name: Some(RestartPolicyNameEnum::ALWAYS),
+                ..Default::default()
+            })
+    }
+
+    fn stop_grace_period(&self) -> GracePeriod {
+        AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .config
+            .as_ref()
+            .and_then(|x| x.stop_timeout)
+            .and_then(|secs| u64::try_from(secs).ok())
+            .map(GracePeriod::from_secs)
+            .unwrap_or(GracePeriod::DOCKER_DEFAULT)
+    }
+
+    fn stop_signal(&self) -> Option<String> {
+        AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .config
+            .as_ref()
+            .and_then(|x| x.stop_signal.clone())
+    }
+
+    fn ip_address(&self) -> Option<String> {
+        AsRef::<ContainerInspectResponse>::as_ref(&self)
+            .network_settings
+            .as_ref()
+            .and_then(|x| x.networks.as_ref())
+            .and_then(|networks| {
+                networks
+                    .values()
+                    .find_map(|n| n.ip_address.clone().filter(|ip| !ip.is_empty()))
+            })
     }
 
     fn into_service(self) -> DockerServiceState {
@@ -202,8 +1001,8 @@ where
             ports: self.ports(),
             volumes: self.volumes(),
             restart: self.restart(),
-            // TODO: figure this one out
-            // stop_grace_period: (),
+            stop_grace_period: self.stop_grace_period(),
+            stop_signal: self.stop_signal(),
             ..Default::default()
         }
     }
@@ -0,0 +1,422 @@
+use std::collections::BTreeSet;
+
+use bollard::{
+    Docker,
+    query_parameters::{
+        CreateContainerOptionsBuilder, RemoveContainerOptionsBuilder,
+        RenameContainerOptionsBuilder, StartContainerOptions,
+    },
+    secret::{ContainerCreateBody, ContainerStateStatusEnum, MountPointTypeEnum, Port},
+};
+use color_eyre::{
+    Result,
+    eyre::{WrapErr, bail},
+};
+use iced::futures::channel::mpsc;
+
+use crate::controller::{
+    docker::{
+        ContainerData, DockerContainerExt, Engine,
+        pull::{self, ImageRef, PullProgress},
+        stop_options,
+    },
+    guest,
+    state::DockerServiceState,
+};
+
+/// Appended to the name of a container while its replacement is being created.
+const REPLACED_SUFFIX: &str = "-winjet-replaced";
+
+/// A difference between the stored service and the container that's actually running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    pub field: String,
+    pub live: String,
+    pub stored: String,
+}
+
+impl ConfigChange {
+    fn new(field: impl Into<String>, live: impl Into<String>, stored: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            live: live.into(),
+            stored: stored.into(),
+        }
+    }
+}
+
+/// Everything that would change if the container was recreated from `stored`.
+pub fn pending_changes(
+    live: Option<&ContainerData>,
+    stored: &DockerServiceState,
+    engine: &Engine,
+) -> Vec<ConfigChange> {
+    let Some(live) = live else {
+        return vec![ConfigChange::new(
+            "container",
+            "missing",
+            &stored.container_name,
+        )];
+    };
+
+    let mut changes = vec![];
+    let mut compare = |field: &str, live: String, stored: String| {
+        if live != stored {
+            changes.push(ConfigChange::new(field, live, stored));
+        }
+    };
+
+    compare(
+        "image",
+        normalize_image(
+            &live
+                .specs
+                .config
+                .as_ref()
+                .and_then(|c| c.image.clone())
+                .unwrap_or_else(|| live.image()),
+        ),
+        normalize_image(&stored.image),
+    );
+
+    // The live environment also has everything the image sets, only our variables matter
+    let live_env = live.env();
+    for var in stored.environment.iter() {
+        let live_value = live_env.get(&var.key);
+
+        compare(
+            &format!("env {}", var.key),
+            live_value.unwrap_or("<unset>").into(),
+            var.value.as_deref().unwrap_or("<unset>").into(),
+        );
+    }
+
+    compare(
+        "devices",
+        joined(live.devices().iter().flat_map(|d| d.path_on_host.clone())),
+        joined(stored.devices.iter().flat_map(|d| d.path_on_host.clone())),
+    );
+    compare(
+        "cap_add",
+        joined(live.cap_add().iter().map(|cap| normalize_cap(cap))),
+        joined(cap_add(stored, engine)),
+    );
+    compare(
+        "ports",
+        joined(live.ports().iter().map(port_spec)),
+        joined(stored.ports.iter().map(port_spec)),
+    );
+    compare(
+        "volumes",
+        joined(live.volumes()),
+        joined(stored.volumes.iter().cloned()),
+    );
+    compare(
+        "restart",
+        live.restart()
+            .name
+            .map(|x| x.to_string())
+            .unwrap_or_default(),
+        stored
+            .restart
+            .name
+            .map(|x| x.to_string())
+            .unwrap_or_default(),
+    );
+    compare(
+        "stop_grace_period",
+        live.stop_grace_period().to_string(),
+        stored.stop_grace_period.to_string(),
+    );
+
+    if let Some(signal) = &stored.stop_signal {
+        compare(
+            "stop_signal",
+            live.stop_signal().unwrap_or_default(),
+            signal.clone(),
+        );
+    }
+
+    changes
+}
+
+fn normalize_image(image: &str) -> String {
+    // Podman spells out the registry Docker leaves implicit
+    let image = image.trim_start_matches("docker.io/");
+    let name = image.rsplit('/').next().unwrap_or(image);
+
+    match name.contains(':') || name.contains('@') {
+        true => image.into(),
+        false => format!("{image}:latest"),
+    }
+}
+
+/// The capabilities of the service with the ones the engine doesn't grant by default.
+fn cap_add(service: &DockerServiceState, engine: &Engine) -> BTreeSet<String> {
+    service
+        .cap_add
+        .iter()
+        .map(|cap| normalize_cap(cap))
+        .chain(
+            engine
+                .missing_default_caps()
+                .iter()
+                .map(|cap| cap.to_string()),
+        )
+        .collect()
+}
+
+fn normalize_cap(cap: &str) -> String {
+    let cap = cap.to_ascii_uppercase();
+    cap.strip_prefix("CAP_").map(String::from).unwrap_or(cap)
+}
+
+fn port_spec(port: &Port) -> String {
+    let typ = port
+        .typ
+        .map(|t| t.to_string())
+        .unwrap_or_else(|| "tcp".into());
+    // Docker reports unbound addresses as empty or the wildcard
+    let ip = port
+        .ip
+        .as_deref()
+        .filter(|ip| !ip.is_empty() && *ip != "0.0.0.0");
+    let host = match (ip, port.public_port) {
+        (Some(ip), Some(public)) => format!("{ip}:{public}:"),
+        (None, Some(public)) => format!("{public}:"),
+        _ => String::new(),
+    };
+
+    format!("{host}{}/{typ}", port.private_port)
+}
+
+fn joined(items: impl IntoIterator<Item = String>) -> String {
+    items
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(super) async fn create_and_start(
+    client: &Docker,
+    engine: &Engine,
+    service: &DockerServiceState,
+    extra_binds: Vec<String>,
+) -> Result<()> {
+    let id = create(client, engine, service, extra_binds).await?;
+    client
+        .start_container(&id, Option::<StartContainerOptions>::None)
+        .await?;
+
+    Ok(())
+}
+
+/// Creates the container of `service` without starting it, resolving to its id.
+async fn create(
+    client: &Docker,
+    engine: &Engine,
+    service: &DockerServiceState,
+    extra_binds: Vec<String>,
+) -> Result<String> {
+    service.environment.validate(&engine.host_resources())?;
+
+    // The shared folders of a remote engine are on its machine
+    if engine.remote_host.is_none()
+        && let Err(err) = guest::provision_token(service)
+    {
+        tracing::warn!(
+            "Failed to leave the guest tools token in the shared folder of {}: {err}",
+            service.container_name
+        );
+    }
+
+    let mut body = ContainerCreateBody::from(service);
+    if let Some(host_config) = body.host_config.as_mut() {
+        host_config
+            .binds
+            .get_or_insert_default()
+            .extend(extra_binds);
+        host_config.cap_add = Some(cap_add(service, engine).into_iter().collect());
+    }
+
+    let created = client
+        .create_container(
+            Some(
+                CreateContainerOptionsBuilder::new()
+                    .name(&service.container_name)
+                    .build(),
+            ),
+            body,
+        )
+        .await?;
+
+    Ok(created.id)
+}
+
+/// Stops the live container and creates it again from `service`.
+///
+/// The old container is only moved aside until the new one started, and put back if it doesn't.
+/// Docker volumes the container had that aren't replaced by the service's own binds get
+/// mounted into the new container as well, so the Windows disk survives even if it wasn't
+/// bound to a host directory.
+pub(super) async fn recreate(
+    client: &Docker,
+    engine: &Engine,
+    service: &DockerServiceState,
+    live: Option<ContainerData>,
+    progress: Option<mpsc::Sender<PullProgress>>,
+) -> Result<()> {
+    // Fail before touching the running container
+    service.environment.validate(&engine.host_resources())?;
+    pull::ensure_image(client, &service.image, progress).await?;
+
+    let Some(live) = live else {
+        return create_and_start(client, engine, service, vec![]).await;
+    };
+
+    let targets = service
+        .volumes
+        .iter()
+        .flat_map(|v| v.split(':').nth(1))
+        .collect::<Vec<_>>();
+
+    let kept_volumes = live
+        .specs
+        .mounts
+        .iter()
+        .flatten()
+        .filter(|m| m.typ == Some(MountPointTypeEnum::VOLUME))
+        .filter_map(|m| Some((m.name.as_deref()?, m.destination.as_deref()?)))
+        .filter(|(_, destination)| !targets.contains(destination))
+        .map(|(name, destination)| format!("{name}:{destination}"))
+        .collect();
+
+    let name = live.name();
+    let aside = format!("{name}{REPLACED_SUFFIX}");
+    let was_running = matches!(
+        live.state_status(),
+        Some(
+            ContainerStateStatusEnum::RUNNING
+                | ContainerStateStatusEnum::PAUSED
+                | ContainerStateStatusEnum::RESTARTING
+        )
+    );
+
+    if was_running {
+        client
+            .stop_container(
+                &name,
+                Some(stop_options(
+                    service.stop_grace_period,
+                    service.stop_signal.as_deref(),
+                )),
+            )
+            .await?;
+    }
+
+    client
+        .rename_container(
+            &name,
+            RenameContainerOptionsBuilder::new().name(&aside).build(),
+        )
+        .await
+        .wrap_err_with(|| format!("Failed to move {name} aside as {aside}"))?;
+
+    let replaced = match create(client, engine, service, kept_volumes).await {
+        Ok(id) => client
+            .start_container(&id, Option::<StartContainerOptions>::None)
+            .await
+            .map_err(|err| (Some(id), err.into())),
+        Err(err) => Err((None, err)),
+    };
+
+    if let Err((created, err)) = replaced {
+        return match restore(client, created.as_deref(), &aside, &name, was_running).await {
+            Ok(()) => Err(err.wrap_err(format!("Kept the previous {name}"))),
+            Err(restore_err) => Err(err.wrap_err(format!(
+                "Failed to restore the previous container, it's left as {aside}: {restore_err}"
+            ))),
+        };
+    }
+
+    // The new container runs, a leftover only costs disk space
+    if let Err(err) = client
+        .remove_container(
+            &aside,
+            Some(RemoveContainerOptionsBuilder::new().v(false).build()),
+        )
+        .await
+    {
+        tracing::warn!("Failed to remove the previous container {aside}: {err}");
+    }
+
+    Ok(())
+}
+
+/// Removes the container that failed to start and puts the old one back as `name`.
+async fn restore(
+    client: &Docker,
+    created: Option<&str>,
+    aside: &str,
+    name: &str,
+    start: bool,
+) -> Result<()> {
+    if let Some(created) = created {
+        client
+            .remove_container(
+                created,
+                Some(
+                    RemoveContainerOptionsBuilder::new()
+                        .force(true)
+                        .v(false)
+                        .build(),
+                ),
+            )
+            .await?;
+    }
+
+    client
+        .rename_container(
+            aside,
+            RenameContainerOptionsBuilder::new().name(name).build(),
+        )
+        .await?;
+
+    if start {
+        client
+            .start_container(name, Option::<StartContainerOptions>::None)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Pulls the image of `service` again and recreates the container from it if the image changed,
+/// resolving to whether it did.
+pub(super) async fn update_image(
+    client: &Docker,
+    engine: &Engine,
+    service: &DockerServiceState,
+    live: Option<ContainerData>,
+    progress: Option<mpsc::Sender<PullProgress>>,
+) -> Result<bool> {
+    if service.image.parse::<ImageRef>()?.pinned() {
+        bail!(
+            "{} is pinned to a digest, change the image of the service to update it",
+            service.image
+        );
+    }
+
+    pull::pull(client, &service.image, progress).await?;
+
+    let latest = pull::local_image(client, &service.image).await?;
+    if latest.is_some() && latest == live.as_ref().and_then(|l| l.specs.image.clone()) {
+        return Ok(false);
+    }
+
+    recreate(client, engine, service, live, None).await?;
+
+    Ok(true)
+}
@@ -0,0 +1,282 @@
+use std::{env, fmt::Display, path::PathBuf};
+
+use bollard::{API_DEFAULT_VERSION, Docker};
+use color_eyre::{
+    Result,
+    eyre::{WrapErr, bail},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::controller::{host::HostResources, state::Size};
+
+/// Seconds, the same bollard's own defaults use.
+const TIMEOUT: u64 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineKind {
+    Docker,
+    /// Through its Docker compatible API.
+    Podman,
+}
+
+/// What answered on the other end of the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Engine {
+    pub kind: EngineKind,
+    pub version: String,
+    pub rootless: bool,
+    /// Where it was reached, `unix:///run/user/1000/podman/podman.sock`.
+    pub endpoint: String,
+    /// The machine it runs on if that isn't this one, containers publish their ports there.
+    pub remote_host: Option<String>,
+    /// Bytes of memory of the machine it runs on, as it reported them.
+    pub mem_total: Option<u64>,
+    /// CPUs of the machine it runs on, as it reported them.
+    pub ncpu: Option<u32>,
+}
+
+/// Where an engine listens, and what it takes to talk to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Connection {
+    /// `unix:///path/to/engine.sock`, `tcp://host:2376` or `ssh://user@host`.
+    pub endpoint: String,
+    /// Folder with the `ca.pem`, `cert.pem` and `key.pem` of TLS, like `DOCKER_CERT_PATH`.
+    #[serde(default)]
+    pub cert_path: Option<PathBuf>,
+}
+
+impl Engine {
+    /// Capabilities Docker gives every container but Podman doesn't, added to the container so
+    /// the image runs as it does under Docker.
+    pub fn missing_default_caps(&self) -> &'static [&'static str] {
+        match self.kind {
+            EngineKind::Docker => &[],
+            EngineKind::Podman => &["MKNOD", "NET_RAW"],
+        }
+    }
+
+    /// What the machine running the containers can give to a VM.
+    pub fn host_resources(&self) -> HostResources {
+        match (self.mem_total, self.ncpu) {
+            (Some(mem_total), Some(ncpu)) if ncpu > 0 => HostResources {
+                memory: Size::from_bytes(mem_total),
+                cpus: ncpu,
+            },
+            _ => {
+                if let Some(host) = &self.remote_host {
+                    tracing::warn!("{host} didn't report its resources, assuming this machine's");
+                }
+
+                HostResources::detect()
+            }
+        }
+    }
+}
+
+impl Connection {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            cert_path: None,
+        }
+    }
+
+    /// The host of `tcp://` and `ssh://` endpoints, unless it's this machine.
+    pub fn remote_host(&self) -> Option<String> {
+        let (scheme, rest) = self.endpoint.split_once("://")?;
+        if !matches!(scheme, "tcp" | "http" | "https" | "ssh") {
+            return None;
+        }
+
+        let authority = rest.split('/').next().unwrap_or(rest);
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        let host = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split_once(']').map_or(ipv6, |(ip, _)| ip),
+            None => host.split(':').next().unwrap_or(host),
+        };
+
+        match host {
+            "" | "localhost" | "127.0.0.1" | "::1" => None,
+            host if host.contains(':') => Some(format!("[{host}]")),
+            host => Some(host.into()),
+        }
+    }
+}
+
+impl Display for EngineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Docker => "Docker",
+            Self::Podman => "Podman",
+        })
+    }
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind, self.version)?;
+
+        if self.rootless {
+            f.write_str(", rootless")?;
+        }
+
+        write!(f, ", at {}", self.endpoint)
+    }
+}
+
+/// The configured connection, or the first engine that answers on a well-known socket.
+pub async fn connect(connection: Option<&Connection>) -> Result<(Docker, Engine)> {
+    if let Some(connection) = connection {
+        return probe(connection)
+            .await
+            .wrap_err_with(|| format!("Failed to reach the engine at {}", connection.endpoint));
+    }
+
+    let mut errors = vec![];
+
+    for connection in candidates() {
+        match probe(&connection).await {
+            Ok(found) => return Ok(found),
+            Err(err) => errors.push(format!("{}: {err}", connection.endpoint)),
+        }
+    }
+
+    match errors.is_empty() {
+        true => bail!("Found no Docker or Podman socket, start an engine or set its endpoint"),
+        false => bail!(
+            "No engine answered, set the endpoint if it's elsewhere\n{}",
+            errors.join("\n")
+        ),
+    }
+}
+
+/// `DOCKER_HOST` if it's set, then the sockets of rootful and rootless Docker and Podman that
+/// exist.
+pub fn candidates() -> Vec<Connection> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("/run/user/{}", nix::unistd::getuid())));
+
+    let sockets = [
+        PathBuf::from("/var/run/docker.sock"),
+        runtime_dir.join("docker.sock"),
+        runtime_dir.join("podman/podman.sock"),
+        PathBuf::from("/run/podman/podman.sock"),
+    ];
+
+    // The Docker CLI only uses the certificates when verification is asked for
+    let docker_host = env::var("DOCKER_HOST")
+        .ok()
+        .filter(|host| !host.is_empty())
+        .map(|host| Connection {
+            endpoint: host,
+            cert_path: env::var_os("DOCKER_TLS_VERIFY")
+                .filter(|verify| !verify.is_empty())
+                .map(|_| {
+                    env::var_os("DOCKER_CERT_PATH")
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| env::home_dir().unwrap_or_default().join(".docker"))
+                }),
+        });
+
+    docker_host
+        .into_iter()
+        .chain(
+            sockets
+                .into_iter()
+                .filter(|socket| socket.exists())
+                .map(|socket| Connection::new(format!("unix://{}", socket.display()))),
+        )
+        .collect()
+}
+
+/// A client for `unix://` sockets and plain paths to them, `tcp://` endpoints with or without
+/// TLS, and `ssh://` hosts.
+///
+/// SSH runs `docker system dial-stdio` on the host through the local `ssh`, so it uses the
+/// user's keys and `~/.ssh/config`, and needs the Docker CLI on the other end.
+pub fn client(connection: &Connection) -> Result<Docker> {
+    let endpoint = connection.endpoint.as_str();
+
+    let client = match (endpoint.split_once("://"), &connection.cert_path) {
+        (Some(("unix", _)), _) => {
+            Docker::connect_with_unix(endpoint, TIMEOUT, API_DEFAULT_VERSION)?
+        }
+        (None, _) if endpoint.starts_with('/') => {
+            Docker::connect_with_unix(endpoint, TIMEOUT, API_DEFAULT_VERSION)?
+        }
+        (Some(("tcp" | "https", _)), Some(certs)) => {
+            for file in ["ca.pem", "cert.pem", "key.pem"] {
+                if !certs.join(file).is_file() {
+                    bail!("{} is missing {file}", certs.display());
+                }
+            }
+
+            Docker::connect_with_ssl(
+                endpoint,
+                &certs.join("key.pem"),
+                &certs.join("cert.pem"),
+                &certs.join("ca.pem"),
+                TIMEOUT,
+                API_DEFAULT_VERSION,
+            )?
+        }
+        (Some(("https", _)), None) => {
+            bail!("{endpoint} uses TLS, set the folder with its certificates")
+        }
+        (Some(("http", _)), _) | (Some(("tcp", _)), None) => {
+            Docker::connect_with_http(endpoint, TIMEOUT, API_DEFAULT_VERSION)?
+        }
+        (Some(("ssh", _)), _) => Docker::connect_with_ssh(endpoint, TIMEOUT, API_DEFAULT_VERSION)?,
+        _ => bail!(
+            "Unsupported endpoint {endpoint}, expected unix:///path/to/engine.sock, \
+             tcp://host:port or ssh://user@host"
+        ),
+    };
+
+    Ok(client)
+}
+
+async fn probe(connection: &Connection) -> Result<(Docker, Engine)> {
+    let client = client(connection)?;
+
+    let version = client.version().await?;
+    let info = client.info().await?;
+
+    let podman = version
+        .platform
+        .iter()
+        .map(|platform| platform.name.as_str())
+        .chain(
+            version
+                .components
+                .iter()
+                .flatten()
+                .map(|component| component.name.as_str()),
+        )
+        .any(|name| name.contains("Podman"));
+
+    let rootless = info
+        .security_options
+        .iter()
+        .flatten()
+        .any(|option| option.split(',').any(|part| part == "name=rootless"));
+
+    let engine = Engine {
+        kind: match podman {
+            true => EngineKind::Podman,
+            false => EngineKind::Docker,
+        },
+        version: version.version.unwrap_or_else(|| "unknown".into()),
+        rootless,
+        endpoint: connection.endpoint.clone(),
+        remote_host: connection.remote_host(),
+        mem_total: info.mem_total.and_then(|bytes| u64::try_from(bytes).ok()),
+        ncpu: info.ncpu.and_then(|cpus| u32::try_from(cpus).ok()),
+    };
+
+    Ok((client, engine))
+}
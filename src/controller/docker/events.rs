@@ -0,0 +1,137 @@
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+use bollard::{
+    Docker,
+    query_parameters::EventsOptionsBuilder,
+    secret::{EventMessage, EventMessageTypeEnum},
+};
+use color_eyre::Result;
+use iced::futures::{SinkExt, StreamExt};
+
+use crate::{
+    app::{AppMsg, AppSubscription},
+    controller::docker::{ContainerData, Engine, WINDOWS_IMAGE, list_containers, load_containers},
+    util::Arced,
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum ContainerEvent {
+    Updated(ContainerData),
+    Removed(String),
+}
+
+/// Subscriptions are identified by their data, the client itself can't be hashed but its
+/// endpoint tells a switch to another engine apart.
+struct EventSource(Docker, Engine);
+
+impl Hash for EventSource {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        "docker-events".hash(state);
+        self.1.endpoint.hash(state);
+    }
+}
+
+pub fn subscription(client: Docker, engine: Engine) -> AppSubscription {
+    AppSubscription::run_with(EventSource(client, engine), |source| {
+        let client = source.0.clone();
+        let remote_host = source.1.remote_host.clone();
+
+        iced::stream::channel(100, move |mut output| async move {
+            loop {
+                // Anything could've happened while we weren't listening
+                let containers = list_containers(&client, remote_host.as_deref())
+                    .await
+                    .arced();
+                if output
+                    .send(AppMsg::RefreshDockerContainersRes(containers))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+
+                let mut events = client.events(Some(
+                    EventsOptionsBuilder::new()
+                        .filters(&HashMap::from_iter([("type", vec!["container"])]))
+                        .build(),
+                ));
+
+                while let Some(event) = events.next().await {
+                    let event = match event {
+                        Ok(event) => event,
+                        Err(err) => {
+                            tracing::error!("Docker event stream failed: {err}");
+                            break;
+                        }
+                    };
+
+                    let Some(id) = windows_container_id(&event) else {
+                        continue;
+                    };
+
+                    let event = container_event(&client, remote_host.as_deref(), id)
+                        .await
+                        .arced();
+                    if output
+                        .send(AppMsg::DockerContainerEvent(event))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        })
+    })
+}
+
+/// Id of the event's container if it's one of ours and the event can change its state.
+fn windows_container_id(event: &EventMessage) -> Option<&str> {
+    if event.typ != Some(EventMessageTypeEnum::CONTAINER) {
+        return None;
+    }
+
+    // `docker exec` doesn't change anything we care about and is noisy
+    if event
+        .action
+        .as_deref()
+        .is_some_and(|action| action.starts_with("exec_"))
+    {
+        return None;
+    }
+
+    let actor = event.actor.as_ref()?;
+    let image = actor.attributes.as_ref()?.get("image")?;
+
+    is_windows_image(image)
+        .then_some(actor.id.as_deref())
+        .flatten()
+}
+
+/// Podman reports images with their registry, `docker.io/dockurr/windows`.
+pub fn is_windows_image(image: &str) -> bool {
+    image
+        .trim_start_matches("docker.io/")
+        .strip_prefix(WINDOWS_IMAGE)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '@']))
+}
+
+async fn container_event(
+    client: &Docker,
+    remote_host: Option<&str>,
+    id: &str,
+) -> Result<ContainerEvent> {
+    let data = load_containers(client, remote_host, HashMap::from_iter([("id", vec![id])]))
+        .await?
+        .into_iter()
+        .next();
+
+    Ok(match data {
+        Some(data) => ContainerEvent::Updated(data),
+        None => ContainerEvent::Removed(id.into()),
+    })
+}
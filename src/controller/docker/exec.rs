@@ -0,0 +1,49 @@
+use bollard::{
+    Docker,
+    exec::{StartExecOptions, StartExecResults},
+    secret::ExecConfig,
+};
+use color_eyre::{Result, eyre::bail};
+use iced::futures::StreamExt;
+
+/// Runs the command in the container and returns what it printed, failing if it exits with an error.
+pub async fn exec(client: &Docker, container_name: &str, cmd: &[&str]) -> Result<String> {
+    let created = client
+        .create_exec(
+            container_name,
+            ExecConfig {
+                cmd: Some(cmd.iter().map(|x| x.to_string()).collect()),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    let mut output = String::new();
+    let started = client
+        .start_exec(&created.id, Option::<StartExecOptions>::None)
+        .await?;
+
+    if let StartExecResults::Attached {
+        output: mut logs, ..
+    } = started
+    {
+        while let Some(log) = logs.next().await {
+            output.push_str(&log?.to_string());
+        }
+    }
+
+    let exit_code = client.inspect_exec(&created.id).await?.exit_code;
+
+    if exit_code != Some(0) {
+        bail!(
+            "`{}` exited with {}: {}",
+            cmd.join(" "),
+            exit_code.map_or_else(|| "an unknown status".into(), |c| c.to_string()),
+            output.trim()
+        );
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,391 @@
+use std::{collections::VecDeque, fmt::Display, hash::Hash};
+
+use bollard::{Docker, query_parameters::LogsOptionsBuilder};
+use iced::futures::{SinkExt, StreamExt};
+
+use crate::app::{AppMsg, AppSubscription};
+
+/// How many raw log lines are kept around for the log pane.
+const MAX_LINES: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+    /// The log is being followed from the start again.
+    Reset,
+    Lines(Vec<String>),
+    Error(String),
+}
+
+/// Log of a `dockurr/windows` container together with what we could make out of it.
+#[derive(Debug, Default)]
+pub struct BootLog {
+    pub lines: VecDeque<String>,
+    pub progress: BootProgress,
+}
+
+impl BootLog {
+    pub fn apply(&mut self, event: LogEvent) {
+        match event {
+            LogEvent::Reset => *self = Self::default(),
+            LogEvent::Lines(lines) => {
+                for line in lines {
+                    self.progress.feed(&line);
+
+                    if self.lines.len() == MAX_LINES {
+                        self.lines.pop_front();
+                    }
+                    self.lines.push_back(line);
+                }
+            }
+            LogEvent::Error(err) => self.progress.error = Some(err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BootStage {
+    #[default]
+    Starting,
+    Downloading,
+    Extracting,
+    Installing,
+    Booting,
+    Ready,
+}
+
+impl BootStage {
+    pub const ALL: [Self; 6] = [
+        Self::Starting,
+        Self::Downloading,
+        Self::Extracting,
+        Self::Installing,
+        Self::Booting,
+        Self::Ready,
+    ];
+}
+
+impl Display for BootStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Starting => "Starting",
+            Self::Downloading => "Downloading Windows",
+            Self::Extracting => "Extracting image",
+            Self::Installing => "Preparing installation",
+            Self::Booting => "Booting",
+            Self::Ready => "Ready",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BootProgress {
+    pub stage: BootStage,
+    /// Download percentage, if the image reported one.
+    pub download: Option<f32>,
+    /// Set when the image had to be downloaded and installed in this run.
+    pub first_boot: bool,
+    pub error: Option<String>,
+}
+
+impl BootProgress {
+    /// Advances the progress with a single line of the container's output.
+    pub fn feed(&mut self, line: &str) {
+        let line = line.trim().trim_start_matches('❯').trim();
+
+        if line.starts_with("Starting Windows") {
+            *self = Self::default();
+            return;
+        }
+
+        if let Some(err) = line.strip_prefix("ERROR:") {
+            self.error = Some(err.trim().into());
+            return;
+        }
+
+        let stage = if line.starts_with("Downloading") || line.starts_with("Requesting") {
+            Some(BootStage::Downloading)
+        } else if line.starts_with("Extracting") {
+            Some(BootStage::Extracting)
+        } else if ["Adding", "Building", "Creating", "Installing"]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+        {
+            Some(BootStage::Installing)
+        } else if line.starts_with("Booting") {
+            Some(BootStage::Booting)
+        } else if line.starts_with("Windows started") {
+            Some(BootStage::Ready)
+        } else {
+            None
+        };
+
+        match stage {
+            Some(stage) => {
+                // Log lines can repeat a stage (e.g. "Adding drivers" after "Building"), never go back
+                if stage > self.stage {
+                    self.stage = stage;
+                }
+
+                if matches!(
+                    stage,
+                    BootStage::Downloading | BootStage::Extracting | BootStage::Installing
+                ) {
+                    self.first_boot = true;
+                }
+            }
+            None if self.stage == BootStage::Downloading => {
+                if let Some(percent) = parse_percentage(line) {
+                    self.download = Some(percent);
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn stage_label(&self, stage: BootStage) -> String {
+        match (stage, self.first_boot) {
+            (BootStage::Booting, true) => "First boot".into(),
+            (stage, _) => stage.to_string(),
+        }
+    }
+}
+
+/// Picks the last `NN%` token out of a progress line (wget and friends).
+fn parse_percentage(line: &str) -> Option<f32> {
+    line.split_whitespace()
+        .rev()
+        .filter_map(|token| token.strip_suffix('%'))
+        .find_map(|value| value.parse::<f32>().ok())
+        .filter(|value| (0.0..=100.0).contains(value))
+}
+
+/// Drops terminal escape sequences (`ESC [ ... letter`), the image colors its output.
+fn strip_ansi(input: &str) -> String {
+    let mut res = String::with_capacity(input.len());
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => {
+                if chars.next() == Some('[') {
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() {
+                            break;
+                        }
+                    }
+                }
+            }
+            c => res.push(c),
+        }
+    }
+
+    res
+}
+
+struct LogSource {
+    client: Docker,
+    container_name: String,
+    /// A new start means a new log to follow.
+    started_at: String,
+}
+
+impl Hash for LogSource {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        "docker-logs".hash(state);
+        self.container_name.hash(state);
+        self.started_at.hash(state);
+    }
+}
+
+pub fn subscription(client: Docker, container_name: String, started_at: String) -> AppSubscription {
+    AppSubscription::run_with(
+        LogSource {
+            client,
+            container_name,
+            started_at,
+        },
+        |source| {
+            let client = source.client.clone();
+            let name = source.container_name.clone();
+
+            iced::stream::channel(100, move |mut output| async move {
+                let msg = |event| AppMsg::DockerLogEvent(name.clone(), event);
+
+                if output.send(msg(LogEvent::Reset)).await.is_err() {
+                    return;
+                }
+
+                let mut logs = client
+                    .logs(
+                        &name,
+                        Some(
+                            LogsOptionsBuilder::new()
+                                .follow(true)
+                                .stdout(true)
+                                .stderr(true)
+                                .tail("all")
+                                .build(),
+                        ),
+                    )
+                    .ready_chunks(64);
+
+                while let Some(chunk) = logs.next().await {
+                    let mut lines = vec![];
+
+                    for log in chunk {
+                        match log {
+                            Ok(log) => lines.extend(
+                                strip_ansi(&log.to_string())
+                                    .split(['\n', '\r'])
+                                    .map(str::trim_end)
+                                    .filter(|line| !line.is_empty())
+                                    .map(String::from),
+                            ),
+                            Err(err) => {
+                                tracing::error!("Failed to follow logs of {name}: {err}");
+                                let _ = output.send(msg(LogEvent::Error(err.to_string()))).await;
+                                return;
+                            }
+                        }
+                    }
+
+                    if output.send(msg(LogEvent::Lines(lines))).await.is_err() {
+                        return;
+                    }
+                }
+            })
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(lines: &[&str]) -> BootProgress {
+        let mut progress = BootProgress::default();
+        for line in lines {
+            progress.feed(line);
+        }
+        progress
+    }
+
+    #[test]
+    fn follows_a_first_boot() {
+        let mut progress = feed(&[
+            "❯ Starting Windows for Docker v4.14...",
+            "❯ For support visit https://github.com/dockur/windows",
+            "❯ CPU: Intel Core i7-8700 | RAM: 12/16 GB | DISK: 98 GB (ext4) | KERNEL: 6.8.0-45-generic...",
+            "❯ Requesting Windows 11 from Microsoft server...",
+            "❯ Downloading Windows 11...",
+        ]);
+        assert_eq!(progress.stage, BootStage::Downloading);
+        assert_eq!(progress.download, None);
+        assert!(progress.first_boot);
+
+        progress
+            .feed("      0K ........ ........ ........ ........ ........ ........  1% 41.2M 2m32s");
+        assert_eq!(progress.download, Some(1.0));
+        progress
+            .feed("3145728K ........ ........ ........ ........ ........ ........ 53% 39.8M 71s");
+        assert_eq!(progress.download, Some(53.0));
+
+        for line in [
+            "❯ Extracting Windows 11 image...",
+            "❯ Adding drivers to image...",
+            "❯ Adding win11x64.xml for automatic installation...",
+            "❯ Building Windows 11 image...",
+        ] {
+            progress.feed(line);
+        }
+        assert_eq!(progress.stage, BootStage::Installing);
+
+        progress.feed("❯ Booting Windows using QEMU v9.1.0...");
+        assert_eq!(progress.stage, BootStage::Booting);
+        assert_eq!(progress.stage_label(BootStage::Booting), "First boot");
+
+        progress.feed(
+            "❯ Windows started successfully, visit http://127.0.0.1:8006/ to view the screen...",
+        );
+        assert_eq!(progress.stage, BootStage::Ready);
+        assert_eq!(progress.error, None);
+    }
+
+    #[test]
+    fn keeps_the_download_across_partial_lines() {
+        // wget's dots arrive in pieces, only the end of a line has the percentage
+        let progress = feed(&[
+            "❯ Downloading Windows 11...",
+            "6291456K ........ ........ ........",
+            " ........ ........ ........ 78% 40.1M 29s",
+            "9437184K ........ ........",
+        ]);
+
+        assert_eq!(progress.download, Some(78.0));
+    }
+
+    #[test]
+    fn ignores_lines_without_a_percentage() {
+        let progress = feed(&[
+            "❯ Downloading Windows 11...",
+            "Resolving software.download.prss.microsoft.com... 23.32.152.10",
+            "HTTP request sent, awaiting response... 200 OK",
+            "Length: 6140975104 (5.7G) [application/octet-stream]",
+            "Saving to: '/storage/win11x64.iso'",
+        ]);
+
+        assert_eq!(progress.stage, BootStage::Downloading);
+        assert_eq!(progress.download, None);
+    }
+
+    #[test]
+    fn booting_an_installed_image_isnt_a_first_boot() {
+        let progress = feed(&[
+            "❯ Starting Windows for Docker v4.14...",
+            "❯ Booting Windows using QEMU v9.1.0...",
+        ]);
+
+        assert_eq!(progress.stage, BootStage::Booting);
+        assert!(!progress.first_boot);
+        assert_eq!(progress.stage_label(BootStage::Booting), "Booting");
+    }
+
+    #[test]
+    fn never_goes_back_and_restarts_fresh() {
+        let mut progress = feed(&[
+            "❯ Booting Windows using QEMU v9.1.0...",
+            "❯ Adding drivers to image...",
+        ]);
+        assert_eq!(progress.stage, BootStage::Booting);
+
+        progress.feed("❯ ERROR: KVM acceleration not available (device file missing)");
+        assert_eq!(
+            progress.error.as_deref(),
+            Some("KVM acceleration not available (device file missing)")
+        );
+
+        progress.feed("❯ Starting Windows for Docker v4.14...");
+        assert_eq!(progress.stage, BootStage::Starting);
+        assert_eq!(progress.error, None);
+    }
+
+    #[test]
+    fn parses_percentages() {
+        assert_eq!(parse_percentage("  ........ 7% 35.2M 3m10s"), Some(7.0));
+        assert_eq!(parse_percentage("..... 100% 41.9M=2m18s"), Some(100.0));
+        assert_eq!(parse_percentage("1% of 50% done"), Some(50.0));
+        assert_eq!(parse_percentage("150% 3s"), None);
+        assert_eq!(parse_percentage("........ ........"), None);
+        assert_eq!(parse_percentage(""), None);
+    }
+
+    #[test]
+    fn strips_colors() {
+        assert_eq!(
+            strip_ansi("\u{1b}[1;34m❯ \u{1b}[1;36mBooting Windows...\u{1b}[0m"),
+            "❯ Booting Windows..."
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use bollard::{
+    Docker, errors::Error, query_parameters::CreateImageOptionsBuilder, secret::CreateImageInfo,
+};
+use color_eyre::{Report, Result, eyre::bail};
+use iced::futures::{SinkExt, StreamExt, channel::mpsc};
+
+/// Layers report every chunk, the UI doesn't need to hear about all of them.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// An image name split the way the engine pulls it, `dockurr/windows`, `dockurr/windows:4.14`
+/// or pinned with `dockurr/windows@sha256:...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    /// With its registry if it has one, `ghcr.io/dockur/windows`.
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    /// What the engine is asked to pull, a digest wins over the tag.
+    pub fn reference(&self) -> &str {
+        self.digest
+            .as_deref()
+            .or(self.tag.as_deref())
+            .unwrap_or("latest")
+    }
+
+    /// A digest always names the same image, there's nothing newer to pull.
+    pub fn pinned(&self) -> bool {
+        self.digest.is_some()
+    }
+}
+
+impl FromStr for ImageRef {
+    type Err = Report;
+
+    fn from_str(image: &str) -> Result<Self> {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest)),
+            None => (image, None),
+        };
+        // A colon before the last slash is the port of a registry
+        let (repository, tag) = match name.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag)),
+            _ => (name, None),
+        };
+
+        if repository.split('/').any(|part| part.is_empty())
+            || repository
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_ascii_uppercase())
+        {
+            bail!("{image} isn't an image name, expected something like dockurr/windows:latest");
+        }
+
+        if let Some(tag) = tag
+            && (tag.is_empty()
+                || tag.len() > 128
+                || !tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        {
+            bail!("{tag:?} isn't a tag, they're made of letters, digits, `_`, `.` and `-`");
+        }
+
+        if let Some(digest) = digest
+            && !digest
+                .strip_prefix("sha256:")
+                .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            bail!("{digest:?} isn't a digest, expected sha256: and 64 hex digits");
+        }
+
+        Ok(Self {
+            repository: repository.into(),
+            tag: tag.map(String::from),
+            digest: digest.map(String::from),
+        })
+    }
+}
+
+impl Display for ImageRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.repository)?;
+
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How far a pull got, per layer like `docker pull` shows it.
+#[derive(Debug, Clone, Default)]
+pub struct PullProgress {
+    pub image: String,
+    /// In the order the engine first mentioned them.
+    pub layers: Vec<LayerProgress>,
+    /// The last message that isn't about a layer, `Status: Image is up to date for ...`.
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayerProgress {
+    pub id: String,
+    /// `Waiting`, `Downloading`, `Extracting`, `Pull complete`...
+    pub status: String,
+    /// Bytes of the current step, when it reports them.
+    pub current: u64,
+    pub total: u64,
+}
+
+impl PullProgress {
+    pub fn new(image: impl Into<String>) -> Self {
+        Self {
+            image: image.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn apply(&mut self, info: CreateImageInfo) {
+        let status = info.status.unwrap_or_default();
+
+        // The first message carries the tag as its id, `Pulling from dockurr/windows`
+        let Some(id) = info.id.filter(|_| !status.starts_with("Pulling from")) else {
+            self.status = Some(status).filter(|s| !s.is_empty());
+            return;
+        };
+
+        let (current, total) = info
+            .progress_detail
+            .map(|detail| {
+                (
+                    detail.current.unwrap_or_default().max(0) as u64,
+                    detail.total.unwrap_or_default().max(0) as u64,
+                )
+            })
+            .unwrap_or_default();
+
+        let layer = LayerProgress {
+            id,
+            status,
+            current,
+            total,
+        };
+
+        match self.layers.iter_mut().find(|l| l.id == layer.id) {
+            Some(existing) => *existing = layer,
+            None => self.layers.push(layer),
+        }
+    }
+
+    /// Layers that are downloaded and extracted, or were already there.
+    pub fn done(&self) -> usize {
+        self.layers.iter().filter(|l| l.done()).count()
+    }
+
+    /// From 0 to 1, downloading counts for half of each layer and extracting for the other.
+    pub fn fraction(&self) -> f32 {
+        if self.layers.is_empty() {
+            return 0.0;
+        }
+
+        self.layers.iter().map(LayerProgress::fraction).sum::<f32>() / self.layers.len() as f32
+    }
+}
+
+impl LayerProgress {
+    pub fn done(&self) -> bool {
+        matches!(self.status.as_str(), "Pull complete" | "Already exists")
+    }
+
+    pub fn fraction(&self) -> f32 {
+        let step = match self.total {
+            0 => 0.0,
+            total => self.current as f32 / total as f32,
+        };
+
+        match self.status.as_str() {
+            _ if self.done() => 1.0,
+            "Downloading" => step / 2.0,
+            "Verifying Checksum" | "Download complete" => 0.5,
+            "Extracting" => 0.5 + step / 2.0,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Display for PullProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.layers.is_empty() {
+            true => write!(f, "Pulling {}", self.image),
+            false => write!(
+                f,
+                "Pulling {}, {} of {} layers",
+                self.image,
+                self.done(),
+                self.layers.len()
+            ),
+        }
+    }
+}
+
+impl Display for LayerProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.id, self.status)?;
+
+        if self.total > 0 && !self.done() {
+            write!(
+                f,
+                " {:.1} of {:.1} MB",
+                self.current as f64 / 1e6,
+                self.total as f64 / 1e6
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Id of the image the engine has under the name, `None` if it was never pulled.
+pub async fn local_image(client: &Docker, image: &str) -> Result<Option<String>> {
+    match client.inspect_image(image).await {
+        Ok(inspect) => Ok(inspect.id),
+        Err(Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Pulls the image, sending how far it got along the way.
+pub async fn pull(
+    client: &Docker,
+    image: &str,
+    mut progress: Option<mpsc::Sender<PullProgress>>,
+) -> Result<PullProgress> {
+    let image_ref = image.parse::<ImageRef>()?;
+    let mut state = PullProgress::new(image);
+
+    let mut stream = client.create_image(
+        Some(
+            CreateImageOptionsBuilder::new()
+                .from_image(&image_ref.repository)
+                .tag(image_ref.reference())
+                .build(),
+        ),
+        None,
+        None,
+    );
+
+    let mut reported = Instant::now();
+
+    while let Some(info) = stream.next().await {
+        state.apply(info?);
+
+        if let Some(progress) = progress.as_mut()
+            && reported.elapsed() >= REPORT_INTERVAL
+        {
+            let _ = progress.send(state.clone()).await;
+            reported = Instant::now();
+        }
+    }
+
+    if let Some(progress) = progress.as_mut() {
+        let _ = progress.send(state.clone()).await;
+    }
+
+    tracing::info!(
+        "Pulled {image}: {}",
+        state.status.as_deref().unwrap_or("done")
+    );
+
+    Ok(state)
+}
+
+/// Pulls the image unless the engine already has it.
+pub async fn ensure_image(
+    client: &Docker,
+    image: &str,
+    progress: Option<mpsc::Sender<PullProgress>>,
+) -> Result<()> {
+    if local_image(client, image).await?.is_none() {
+        pull(client, image, progress).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use bollard::{
+    Docker,
+    query_parameters::{InspectContainerOptions, StopContainerOptionsBuilder},
+};
+use color_eyre::Result;
+use iced::futures::{SinkExt, channel::mpsc};
+
+use crate::controller::{
+    docker::stop_options,
+    guest::GuestClient,
+    qemu::{Monitor, VmCommand},
+    state::{DockerServiceState, ShutdownOutcome, ShutdownRecord, ShutdownVia},
+};
+
+/// How often the container is looked at while Windows shuts down.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Windows already had its grace period when the container gets stopped after a shutdown.
+const STOP_TIMEOUT_SECS: i32 = 10;
+
+#[derive(Debug, Clone)]
+pub enum ShutdownProgress {
+    Requesting,
+    Waiting {
+        via: ShutdownVia,
+        elapsed: Duration,
+        grace_period: Duration,
+    },
+    Stopping,
+}
+
+impl Display for ShutdownProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Requesting => f.write_str("Asking Windows to shut down"),
+            Self::Waiting {
+                via,
+                elapsed,
+                grace_period,
+            } => write!(
+                f,
+                "Windows is shutting down through {via}, {}s of {}s",
+                elapsed.as_secs(),
+                grace_period.as_secs()
+            ),
+            Self::Stopping => f.write_str("Stopping the container"),
+        }
+    }
+}
+
+/// Asks Windows to shut down, through the guest tools at `guest` if they answer and the ACPI
+/// power button otherwise, waits for it up to the grace period and stops the container after.
+pub async fn graceful_stop(
+    client: Docker,
+    service: DockerServiceState,
+    guest: Option<String>,
+    monitor: Monitor,
+    mut progress: mpsc::Sender<ShutdownProgress>,
+) -> Result<ShutdownRecord> {
+    let name = &service.container_name;
+    let grace_period = *service.stop_grace_period;
+    let start = Instant::now();
+
+    let _ = progress.send(ShutdownProgress::Requesting).await;
+
+    let booted = started_at(&client, name).await?;
+    let via = match booted {
+        Some(_) => request_shutdown(&service, guest.as_deref(), &monitor).await,
+        None => None,
+    };
+
+    let outcome = match via {
+        None => ShutdownOutcome::Forced,
+        Some(via) => loop {
+            // Stopped, or already brought back up by its restart policy once QEMU exited
+            if started_at(&client, name).await? != booted {
+                break ShutdownOutcome::Clean;
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= grace_period {
+                break ShutdownOutcome::TimedOut;
+            }
+
+            let _ = progress
+                .send(ShutdownProgress::Waiting {
+                    via,
+                    elapsed,
+                    grace_period,
+                })
+                .await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        },
+    };
+
+    let _ = progress.send(ShutdownProgress::Stopping).await;
+
+    // Also marks the container as stopped on purpose, so its restart policy leaves it alone
+    let options = match outcome {
+        ShutdownOutcome::Forced => {
+            stop_options(service.stop_grace_period, service.stop_signal.as_deref())
+        }
+        _ => StopContainerOptionsBuilder::new()
+            .t(STOP_TIMEOUT_SECS)
+            .build(),
+    };
+    client.stop_container(name, Some(options)).await?;
+
+    Ok(ShutdownRecord::new(
+        service.id.clone(),
+        via,
+        outcome,
+        start.elapsed(),
+    ))
+}
+
+/// `None` if nothing took the request.
+async fn request_shutdown(
+    service: &DockerServiceState,
+    guest: Option<&str>,
+    monitor: &Monitor,
+) -> Option<ShutdownVia> {
+    let container_name = &service.container_name;
+
+    if let Some(address) = guest {
+        let res = async {
+            GuestClient::connect(address, &service.guest_token)
+                .await?
+                .shutdown(false)
+                .await
+        }
+        .await;
+
+        match res {
+            Ok(()) => return Some(ShutdownVia::GuestTools),
+            Err(err) => {
+                tracing::warn!("The guest tools of {container_name} didn't shut down: {err}")
+            }
+        }
+    }
+
+    match monitor.execute(VmCommand::SystemPowerdown).await {
+        Ok(_) => Some(ShutdownVia::Acpi),
+        Err(err) => {
+            tracing::warn!("Failed to press the power button of {container_name}: {err}");
+            None
+        }
+    }
+}
+
+/// When the running container was started, `None` if it isn't running.
+async fn started_at(client: &Docker, container_name: &str) -> Result<Option<String>> {
+    let state = client
+        .inspect_container(container_name, Option::<InspectContainerOptions>::None)
+        .await?
+        .state
+        .unwrap_or_default();
+
+    let running = state.running == Some(true) && state.paused != Some(true);
+
+    Ok(state.started_at.filter(|_| running))
+}
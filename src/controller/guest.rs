@@ -0,0 +1,459 @@
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, BufReader},
+    net::TcpStream,
+};
+use winjet_gt_proto::{
+    DEFAULT_PORT, Frame, InstalledApp, PROTOCOL_VERSION, Request, Response, SessionInfo,
+    TOKEN_FILE,
+    tokio::{read_frame, write_frame},
+};
+
+use crate::controller::{
+    docker::{ContainerData, DockerContainerExt},
+    rdp::PathMap,
+    state::DockerServiceState,
+};
+
+/// The guest tools answer right away, anything slower means they're gone.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Except when they have to look through everything installed and extract icons.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The published port of the guest tools if there is one, the container's own address otherwise.
+///
+/// `dockurr/windows` forwards the port to Windows when it's listed in `USER_PORTS`.
+pub fn guest_address(container: &ContainerData) -> Result<String> {
+    if let Some((host, port)) = container.published_tcp(DEFAULT_PORT) {
+        return Ok(format!("{host}:{port}"));
+    }
+    if container.remote_host().is_some() {
+        bail!("Publish port {DEFAULT_PORT}/tcp to reach the guest tools on a remote engine");
+    }
+
+    container
+        .ip_address()
+        .map(|ip| format!("{ip}:{DEFAULT_PORT}"))
+        .ok_or_else(|| eyre!("The container has no address to reach the guest tools on"))
+}
+
+/// Leaves the token of `service` in its shared folders, where the guest tools pick it up.
+///
+/// Without a shared folder the token has to be put next to the guest tools by hand, as it has
+/// for containers on remote engines.
+pub fn provision_token(service: &DockerServiceState) -> Result<()> {
+    for folder in PathMap::new(service, false).shared_folders() {
+        // Docker would create it owned by root
+        fs::create_dir_all(folder)?;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(folder.join(TOKEN_FILE))?;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(service.guest_token.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// A connection to the guest tools (`winjet-gt`) running in Windows.
+pub struct GuestClient<S = TcpStream> {
+    stream: BufReader<S>,
+    next_id: u64,
+    /// Name and version the guest tools introduced themselves with.
+    pub agent: String,
+}
+
+impl GuestClient {
+    pub async fn connect(address: &str, token: &str) -> Result<Self> {
+        let stream = tokio::time::timeout(REQUEST_TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| eyre!("The guest tools at {address} didn't answer"))??;
+
+        Self::handshake(stream, token).await
+    }
+
+    pub async fn connect_to(
+        container: &ContainerData,
+        service: &DockerServiceState,
+    ) -> Result<Self> {
+        Self::connect(&guest_address(container)?, &service.guest_token).await
+    }
+}
+
+impl<S> GuestClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Says hello over any stream with the token the guest was provisioned with, failing if it
+    /// speaks another protocol version.
+    pub async fn handshake(stream: S, token: &str) -> Result<Self> {
+        let mut client = Self {
+            stream: BufReader::new(stream),
+            next_id: 0,
+            agent: String::new(),
+        };
+
+        match client
+            .request(Request::Hello {
+                version: PROTOCOL_VERSION,
+                token: token.into(),
+            })
+            .await?
+        {
+            Response::Hello { version, agent } if version == PROTOCOL_VERSION => {
+                client.agent = agent;
+                Ok(client)
+            }
+            Response::Hello { version, .. } => {
+                bail!("The guest tools speak protocol {version}, winjet speaks {PROTOCOL_VERSION}")
+            }
+            res => Err(unexpected(res)),
+        }
+    }
+
+    /// The round trip time.
+    pub async fn ping(&mut self) -> Result<Duration> {
+        let start = Instant::now();
+
+        match self.request(Request::Ping).await? {
+            Response::Pong => Ok(start.elapsed()),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    pub async fn list_apps(&mut self) -> Result<Vec<InstalledApp>> {
+        match self
+            .request_within(Request::ListApps, DISCOVERY_TIMEOUT)
+            .await?
+        {
+            Response::Apps { apps } => Ok(apps),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    /// The process id of the started program.
+    pub async fn launch_app(&mut self, program: &str, args: Option<&str>) -> Result<u32> {
+        let request = Request::LaunchApp {
+            program: program.into(),
+            args: args.map(Into::into),
+        };
+
+        match self.request(request).await? {
+            Response::Launched { pid } => Ok(pid),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    pub async fn sessions(&mut self) -> Result<Vec<SessionInfo>> {
+        match self.request(Request::QuerySession).await? {
+            Response::Session { sessions } => Ok(sessions),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    /// Returns once Windows started shutting down, not once it's off.
+    pub async fn shutdown(mut self, force: bool) -> Result<()> {
+        match self.request(Request::Shutdown { force }).await? {
+            Response::ShuttingDown => Ok(()),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    async fn request(&mut self, body: Request) -> Result<Response> {
+        self.request_within(body, REQUEST_TIMEOUT).await
+    }
+
+    async fn request_within(&mut self, body: Request, timeout: Duration) -> Result<Response> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let exchange = async {
+            write_frame(&mut self.stream, &Frame { id, body }).await?;
+
+            match read_frame::<Response>(&mut self.stream).await? {
+                Some(frame) if frame.id == id => Result::Ok(frame.body),
+                Some(frame) => bail!(
+                    "The guest tools answered request {} instead of {id}",
+                    frame.id
+                ),
+                None => bail!("The guest tools closed the connection"),
+            }
+        };
+
+        let res = tokio::time::timeout(timeout, exchange)
+            .await
+            .map_err(|_| eyre!("The guest tools didn't answer in time"))??;
+
+        match res {
+            Response::Error { message } => bail!("The guest tools failed: {message}"),
+            res => Ok(res),
+        }
+    }
+}
+
+fn unexpected(res: Response) -> color_eyre::Report {
+    eyre!("The guest tools answered with {res:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf},
+        runtime::Handle,
+        task::JoinHandle,
+    };
+    use winjet_gt_proto::{
+        AppSource,
+        guest::{Guest, Served, serve},
+    };
+
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    struct FakeGuest;
+
+    impl Guest for FakeGuest {
+        fn agent(&self) -> String {
+            "fake-guest 1.0".into()
+        }
+
+        fn list_apps(&mut self) -> Result<Vec<InstalledApp>, String> {
+            Ok(installed_apps())
+        }
+
+        fn launch_app(&mut self, _: &str, _: Option<&str>) -> Result<u32, String> {
+            Err("Nothing to launch".into())
+        }
+
+        fn sessions(&mut self) -> Result<Vec<SessionInfo>, String> {
+            Ok(vec![])
+        }
+
+        fn shutdown(&mut self, _: bool) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn installed_apps() -> Vec<InstalledApp> {
+        vec![
+            InstalledApp {
+                name: "Notepad".into(),
+                program: r"C:\Windows\System32\notepad.exe".into(),
+                args: None,
+                icon: Some(b"BM".to_vec()),
+                source: AppSource::StartMenu,
+            },
+            InstalledApp {
+                name: "Calculator".into(),
+                program: r"C:\Windows\explorer.exe".into(),
+                args: Some(
+                    r"shell:AppsFolder\Microsoft.WindowsCalculator_8wekyb3d8bbwe!App".into(),
+                ),
+                icon: None,
+                source: AppSource::Uwp,
+            },
+        ]
+    }
+
+    /// Lets the blocking guest side use its end of the duplex stream.
+    struct Blocking<T>(T, Handle);
+
+    impl Read for Blocking<ReadHalf<DuplexStream>> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1.block_on(self.0.read(buf))
+        }
+    }
+
+    impl Write for Blocking<WriteHalf<DuplexStream>> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1.block_on(self.0.write(buf))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.1.block_on(self.0.flush())
+        }
+    }
+
+    /// The host's end of a stream the guest tools serve with `token`.
+    fn served(token: &'static str) -> (DuplexStream, JoinHandle<io::Result<Served>>) {
+        let (host, guest) = tokio::io::duplex(4096);
+        let (read, write) = tokio::io::split(guest);
+        let handle = Handle::current();
+
+        let serving = tokio::task::spawn_blocking(move || {
+            serve(
+                &mut FakeGuest,
+                token,
+                std::io::BufReader::new(Blocking(read, handle.clone())),
+                Blocking(write, handle),
+            )
+        });
+
+        (host, serving)
+    }
+
+    /// The host's end of a stream some other guest answers the hello on with `response`.
+    fn answering(id: u64, response: Response) -> DuplexStream {
+        let (host, guest) = tokio::io::duplex(4096);
+
+        tokio::spawn(async move {
+            let mut guest = BufReader::new(guest);
+            let _ = read_frame::<Request>(&mut guest).await;
+            let _ = write_frame(&mut guest, &Frame { id, body: response }).await;
+        });
+
+        host
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shakes_hands_with_the_token() {
+        let (stream, serving) = served(TOKEN);
+
+        let mut client = GuestClient::handshake(stream, TOKEN).await.unwrap();
+        assert_eq!(client.agent, "fake-guest 1.0");
+        client.ping().await.unwrap();
+
+        let err = client.launch_app("notepad.exe", None).await.unwrap_err();
+        assert!(err.to_string().contains("Nothing to launch"));
+
+        drop(client);
+        assert_eq!(serving.await.unwrap().unwrap(), Served::Closed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lists_the_installed_apps() {
+        let (stream, serving) = served(TOKEN);
+
+        let mut client = GuestClient::handshake(stream, TOKEN).await.unwrap();
+        assert_eq!(client.list_apps().await.unwrap(), installed_apps());
+
+        drop(client);
+        assert_eq!(serving.await.unwrap().unwrap(), Served::Closed);
+    }
+
+    #[tokio::test]
+    async fn refuses_other_answers_to_list_apps() {
+        let (host, guest) = tokio::io::duplex(4096);
+
+        tokio::spawn(async move {
+            let mut guest = BufReader::new(guest);
+            let hello = Response::Hello {
+                version: PROTOCOL_VERSION,
+                agent: "winjet-gt 1.0".into(),
+            };
+
+            for body in [hello, Response::Pong] {
+                let Ok(Some(Frame { id, .. })) = read_frame::<Request>(&mut guest).await else {
+                    return;
+                };
+                let _ = write_frame(&mut guest, &Frame { id, body }).await;
+            }
+        });
+
+        let mut client = GuestClient::handshake(host, TOKEN).await.unwrap();
+        let err = client.list_apps().await.unwrap_err();
+        assert!(err.to_string().contains("Pong"), "{err}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn is_turned_away_without_the_token() {
+        for token in ["fedcba9876543210", "0123", ""] {
+            let (stream, serving) = served(TOKEN);
+
+            let err = GuestClient::handshake(stream, token).await.err().unwrap();
+            assert!(err.to_string().contains("token"), "{err}");
+            assert_eq!(serving.await.unwrap().unwrap(), Served::Closed);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn nobody_gets_in_without_a_provisioned_token() {
+        let (stream, serving) = served("");
+
+        assert!(GuestClient::handshake(stream, "").await.is_err());
+        assert_eq!(serving.await.unwrap().unwrap(), Served::Closed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_guest_refuses_requests_before_hello() {
+        let (stream, serving) = served(TOKEN);
+        let mut stream = BufReader::new(stream);
+
+        let request = Frame {
+            id: 0,
+            body: Request::Shutdown { force: true },
+        };
+        write_frame(&mut stream, &request).await.unwrap();
+
+        let answer = read_frame::<Response>(&mut stream).await.unwrap().unwrap();
+        assert!(matches!(answer.body, Response::Error { .. }));
+        assert_eq!(serving.await.unwrap().unwrap(), Served::Closed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_guest_refuses_another_protocol_version() {
+        let (stream, serving) = served(TOKEN);
+        let mut stream = BufReader::new(stream);
+
+        let hello = Frame {
+            id: 0,
+            body: Request::Hello {
+                version: PROTOCOL_VERSION - 1,
+                token: TOKEN.into(),
+            },
+        };
+        write_frame(&mut stream, &hello).await.unwrap();
+
+        let answer = read_frame::<Response>(&mut stream).await.unwrap().unwrap();
+        let Response::Error { message } = answer.body else {
+            panic!("Answered with {:?}", answer.body);
+        };
+        assert!(message.contains("protocol"), "{message}");
+        assert_eq!(serving.await.unwrap().unwrap(), Served::Closed);
+    }
+
+    #[tokio::test]
+    async fn refuses_guests_of_another_protocol_version() {
+        let stream = answering(
+            0,
+            Response::Hello {
+                version: PROTOCOL_VERSION + 1,
+                agent: "winjet-gt 9.0".into(),
+            },
+        );
+
+        let err = GuestClient::handshake(stream, TOKEN).await.err().unwrap();
+        assert!(err.to_string().contains("speak protocol"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn refuses_answers_to_other_requests() {
+        let stream = answering(
+            7,
+            Response::Hello {
+                version: PROTOCOL_VERSION,
+                agent: "winjet-gt 1.0".into(),
+            },
+        );
+
+        let err = GuestClient::handshake(stream, TOKEN).await.err().unwrap();
+        assert!(err.to_string().contains("request 7 instead of 0"), "{err}");
+    }
+}
@@ -0,0 +1,93 @@
+use std::{fmt::Display, fs, thread};
+
+use serde::Serialize;
+
+use crate::controller::state::Size;
+
+/// What the host can give to the VM, used to bound the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostResources {
+    pub memory: Size,
+    pub cpus: u32,
+}
+
+impl HostResources {
+    pub fn detect() -> Self {
+        let memory = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| {
+                meminfo
+                    .lines()
+                    .find_map(|line| line.strip_prefix("MemTotal:"))
+                    .and_then(|kib| kib.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            })
+            .map(|kib| Size::from_bytes(kib * 1024))
+            .unwrap_or_else(|| {
+                tracing::warn!("Failed to read the total memory of the host");
+                Size::from_gib(4)
+            });
+
+        let cpus = thread::available_parallelism()
+            .map(|cpus| cpus.get() as u32)
+            .unwrap_or(1);
+
+        Self { memory, cpus }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckLevel {
+    Pass,
+    /// Windows runs, but worse or without some features.
+    Warn,
+    /// Windows won't run until it's fixed.
+    Fail,
+}
+
+/// One item of a host diagnostics report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub level: CheckLevel,
+    /// What was found.
+    pub detail: String,
+    /// How to fix it, for warnings and failures.
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    pub fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: CheckLevel::Pass,
+            detail: detail.into(),
+            remedy: None,
+        }
+    }
+
+    pub fn warn(name: &'static str, detail: impl Into<String>, remedy: impl Into<String>) -> Self {
+        Self {
+            level: CheckLevel::Warn,
+            remedy: Some(remedy.into()),
+            ..Self::pass(name, detail)
+        }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>, remedy: impl Into<String>) -> Self {
+        Self {
+            level: CheckLevel::Fail,
+            ..Self::warn(name, detail, remedy)
+        }
+    }
+}
+
+impl Display for CheckLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        })
+    }
+}
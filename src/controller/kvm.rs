@@ -1,13 +1,27 @@
+mod diagnostics;
+
 use color_eyre::Result;
-use kvm_ioctls::Kvm;
 
-use crate::controller::{Controller, ControllerModule};
+use crate::controller::{
+    Controller, ControllerModule,
+    host::{Check, CheckLevel},
+};
+
+pub use diagnostics::diagnose;
 
 pub type KVMController = Controller<KVMModule>;
 
 #[derive(Debug)]
 pub struct KVMModule {
-    kvm: Kvm,
+    /// What the host offers for virtualization, worst first.
+    pub checks: Vec<Check>,
+}
+
+impl KVMModule {
+    /// Whether QEMU can use KVM at all, warnings only cost features or speed.
+    pub fn usable(&self) -> bool {
+        self.checks.iter().all(|c| c.level != CheckLevel::Fail)
+    }
 }
 
 impl ControllerModule for KVMModule {
@@ -19,8 +33,13 @@ impl ControllerModule for KVMModule {
     where
         Self: Sized,
     {
-        let kvm = Kvm::new()?;
+        let mut checks = diagnose();
+        checks.sort_by_key(|c| std::cmp::Reverse(c.level));
+
+        for check in checks.iter().filter(|c| c.level != CheckLevel::Pass) {
+            tracing::warn!("KVM {}: {} ({})", check.name, check.detail, check.level);
+        }
 
-        Ok(Self { kvm })
+        Ok(Self { checks })
     }
 }
@@ -0,0 +1,293 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+    thread,
+};
+
+use kvm_ioctls::{Cap, Kvm};
+
+use crate::controller::host::Check;
+
+const DEVICE: &str = "/dev/kvm";
+/// The only version the KVM API ever had.
+const API_VERSION: i32 = 12;
+
+/// Without these QEMU can't run a KVM guest.
+const REQUIRED: [(Cap, &str); 4] = [
+    (Cap::UserMemory, "user memory"),
+    (Cap::Irqchip, "in-kernel interrupt controller"),
+    (Cap::Irqfd, "irqfd"),
+    (Cap::Ioeventfd, "ioeventfd"),
+];
+/// Windows runs without these, but slower.
+const RECOMMENDED: [(Cap, &str); 3] = [
+    (Cap::Hyperv, "Hyper-V enlightenments"),
+    (Cap::HypervTime, "Hyper-V reference clock"),
+    (Cap::TscDeadlineTimer, "TSC deadline timer"),
+];
+
+/// Looks at the CPU, `/dev/kvm` and the KVM modules, the checks of `/dev/kvm` itself are left out
+/// if it can't be opened.
+pub fn diagnose() -> Vec<Check> {
+    let mut checks = vec![cpu_flags(Path::new(DEVICE).exists()), group()];
+
+    match Kvm::new() {
+        Ok(kvm) => {
+            checks.push(Check::pass("Device", format!("{DEVICE} is usable")));
+            checks.push(api_version(&kvm));
+            checks.push(extensions(&kvm));
+            checks.push(vcpus(&kvm));
+        }
+        Err(err) => {
+            let err = std::io::Error::from_raw_os_error(err.errno());
+
+            checks.push(match err.kind() {
+                ErrorKind::NotFound => Check::fail(
+                    "Device",
+                    format!("{DEVICE} doesn't exist"),
+                    "Enable virtualization (VT-x or AMD-V) in the firmware settings and load the \
+                     module with `sudo modprobe kvm_intel` or `sudo modprobe kvm_amd`",
+                ),
+                ErrorKind::PermissionDenied => Check::fail(
+                    "Device",
+                    format!("Not allowed to open {DEVICE}"),
+                    "See the permissions check",
+                ),
+                _ => Check::fail(
+                    "Device",
+                    format!("Failed to open {DEVICE}: {err}"),
+                    "Check the kernel log, `sudo dmesg | grep -i kvm`",
+                ),
+            });
+        }
+    }
+
+    checks.push(nested());
+
+    checks
+}
+
+/// Some hypervisors hide the flags but still give their VMs KVM.
+fn cpu_flags(device: bool) -> Check {
+    const NAME: &str = "CPU";
+
+    let Ok(cpuinfo) = fs::read_to_string("/proc/cpuinfo") else {
+        return Check::warn(
+            NAME,
+            "Failed to read /proc/cpuinfo",
+            "Make sure /proc is mounted",
+        );
+    };
+
+    let field = |name: &str| {
+        cpuinfo
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim())
+    };
+    let vendor = field("vendor_id").unwrap_or("Unknown vendor");
+    let flags = field("flags").unwrap_or_default().split_whitespace();
+
+    let mut virtualization = None;
+    let mut hypervisor = false;
+    for flag in flags {
+        match flag {
+            "vmx" => virtualization = Some("VT-x"),
+            "svm" => virtualization = Some("AMD-V"),
+            "hypervisor" => hypervisor = true,
+            _ => {}
+        }
+    }
+
+    match (virtualization, hypervisor) {
+        (Some(virtualization), false) => {
+            Check::pass(NAME, format!("{vendor} with {virtualization}"))
+        }
+        (Some(virtualization), true) => Check::pass(
+            NAME,
+            format!("{vendor} with {virtualization}, passed through by a hypervisor"),
+        ),
+        (None, _) if device => Check::pass(
+            NAME,
+            format!("{vendor}, its virtualization flags are hidden but {DEVICE} exists"),
+        ),
+        (None, false) => Check::fail(
+            NAME,
+            format!("{vendor} without hardware virtualization"),
+            "Enable VT-x or AMD-V (sometimes called SVM) in the firmware settings",
+        ),
+        (None, true) => Check::fail(
+            NAME,
+            format!("{vendor} in a VM that hides hardware virtualization"),
+            "Enable nested virtualization for this VM on its host",
+        ),
+    }
+}
+
+/// `/dev/kvm` is usually `root:kvm` and `0660`, but some distributions open it to everyone.
+fn group() -> Check {
+    const NAME: &str = "Permissions";
+
+    let Ok(metadata) = fs::metadata(DEVICE) else {
+        return Check::pass(NAME, format!("{DEVICE} doesn't exist yet"));
+    };
+
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let ids = |key: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|id| id.parse::<u32>().ok())
+            .collect::<Vec<_>>()
+    };
+    // Real, effective, saved and filesystem
+    let uid = ids("Uid:").get(1).copied();
+    let groups = ids("Groups:");
+
+    let gid = metadata.gid();
+    let (group, members) = group_entry(gid).unwrap_or_else(|| (gid.to_string(), vec![]));
+
+    if uid == Some(0) {
+        return Check::pass(NAME, "Running as root");
+    }
+    if metadata.permissions().mode() & 0o006 == 0o006 {
+        return Check::pass(NAME, format!("{DEVICE} is open to every user"));
+    }
+    if groups.contains(&gid) {
+        return Check::pass(NAME, format!("In the {group} group"));
+    }
+
+    match std::env::var("USER").is_ok_and(|user| members.contains(&user)) {
+        true => Check::fail(
+            NAME,
+            format!("Added to the {group} group after logging in"),
+            "Log out and back in for the group to apply",
+        ),
+        false => Check::fail(
+            NAME,
+            format!("Not in the {group} group"),
+            format!("`sudo usermod -aG {group} $USER`, then log out and back in"),
+        ),
+    }
+}
+
+/// The name and members of the group with the id, from `/etc/group`.
+fn group_entry(gid: u32) -> Option<(String, Vec<String>)> {
+    fs::read_to_string("/etc/group")
+        .ok()?
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.get(2).and_then(|id| id.parse().ok()) == Some(gid))
+        .map(|fields| {
+            (
+                fields[0].to_string(),
+                fields
+                    .get(3)
+                    .map(|members| {
+                        members
+                            .split(',')
+                            .filter(|m| !m.is_empty())
+                            .map(String::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            )
+        })
+}
+
+fn api_version(kvm: &Kvm) -> Check {
+    const NAME: &str = "API version";
+
+    match kvm.get_api_version() {
+        API_VERSION => Check::pass(NAME, API_VERSION.to_string()),
+        version => Check::fail(
+            NAME,
+            format!("{version}, expected {API_VERSION}"),
+            "Update the kernel",
+        ),
+    }
+}
+
+fn extensions(kvm: &Kvm) -> Check {
+    const NAME: &str = "Extensions";
+
+    let missing = |caps: &[(Cap, &'static str)]| {
+        caps.iter()
+            .filter(|(cap, _)| !kvm.check_extension(*cap))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>()
+    };
+
+    let required = missing(&REQUIRED);
+    let recommended = missing(&RECOMMENDED);
+
+    if !required.is_empty() {
+        return Check::fail(
+            NAME,
+            format!("Missing {}", required.join(", ")),
+            "Update the kernel, or use one built with full KVM support",
+        );
+    }
+    if !recommended.is_empty() {
+        return Check::warn(
+            NAME,
+            format!("Missing {}, Windows will be slower", recommended.join(", ")),
+            "Update the kernel",
+        );
+    }
+
+    Check::pass(
+        NAME,
+        format!("All {} needed ones", REQUIRED.len() + RECOMMENDED.len()),
+    )
+}
+
+fn vcpus(kvm: &Kvm) -> Check {
+    const NAME: &str = "vCPUs";
+
+    let max = kvm.get_max_vcpus();
+    let host = thread::available_parallelism()
+        .map(|cpus| cpus.get())
+        .unwrap_or(1);
+
+    match max >= host {
+        true => Check::pass(NAME, format!("Up to {max} per VM")),
+        false => Check::warn(
+            NAME,
+            format!("Up to {max} per VM, the host has {host}"),
+            format!("Keep CPU_CORES at {max} or less"),
+        ),
+    }
+}
+
+/// Only matters for Windows features that virtualize themselves, WSL2, Hyper-V, Sandbox, VBS.
+fn nested() -> Check {
+    const NAME: &str = "Nested virtualization";
+
+    let param = ["kvm_intel", "kvm_amd"].into_iter().find_map(|module| {
+        fs::read_to_string(format!("/sys/module/{module}/parameters/nested"))
+            .ok()
+            .map(|value| (module, value.trim().to_string()))
+    });
+
+    match param {
+        Some((_, value)) if value == "Y" || value == "1" => Check::pass(NAME, "Enabled"),
+        Some((module, _)) => Check::warn(
+            NAME,
+            "Disabled, WSL2 and Hyper-V won't run inside Windows",
+            format!(
+                "Add `options {module} nested=1` to /etc/modprobe.d/kvm.conf and reload {module}"
+            ),
+        ),
+        None => Check::warn(
+            NAME,
+            "Unknown, neither kvm_intel nor kvm_amd is loaded",
+            "Load the module for the CPU, `sudo modprobe kvm_intel` or `sudo modprobe kvm_amd`",
+        ),
+    }
+}
@@ -0,0 +1,562 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::ErrorKind,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use color_eyre::Result;
+use nix::sys::{
+    statfs::{BTRFS_SUPER_MAGIC, statfs},
+    statvfs::statvfs,
+};
+
+use crate::controller::{
+    Controller, ControllerModule,
+    host::{Check, CheckLevel},
+    state::{DockerServiceState, DockurrVar, Size},
+};
+
+/// Where `dockurr/windows` keeps the disk image and its other state.
+const STORAGE_TARGET: &str = "/storage";
+/// The disk image, raw unless `DISK_FMT=qcow2`.
+const DISK_IMAGES: [&str; 2] = ["data.img", "data.qcow2"];
+const TUN: &str = "/dev/net/tun";
+
+pub type PreflightController = Controller<PreflightModule>;
+
+/// Whether the host can give a service what it asks for, before its container is created.
+#[derive(Debug)]
+pub struct PreflightModule {
+    pub checks: Vec<Check>,
+}
+
+/// The service to check, and where the host's filesystem starts.
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    /// `/` outside of tests, device and volume paths are looked up under it.
+    pub root: PathBuf,
+    pub service: DockerServiceState,
+}
+
+impl PreflightModule {
+    pub fn usable(&self) -> bool {
+        self.checks.iter().all(|c| c.level != CheckLevel::Fail)
+    }
+}
+
+impl ControllerModule for PreflightModule {
+    const NAME: &str = "Host";
+
+    type Init = Preflight;
+
+    async fn init_impl(preflight: Self::Init) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut checks = preflight.run();
+        checks.sort_by_key(|c| std::cmp::Reverse(c.level));
+
+        Ok(Self { checks })
+    }
+}
+
+impl Preflight {
+    pub fn new(service: DockerServiceState) -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            service,
+        }
+    }
+
+    pub fn run(&self) -> Vec<Check> {
+        let mut checks = self
+            .service
+            .devices
+            .iter()
+            .filter_map(|device| device.path_on_host.as_deref())
+            .map(|device| self.device(device))
+            .collect::<Vec<_>>();
+
+        checks.extend(self.net_admin());
+
+        for (host, target) in self.binds() {
+            checks.push(self.volume(&host));
+
+            if target == STORAGE_TARGET {
+                checks.push(self.disk_space(&host));
+                checks.extend(self.copy_on_write(&host));
+            }
+        }
+
+        checks
+    }
+
+    /// The path on the host, under [`Preflight::root`].
+    fn host(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    /// Host and container path of the bind mounts, named volumes have nothing to check.
+    fn binds(&self) -> Vec<(String, String)> {
+        self.service
+            .volumes
+            .iter()
+            .filter_map(|volume| {
+                let mut parts = volume.split(':');
+                let (host, target) = (parts.next()?, parts.next()?);
+
+                host.starts_with('/')
+                    .then(|| (host.to_string(), target.trim_end_matches('/').to_string()))
+            })
+            .collect()
+    }
+
+    /// Docker refuses to create a container whose devices are missing, rootless engines also
+    /// need the user to be able to open them.
+    fn device(&self, device: &str) -> Check {
+        const NAME: &str = "Device";
+
+        let path = self.host(device);
+
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Check::fail(
+                    NAME,
+                    format!("{device} doesn't exist, the container won't start"),
+                    match device {
+                        TUN => "Load the module with `sudo modprobe tun`".into(),
+                        "/dev/kvm" => "See the KVM checks".into(),
+                        _ => format!("Remove {device} from the devices of the service"),
+                    },
+                );
+            }
+            Err(err) => {
+                return Check::fail(
+                    NAME,
+                    format!("Failed to look at {device}: {err}"),
+                    format!("Check the permissions of {}", parent(device)),
+                );
+            }
+        };
+
+        if !metadata.file_type().is_char_device() {
+            return Check::fail(
+                NAME,
+                format!("{device} isn't a character device"),
+                format!("Remove {device} from the devices of the service"),
+            );
+        }
+
+        match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(_) => Check::pass(NAME, format!("{device} is usable")),
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => Check::warn(
+                NAME,
+                format!("{device} can't be opened by this user"),
+                "Fine for Docker, rootless engines need the user to have read and write access",
+            ),
+            // Opening the TUN device fails with EBADFD once a process holds it, it exists though
+            Err(_) if device == TUN => Check::pass(NAME, format!("{device} is usable")),
+            Err(err) => Check::warn(
+                NAME,
+                format!("Failed to open {device}: {err}"),
+                format!("Check `ls -l {device}` and the kernel log"),
+            ),
+        }
+    }
+
+    /// The image needs it to make a TAP device out of `/dev/net/tun`.
+    fn net_admin(&self) -> Option<Check> {
+        const NAME: &str = "Capabilities";
+
+        let tun = self
+            .service
+            .devices
+            .iter()
+            .any(|d| d.path_on_host.as_deref() == Some(TUN));
+        if !tun {
+            return None;
+        }
+
+        let net_admin = self.service.cap_add.iter().any(|cap| {
+            cap.trim_start_matches("CAP_")
+                .eq_ignore_ascii_case("NET_ADMIN")
+        });
+
+        Some(match net_admin {
+            true => Check::pass(NAME, "NET_ADMIN is requested"),
+            false => Check::warn(
+                NAME,
+                "NET_ADMIN isn't requested, networking falls back to slower user mode",
+                "Add NET_ADMIN to the capabilities of the service",
+            ),
+        })
+    }
+
+    fn volume(&self, host: &str) -> Check {
+        const NAME: &str = "Volume";
+
+        let path = self.host(host);
+        let existing = existing_ancestor(&path);
+
+        let created = match existing == path {
+            true => "",
+            false => ", Docker will create it",
+        };
+
+        match writable(existing) {
+            Ok(()) => Check::pass(NAME, format!("{host} is writable{created}")),
+            Err(err) if err.kind() == ErrorKind::ReadOnlyFilesystem => Check::fail(
+                NAME,
+                format!("{host} is on a read-only filesystem"),
+                "Bind a folder on a writable filesystem",
+            ),
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => Check::warn(
+                NAME,
+                format!("{host} isn't writable by this user{created}"),
+                "Fine for Docker, but rootless engines and shared files need it to be yours, \
+                 `sudo chown -R $USER` it",
+            ),
+            Err(err) => Check::warn(
+                NAME,
+                format!("Failed to write to {host}: {err}"),
+                format!("Check the permissions of {host}"),
+            ),
+        }
+    }
+
+    /// The image refuses to create a disk image larger than the free space.
+    fn disk_space(&self, host: &str) -> Check {
+        const NAME: &str = "Disk space";
+
+        let disk_size = match self.service.environment.disk_size() {
+            Some(Ok(size)) => size,
+            Some(Err(err)) => {
+                return Check::fail(
+                    NAME,
+                    format!("DISK_SIZE is invalid: {err}"),
+                    "Fix it in the settings of the service",
+                );
+            }
+            None => DockurrVar::DiskSize
+                .image_default()
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(Size::from_gib(64)),
+        };
+
+        let path = self.host(host);
+        let free = match statvfs(existing_ancestor(&path)) {
+            Ok(stat) => Size::from_bytes(stat.blocks_available() * stat.fragment_size()),
+            Err(err) => {
+                return Check::warn(
+                    NAME,
+                    format!("Failed to tell the free space of {host}: {err}"),
+                    "Make sure it has room for DISK_SIZE",
+                );
+            }
+        };
+
+        // Raw images are sparse, only what they already take counts
+        let allocated = DISK_IMAGES
+            .iter()
+            .filter_map(|image| fs::metadata(path.join(image)).ok())
+            .map(|metadata| metadata.blocks() * 512)
+            .max();
+
+        match allocated {
+            None if free < disk_size => Check::fail(
+                NAME,
+                format!(
+                    "{} GiB free in {host}, DISK_SIZE is {disk_size}",
+                    free.gib()
+                ),
+                "Free up space, lower DISK_SIZE or bind /storage to a larger disk",
+            ),
+            Some(allocated) if free.bytes() < disk_size.bytes().saturating_sub(allocated) => {
+                Check::warn(
+                    NAME,
+                    format!(
+                        "{} GiB free in {host}, not enough for the disk to grow to {disk_size}",
+                        free.gib()
+                    ),
+                    "Free up space before Windows fills its disk",
+                )
+            }
+            _ => Check::pass(
+                NAME,
+                format!("{} GiB free for a {disk_size} disk", free.gib()),
+            ),
+        }
+    }
+
+    /// Copy-on-write fragments disk images that are written in place, and slows Windows down.
+    fn copy_on_write(&self, host: &str) -> Option<Check> {
+        const NAME: &str = "Filesystem";
+
+        let path = self.host(host);
+        let existing = existing_ancestor(&path);
+
+        if statfs(existing).ok()?.filesystem_type() != BTRFS_SUPER_MAGIC {
+            return None;
+        }
+
+        // New files inherit the attribute from their folder
+        let no_cow = Command::new("lsattr")
+            .arg("-d")
+            .arg(existing)
+            .output()
+            .ok()
+            .and_then(|output| {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                stdout
+                    .split_whitespace()
+                    .next()
+                    .map(|flags| flags.contains('C'))
+            })
+            .unwrap_or(false);
+
+        let image = DISK_IMAGES.iter().any(|image| path.join(image).exists());
+
+        Some(match (no_cow, image) {
+            (true, _) => Check::pass(NAME, "btrfs, with copy-on-write disabled"),
+            (false, false) => Check::warn(
+                NAME,
+                format!("{host} is on btrfs with copy-on-write"),
+                format!("Disable it before Windows is installed, `chattr +C {host}`"),
+            ),
+            (false, true) => Check::warn(
+                NAME,
+                format!("{host} is on btrfs, the disk image is copy-on-write"),
+                format!(
+                    "`chattr +C {host}` only applies to new files, copy the image out and back in"
+                ),
+            ),
+        })
+    }
+}
+
+/// The path itself if it exists, its closest existing parent otherwise.
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("/"))
+}
+
+/// Writes and removes a file in the folder, or opens the file for writing.
+fn writable(path: &Path) -> std::io::Result<()> {
+    if path.is_file() {
+        return OpenOptions::new().append(true).open(path).map(|_| ());
+    }
+
+    let probe = path.join(".winjet-preflight");
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+
+    fs::remove_file(probe)
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/')
+        .map(|(parent, _)| parent)
+        .filter(|parent| !parent.is_empty())
+        .unwrap_or("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use bollard::secret::DeviceMapping;
+
+    use super::*;
+    use crate::{controller::state::Environment, util::ScratchDir};
+
+    /// A scratch filesystem root.
+    struct Root(ScratchDir);
+
+    impl Root {
+        fn new(name: &str) -> Self {
+            Self(ScratchDir::new(&format!("preflight-{name}")))
+        }
+
+        fn dir(&self, path: &str) -> PathBuf {
+            let path = self.0.join(path.trim_start_matches('/'));
+            fs::create_dir_all(&path).unwrap();
+            path
+        }
+
+        fn run(&self, devices: &[&str], volumes: &[&str], env: &[(&str, &str)]) -> Vec<Check> {
+            let service = DockerServiceState {
+                devices: devices
+                    .iter()
+                    .map(|device| DeviceMapping {
+                        path_on_host: Some(device.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+                volumes: volumes.iter().map(|v| v.to_string()).collect(),
+                environment: Environment::from_iter(env.iter().copied()),
+                ..Default::default()
+            };
+
+            Preflight {
+                root: self.0.to_path_buf(),
+                service,
+            }
+            .run()
+        }
+    }
+
+    fn check<'a>(checks: &'a [Check], name: &str, detail: &str) -> &'a Check {
+        checks
+            .iter()
+            .find(|c| c.name == name && c.detail.contains(detail))
+            .unwrap_or_else(|| panic!("No {name} check about '{detail}' in {checks:#?}"))
+    }
+
+    #[test]
+    fn fails_without_the_devices() {
+        let root = Root::new("devices");
+        root.dir("/dev/net");
+
+        let checks = root.run(&["/dev/kvm", "/dev/net/tun"], &[], &[]);
+
+        let tun = check(&checks, "Device", "/dev/net/tun doesn't exist");
+        assert_eq!(tun.level, CheckLevel::Fail);
+        assert!(tun.remedy.as_deref().unwrap().contains("modprobe tun"));
+
+        let kvm = check(&checks, "Device", "/dev/kvm doesn't exist");
+        assert_eq!(kvm.level, CheckLevel::Fail);
+        assert_eq!(kvm.remedy.as_deref(), Some("See the KVM checks"));
+    }
+
+    #[test]
+    fn accepts_character_devices_only() {
+        let root = Root::new("chardev");
+        std::os::unix::fs::symlink("/dev/null", root.dir("/dev/net").join("tun")).unwrap();
+        fs::write(root.dir("/dev").join("kvm"), "").unwrap();
+
+        let checks = root.run(&["/dev/kvm", "/dev/net/tun"], &[], &[]);
+
+        assert_eq!(
+            check(&checks, "Device", "/dev/net/tun is usable").level,
+            CheckLevel::Pass
+        );
+        assert_eq!(
+            check(&checks, "Device", "/dev/kvm isn't a character device").level,
+            CheckLevel::Fail
+        );
+    }
+
+    #[test]
+    fn wants_net_admin_for_tun() {
+        let root = Root::new("netadmin");
+
+        let checks = root.run(&["/dev/net/tun"], &[], &[]);
+        assert_eq!(
+            check(&checks, "Capabilities", "NET_ADMIN is requested").level,
+            CheckLevel::Pass
+        );
+
+        let service = DockerServiceState {
+            cap_add: vec![],
+            ..Default::default()
+        };
+        let checks = Preflight {
+            root: root.0.to_path_buf(),
+            service,
+        }
+        .run();
+        assert_eq!(
+            check(&checks, "Capabilities", "NET_ADMIN isn't requested").level,
+            CheckLevel::Warn
+        );
+    }
+
+    #[test]
+    fn checks_that_volumes_are_writable() {
+        let root = Root::new("volumes");
+        root.dir("/srv/shared");
+
+        let checks = root.run(
+            &[],
+            &[
+                "/srv/shared:/shared",
+                "/srv/new/windows:/data",
+                "iso:/boot.iso",
+            ],
+            &[],
+        );
+
+        assert_eq!(checks.len(), 2, "{checks:#?}");
+        assert_eq!(
+            check(&checks, "Volume", "/srv/shared is writable").level,
+            CheckLevel::Pass
+        );
+        assert_eq!(
+            check(
+                &checks,
+                "Volume",
+                "/srv/new/windows is writable, Docker will create it"
+            )
+            .level,
+            CheckLevel::Pass
+        );
+    }
+
+    #[test]
+    #[ignore = "root writes through permissions, run with --ignored as another user"]
+    fn warns_about_unwritable_volumes() {
+        let root = Root::new("locked");
+        let locked = root.dir("/srv/locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let checks = root.run(&[], &["/srv/locked:/oem"], &[]);
+
+        let locked = check(&checks, "Volume", "/srv/locked isn't writable by this user");
+        assert_eq!(locked.level, CheckLevel::Warn);
+        assert!(locked.remedy.as_deref().unwrap().contains("chown"));
+    }
+
+    #[test]
+    fn wants_room_for_the_disk() {
+        let root = Root::new("disk");
+        let storage = root.dir("/srv/windows");
+        let volumes = ["/srv/windows:/storage/"];
+
+        let checks = root.run(&[], &volumes, &[("DISK_SIZE", "1024T")]);
+        let space = check(&checks, "Disk space", "DISK_SIZE is 1024T");
+        assert_eq!(space.level, CheckLevel::Fail);
+        assert!(space.remedy.as_deref().unwrap().contains("lower DISK_SIZE"));
+
+        let checks = root.run(&[], &volumes, &[("DISK_SIZE", "1G")]);
+        assert_eq!(
+            check(&checks, "Disk space", "free for a 1G disk").level,
+            CheckLevel::Pass
+        );
+
+        let checks = root.run(&[], &volumes, &[("DISK_SIZE", "64 potatoes")]);
+        assert_eq!(
+            check(&checks, "Disk space", "DISK_SIZE is invalid").level,
+            CheckLevel::Fail
+        );
+
+        // A sparse image only takes what Windows wrote so far
+        fs::write(storage.join("data.img"), vec![1; 4096]).unwrap();
+        let checks = root.run(&[], &volumes, &[("DISK_SIZE", "1024T")]);
+        assert_eq!(
+            check(
+                &checks,
+                "Disk space",
+                "not enough for the disk to grow to 1024T"
+            )
+            .level,
+            CheckLevel::Warn
+        );
+    }
+}
@@ -0,0 +1,487 @@
+use std::{fmt::Display, time::Duration};
+
+use bollard::{Docker, secret::Port};
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use crate::controller::docker::{exec, published_tcp};
+
+/// QEMU's human monitor, `dockurr/windows` always starts it on this port inside the container.
+pub const MONITOR_PORT: u16 = 7100;
+/// Where QMP is expected when a service enables it, with
+/// `ARGUMENTS=-qmp tcp:0.0.0.0:7101,server,wait=off` and the port published.
+pub const QMP_PORT: u16 = 7101;
+
+const QMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmCommand {
+    /// Freezes the CPUs, unlike pausing the container QEMU keeps answering its monitor and VNC.
+    Stop,
+    Cont,
+    /// Presses the ACPI power button.
+    SystemPowerdown,
+    SystemReset,
+}
+
+impl VmCommand {
+    pub const ALL: [Self; 4] = [
+        Self::Stop,
+        Self::Cont,
+        Self::SystemPowerdown,
+        Self::SystemReset,
+    ];
+
+    /// What QMP and the human monitor both call it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::Cont => "cont",
+            Self::SystemPowerdown => "system_powerdown",
+            Self::SystemReset => "system_reset",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Stop => "Suspend",
+            Self::Cont => "Resume",
+            Self::SystemPowerdown => "Power Button",
+            Self::SystemReset => "Reset",
+        }
+    }
+
+    /// Whether the command makes sense for a VM in the given state.
+    pub fn available(&self, status: &VmStatus) -> bool {
+        match self {
+            Self::Stop | Self::SystemPowerdown => *status == VmStatus::Running,
+            Self::Cont => *status == VmStatus::Paused,
+            Self::SystemReset => matches!(status, VmStatus::Running | VmStatus::Paused),
+        }
+    }
+}
+
+impl Display for VmCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Stop => "suspend",
+            Self::Cont => "resume",
+            Self::SystemPowerdown => "press the power button of",
+            Self::SystemReset => "reset",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmStatus {
+    Running,
+    Paused,
+    /// Windows powered off, QEMU is about to exit.
+    Shutdown,
+    /// One of QEMU's other run states, `inmigrate`, `guest-panicked`...
+    Other(String),
+}
+
+impl VmStatus {
+    /// A QMP run state or what the human monitor prints after `VM status:`, `paused (shutdown)`.
+    pub fn parse(status: &str) -> Self {
+        let status = status.trim();
+
+        match status {
+            "running" => Self::Running,
+            "shutdown" | "paused (shutdown)" => Self::Shutdown,
+            x if x == "paused" || x.starts_with("paused (") => Self::Paused,
+            x => Self::Other(x.into()),
+        }
+    }
+}
+
+impl Display for VmStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Running => f.write_str("running"),
+            Self::Paused => f.write_str("suspended"),
+            Self::Shutdown => f.write_str("shut down"),
+            Self::Other(status) => f.write_str(status),
+        }
+    }
+}
+
+/// How the QEMU of a container is reached.
+#[derive(Debug, Clone)]
+pub enum Monitor {
+    /// QMP on the published [`QMP_PORT`].
+    Qmp(String),
+    /// The human monitor through `docker exec`, works with every `dockurr/windows` container.
+    Exec {
+        client: Docker,
+        container_name: String,
+    },
+}
+
+impl Monitor {
+    /// QMP if the container publishes it, the human monitor otherwise.
+    pub fn new(
+        client: Docker,
+        container_name: String,
+        ports: &[Port],
+        remote_host: Option<&str>,
+    ) -> Self {
+        match published_tcp(ports, QMP_PORT, remote_host) {
+            Some((host, port)) => Self::Qmp(format!("{host}:{port}")),
+            None => Self::Exec {
+                client,
+                container_name,
+            },
+        }
+    }
+
+    pub async fn execute(&self, command: VmCommand) -> Result<()> {
+        match self {
+            Self::Qmp(address) => {
+                QmpClient::connect(address)
+                    .await?
+                    .execute(command.name(), None)
+                    .await?;
+            }
+            Self::Exec { .. } => {
+                self.human(command.name()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn status(&self) -> Result<VmStatus> {
+        match self {
+            Self::Qmp(address) => QmpClient::connect(address).await?.query_status().await,
+            Self::Exec { .. } => {
+                let output = self.human("info status").await?;
+
+                output
+                    .lines()
+                    .find_map(|line| line.split_once("VM status:"))
+                    .map(|(_, status)| VmStatus::parse(status))
+                    .ok_or_else(|| eyre!("QEMU didn't tell its status: {}", output.trim()))
+            }
+        }
+    }
+
+    /// Sends one line to the human monitor, its answer comes back between the banner and prompts.
+    async fn human(&self, command: &str) -> Result<String> {
+        let Self::Exec {
+            client,
+            container_name,
+        } = self
+        else {
+            bail!("QEMU is reached through QMP");
+        };
+
+        // The monitor never hangs up, whatever it said within a second is the answer
+        let script = format!(
+            r#"if command -v bash >/dev/null 2>&1; then
+    exec bash -c 'exec 3<>/dev/tcp/localhost/{MONITOR_PORT} || exit 1
+        echo "$1" >&3
+        timeout 1 cat <&3
+        exit 0' bash "$1"
+elif command -v nc >/dev/null 2>&1; then
+    echo "$1" | nc -q 1 -w 1 localhost {MONITOR_PORT}
+else
+    echo "The container has neither bash nor nc to reach QEMU's monitor with" >&2
+    exit 127
+fi"#
+        );
+        let output = exec(
+            client,
+            container_name,
+            &["sh", "-c", &script, "sh", command],
+        )
+        .await?;
+
+        if let Some(err) = output
+            .lines()
+            .map(|line| line.trim_start_matches("(qemu)").trim())
+            .find(|line| line.starts_with("Error") || line.starts_with("unknown command"))
+        {
+            bail!("QEMU refused {command}: {err}");
+        }
+
+        Ok(output)
+    }
+}
+
+/// A QEMU Machine Protocol connection.
+pub struct QmpClient<S = TcpStream> {
+    stream: BufReader<S>,
+}
+
+impl QmpClient {
+    pub async fn connect(address: &str) -> Result<Self> {
+        let stream = tokio::time::timeout(QMP_TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| eyre!("QMP at {address} didn't answer"))??;
+
+        Self::handshake(stream).await
+    }
+}
+
+impl<S> QmpClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Reads the greeting and leaves capabilities negotiation mode.
+    pub async fn handshake(stream: S) -> Result<Self> {
+        let mut client = Self {
+            stream: BufReader::new(stream),
+        };
+
+        let greeting = client.read().await?;
+        if greeting.get("QMP").is_none() {
+            bail!("The server doesn't speak QMP");
+        }
+
+        client.execute("qmp_capabilities", None).await?;
+
+        Ok(client)
+    }
+
+    pub async fn query_status(&mut self) -> Result<VmStatus> {
+        let res = self.execute("query-status", None).await?;
+
+        res.get("status")
+            .and_then(Value::as_str)
+            .map(VmStatus::parse)
+            .ok_or_else(|| eyre!("QMP answered query-status with {res}"))
+    }
+
+    /// The `return` value of the command, events that arrive in between are skipped.
+    pub async fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value> {
+        let mut request = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            request["arguments"] = arguments;
+        }
+
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+
+        let exchange = async {
+            self.stream.get_mut().write_all(&line).await?;
+
+            loop {
+                let mut msg = self.read().await?;
+
+                if let Some(res) = msg.get_mut("return") {
+                    return Result::Ok(res.take());
+                }
+                if let Some(err) = msg.get("error") {
+                    bail!(
+                        "QEMU refused {command}: {}",
+                        err.get("desc")
+                            .and_then(Value::as_str)
+                            .unwrap_or("no reason")
+                    );
+                }
+            }
+        };
+
+        tokio::time::timeout(QMP_TIMEOUT, exchange)
+            .await
+            .map_err(|_| eyre!("QMP didn't answer {command} in time"))?
+    }
+
+    async fn read(&mut self) -> Result<Value> {
+        let mut line = String::new();
+
+        if self.stream.read_line(&mut line).await? == 0 {
+            bail!("QMP closed the connection");
+        }
+
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::DuplexStream, task::JoinHandle};
+
+    use super::*;
+
+    const GREETING: &str = r#"{"QMP": {"version": {"qemu": {"micro": 0, "minor": 2, "major": 9}, "package": "Debian 1:9.2.0"}, "capabilities": ["oob"]}}"#;
+    const EVENT: &str = r#"{"timestamp": {"seconds": 1760700000, "microseconds": 1}, "event": "RTC_CHANGE", "data": {"offset": 0}}"#;
+
+    /// QEMU's end of a connection, answering the n-th request with the n-th batch of lines.
+    /// Resolves to the requests it got.
+    fn fake_qemu(
+        greeting: &'static str,
+        answers: Vec<Vec<&'static str>>,
+    ) -> (DuplexStream, JoinHandle<Vec<Value>>) {
+        let (client, server) = tokio::io::duplex(4096);
+
+        let served = tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let mut requests = vec![];
+
+            let _ = server
+                .get_mut()
+                .write_all(format!("{greeting}\r\n").as_bytes())
+                .await;
+
+            for answer in answers {
+                let mut line = String::new();
+                if server.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    break;
+                }
+                requests.push(serde_json::from_str(&line).unwrap());
+
+                for line in answer {
+                    let _ = server
+                        .get_mut()
+                        .write_all(format!("{line}\r\n").as_bytes())
+                        .await;
+                }
+            }
+
+            requests
+        });
+
+        (client, served)
+    }
+
+    #[test]
+    fn parses_run_states() {
+        for (status, parsed) in [
+            ("running", VmStatus::Running),
+            (" running\r\n", VmStatus::Running),
+            ("paused", VmStatus::Paused),
+            ("paused (debug)", VmStatus::Paused),
+            ("paused (io-error)", VmStatus::Paused),
+            ("shutdown", VmStatus::Shutdown),
+            ("paused (shutdown)", VmStatus::Shutdown),
+            ("inmigrate", VmStatus::Other("inmigrate".into())),
+            ("guest-panicked", VmStatus::Other("guest-panicked".into())),
+        ] {
+            assert_eq!(VmStatus::parse(status), parsed, "{status:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_events_between_requests_and_answers() {
+        let (stream, served) = fake_qemu(
+            GREETING,
+            vec![
+                vec![EVENT, r#"{"return": {}}"#],
+                vec![
+                    EVENT,
+                    EVENT,
+                    r#"{"return": {"status": "paused", "singlestep": false, "running": false}}"#,
+                ],
+                vec![
+                    r#"{"timestamp": {"seconds": 1760700001, "microseconds": 2}, "event": "STOP"}"#,
+                    r#"{"return": {}}"#,
+                ],
+            ],
+        );
+
+        let mut client = QmpClient::handshake(stream).await.unwrap();
+        assert_eq!(client.query_status().await.unwrap(), VmStatus::Paused);
+        assert_eq!(client.execute("stop", None).await.unwrap(), json!({}));
+
+        drop(client);
+        let requests = served.await.unwrap();
+        assert_eq!(
+            requests,
+            [
+                json!({ "execute": "qmp_capabilities" }),
+                json!({ "execute": "query-status" }),
+                json!({ "execute": "stop" }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_arguments() {
+        let (stream, served) = fake_qemu(
+            GREETING,
+            vec![vec![r#"{"return": {}}"#], vec![r#"{"return": {}}"#]],
+        );
+
+        let mut client = QmpClient::handshake(stream).await.unwrap();
+        client
+            .execute(
+                "send-key",
+                Some(json!({ "keys": [{ "type": "qcode", "data": "ret" }] })),
+            )
+            .await
+            .unwrap();
+
+        drop(client);
+        assert_eq!(
+            served.await.unwrap()[1],
+            json!({
+                "execute": "send-key",
+                "arguments": { "keys": [{ "type": "qcode", "data": "ret" }] },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_error_replies() {
+        let (stream, _) = fake_qemu(
+            GREETING,
+            vec![
+                vec![r#"{"return": {}}"#],
+                vec![
+                    EVENT,
+                    r#"{"error": {"class": "CommandNotFound", "desc": "The command system_wakeup has not been found"}}"#,
+                ],
+                vec![r#"{"error": {"class": "GenericError"}}"#],
+                vec![r#"{"return": {"status": "running", "singlestep": false, "running": true}}"#],
+            ],
+        );
+
+        let mut client = QmpClient::handshake(stream).await.unwrap();
+
+        let err = client.execute("system_wakeup", None).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "QEMU refused system_wakeup: The command system_wakeup has not been found"
+        );
+        let err = client.execute("cont", None).await.unwrap_err();
+        assert_eq!(err.to_string(), "QEMU refused cont: no reason");
+
+        // The connection is still usable after an error
+        assert_eq!(client.query_status().await.unwrap(), VmStatus::Running);
+    }
+
+    #[tokio::test]
+    async fn refuses_servers_that_arent_qemu() {
+        let (stream, _) = fake_qemu(r#"{"hello": "world"}"#, vec![]);
+        let err = QmpClient::handshake(stream).await.err().unwrap();
+        assert!(err.to_string().contains("doesn't speak QMP"), "{err}");
+
+        let (stream, _) = fake_qemu(
+            GREETING,
+            vec![vec![
+                r#"{"error": {"class": "CommandNotFound", "desc": "Capabilities negotiation is already complete"}}"#,
+            ]],
+        );
+        let err = QmpClient::handshake(stream).await.err().unwrap();
+        assert!(err.to_string().contains("qmp_capabilities"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn notices_qemu_going_away() {
+        let (stream, _) = fake_qemu(GREETING, vec![vec![r#"{"return": {}}"#], vec![EVENT]]);
+
+        let mut client = QmpClient::handshake(stream).await.unwrap();
+        let err = client.query_status().await.unwrap_err();
+        assert!(err.to_string().contains("closed the connection"), "{err}");
+    }
+}
@@ -0,0 +1,575 @@
+mod paths;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use iced::futures::SinkExt;
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::oneshot,
+};
+
+use crate::{
+    app::{AppMsg, AppTask},
+    controller::{
+        Controller, ControllerModule,
+        docker::{ContainerData, DockerContainerExt, published_tcp},
+        state::{DockerServiceState, DockurrVar, WindowsApp},
+    },
+    util::find_executable,
+};
+
+pub use paths::PathMap;
+
+pub type RdpController = Controller<RdpModule>;
+
+/// Port RDP listens on inside the `dockurr/windows` container.
+pub const RDP_PORT: u16 = 3389;
+
+/// FreeRDP reports a regular disconnect or logoff with these.
+const CLEAN_EXIT_CODES: [i32; 5] = [0, 1, 2, 11, 12];
+
+#[derive(Debug)]
+pub struct RdpModule {
+    /// FreeRDP clients found on `PATH`, best fit for the session first.
+    pub clients: Vec<RdpClient>,
+    pub sessions: HashMap<SessionKey, RdpSession>,
+}
+
+impl ControllerModule for RdpModule {
+    const NAME: &str = "FreeRDP";
+
+    type Init = ();
+
+    async fn init_impl(_: Self::Init) -> Result<Self> {
+        let clients = RdpClient::detect();
+
+        if clients.is_empty() {
+            bail!("No FreeRDP client (xfreerdp, wlfreerdp, sdl-freerdp) found");
+        }
+
+        Ok(Self {
+            clients,
+            sessions: HashMap::new(),
+        })
+    }
+}
+
+impl RdpModule {
+    /// The client the options ask for, or the best one available.
+    pub fn client(&self, options: &RdpOptions) -> Option<&RdpClient> {
+        options
+            .client
+            .and_then(|kind| self.clients.iter().find(|c| c.kind == kind))
+            .or_else(|| self.clients.first())
+    }
+
+    pub fn session(&self, key: &SessionKey) -> Option<&RdpSession> {
+        self.sessions.get(key)
+    }
+
+    /// Opens a full desktop session to the service's container.
+    pub fn connect(
+        &mut self,
+        service: &DockerServiceState,
+        container: Option<&ContainerData>,
+    ) -> AppTask {
+        let launch = self.desktop_command(service, container);
+        self.start(SessionKey::Desktop(service.container_name.clone()), launch)
+    }
+
+    /// Runs a single program of the guest as a RemoteApp window, opening `file` with it.
+    pub fn launch_app(
+        &mut self,
+        service: &DockerServiceState,
+        app: &WindowsApp,
+        container: Option<&ContainerData>,
+        file: Option<&Path>,
+    ) -> AppTask {
+        let launch = self.app_command(service, app, container, file);
+        self.start(SessionKey::App(app.id.to_string()), launch)
+    }
+
+    /// The client and its arguments for a desktop session.
+    pub fn desktop_command(
+        &self,
+        service: &DockerServiceState,
+        container: Option<&ContainerData>,
+    ) -> Result<RdpCommand> {
+        self.command(service, container, |options, target, _| {
+            Ok(options.desktop_args(target))
+        })
+    }
+
+    /// The client and its arguments for a RemoteApp session.
+    pub fn app_command(
+        &self,
+        service: &DockerServiceState,
+        app: &WindowsApp,
+        container: Option<&ContainerData>,
+        file: Option<&Path>,
+    ) -> Result<RdpCommand> {
+        self.command(service, container, |options, target, client| {
+            let remote = container.and_then(|c| c.remote_host()).is_some();
+            let file = file
+                .map(|file| PathMap::new(service, remote).translate(file))
+                .transpose()?;
+
+            Ok(options.app_args(target, app, client, file.as_deref()))
+        })
+    }
+
+    fn command(
+        &self,
+        service: &DockerServiceState,
+        container: Option<&ContainerData>,
+        args: impl FnOnce(&RdpOptions, &RdpTarget, RdpClientKind) -> Result<Vec<String>>,
+    ) -> Result<RdpCommand> {
+        let target = RdpTarget::resolve(service, container)?;
+        let client = self
+            .client(&service.rdp)
+            .ok_or_else(|| eyre!("No FreeRDP client available"))?;
+
+        Ok(RdpCommand {
+            client: client.clone(),
+            args: args(&service.rdp, &target, client.kind)?,
+            password: target.password,
+        })
+    }
+
+    fn start(&mut self, key: SessionKey, launch: Result<RdpCommand>) -> AppTask {
+        let command = match launch {
+            Ok(launch) => launch,
+            Err(err) => {
+                self.sessions.insert(
+                    key,
+                    RdpSession {
+                        client: None,
+                        status: SessionStatus::Disconnected {
+                            error: Some(err.to_string()),
+                        },
+                        kill: None,
+                    },
+                );
+                return AppTask::none();
+            }
+        };
+
+        let (kill, killed) = oneshot::channel();
+
+        self.sessions.insert(
+            key.clone(),
+            RdpSession {
+                client: Some(command.client.kind),
+                status: SessionStatus::Starting,
+                kill: Some(kill),
+            },
+        );
+
+        run_session(key, command, killed)
+    }
+
+    pub fn disconnect(&mut self, key: &SessionKey) {
+        if let Some(kill) = self.sessions.get_mut(key).and_then(|s| s.kill.take()) {
+            let _ = kill.send(());
+        }
+    }
+
+    pub fn apply_event(&mut self, key: SessionKey, event: SessionEvent) {
+        let Some(session) = self.sessions.get_mut(&key) else {
+            return;
+        };
+
+        match event {
+            SessionEvent::Started => session.status = SessionStatus::Connected,
+            SessionEvent::Exited { error } => {
+                if let Some(err) = &error {
+                    tracing::error!("RDP session {key:?} failed: {err}");
+                }
+
+                session.kill = None;
+                session.status = SessionStatus::Disconnected { error };
+            }
+        }
+    }
+}
+
+/// Every desktop and every running application gets its own FreeRDP process.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SessionKey {
+    /// By container name.
+    Desktop(String),
+    /// By [`WindowsApp`] id.
+    App(String),
+}
+
+#[derive(Debug)]
+pub struct RdpSession {
+    pub client: Option<RdpClientKind>,
+    pub status: SessionStatus,
+    kill: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionStatus {
+    Starting,
+    Connected,
+    Disconnected { error: Option<String> },
+}
+
+impl SessionStatus {
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Starting | Self::Connected)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    Started,
+    Exited { error: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RdpClientKind {
+    X,
+    Wayland,
+    Sdl,
+}
+
+impl RdpClientKind {
+    pub const ALL: [Self; 3] = [Self::X, Self::Wayland, Self::Sdl];
+
+    /// Executable names, FreeRDP 3 packages suffix them with the major version.
+    fn binaries(&self) -> &'static [&'static str] {
+        match self {
+            Self::X => &["xfreerdp3", "xfreerdp"],
+            Self::Wayland => &["wlfreerdp3", "wlfreerdp"],
+            Self::Sdl => &["sdl-freerdp3", "sdl-freerdp"],
+        }
+    }
+}
+
+impl Display for RdpClientKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::X => "xfreerdp",
+            Self::Wayland => "wlfreerdp",
+            Self::Sdl => "sdl-freerdp",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RdpClient {
+    pub kind: RdpClientKind,
+    pub path: PathBuf,
+}
+
+impl RdpClient {
+    /// Installed clients, preferring the native ones for the running session.
+    pub fn detect() -> Vec<Self> {
+        let order = match std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            true => [RdpClientKind::Sdl, RdpClientKind::Wayland, RdpClientKind::X],
+            false => [RdpClientKind::X, RdpClientKind::Sdl, RdpClientKind::Wayland],
+        };
+
+        order
+            .into_iter()
+            .filter_map(|kind| {
+                let path = kind.binaries().iter().find_map(|x| find_executable(x))?;
+                Some(Self { kind, path })
+            })
+            .collect()
+    }
+}
+
+/// How desktop sessions to a service are opened, stored with the service.
+#[derive(SmartDefault, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RdpOptions {
+    /// Preferred client, the best installed one otherwise.
+    pub client: Option<RdpClientKind>,
+    /// Percentage, FreeRDP only accepts the values in [`RdpOptions::SCALES`].
+    #[default(100)]
+    pub scale: u32,
+    pub multimon: bool,
+    #[default(true)]
+    pub dynamic_resolution: bool,
+    #[default(true)]
+    pub clipboard: bool,
+    /// Shares the host's home folder with the guest as a drive.
+    pub home_drive: bool,
+}
+
+impl RdpOptions {
+    pub const SCALES: [u32; 3] = [100, 140, 180];
+
+    fn common_args(&self, target: &RdpTarget) -> Vec<String> {
+        let toggle = |on: bool, name: &str| format!("{}{name}", if on { '+' } else { '-' });
+
+        let mut args = vec![
+            format!("/v:{}:{}", target.host, target.port),
+            format!("/u:{}", target.username),
+            // Without one, FreeRDP asks for the domain before the password on stdin
+            "/d:".into(),
+            // The password goes through stdin, arguments are visible to every user
+            "/from-stdin:force".into(),
+            // dockurr/windows uses a self-signed certificate
+            "/cert:ignore".into(),
+            toggle(self.clipboard, "clipboard"),
+            toggle(self.home_drive, "home-drive"),
+        ];
+
+        if Self::SCALES.contains(&self.scale) && self.scale != 100 {
+            args.push(format!("/scale:{}", self.scale));
+        }
+
+        args
+    }
+
+    pub fn desktop_args(&self, target: &RdpTarget) -> Vec<String> {
+        let mut args = self.common_args(target);
+
+        if self.multimon {
+            args.push("/multimon".into());
+        } else if self.dynamic_resolution {
+            // FreeRDP can't resize a session spanning several monitors
+            args.push("/dynamic-resolution".into());
+        }
+
+        args
+    }
+
+    /// The guest has to allow unlisted RemoteApp programs (`fAllowUnlistedRemotePrograms`).
+    ///
+    /// `file` is a guest path, see [`PathMap`].
+    pub fn app_args(
+        &self,
+        target: &RdpTarget,
+        app: &WindowsApp,
+        client: RdpClientKind,
+        file: Option<&str>,
+    ) -> Vec<String> {
+        let mut args = self.common_args(target);
+
+        let cmd = app
+            .args
+            .iter()
+            .filter(|x| !x.is_empty())
+            .cloned()
+            .chain(file.map(|file| format!("\"{file}\"")))
+            .collect::<Vec<_>>();
+
+        let mut app_arg = format!("/app:program:{},name:{}", app.program, app.name);
+        if !cmd.is_empty() {
+            app_arg.push_str(&format!(",cmd:{}", cmd.join(" ")));
+        }
+        args.push(app_arg);
+
+        // Lets the desktop group the windows and match them to the app's entry
+        if client == RdpClientKind::X {
+            args.push(format!("/wm-class:{}", app.name));
+        }
+
+        args
+    }
+}
+
+/// Where and as whom to connect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RdpTarget {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+}
+
+impl RdpTarget {
+    /// Uses the ports the container actually published, the stored ones if it doesn't exist.
+    pub fn resolve(
+        service: &DockerServiceState,
+        container: Option<&ContainerData>,
+    ) -> Result<Self> {
+        let ports = match container {
+            Some(container) => container.ports(),
+            None => service.ports.clone(),
+        };
+
+        let remote_host = container.and_then(|c| c.remote_host());
+        let (host, port) = published_tcp(&ports, RDP_PORT, remote_host)
+            .ok_or_else(|| eyre!("Port {RDP_PORT}/tcp isn't published on the host"))?;
+
+        let env = &service.environment;
+        let credential = |var: DockurrVar| {
+            env.get(var.key())
+                .or_else(|| var.image_default())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        Ok(Self {
+            host,
+            port,
+            username: credential(DockurrVar::Username),
+            password: credential(DockurrVar::Password),
+        })
+    }
+}
+
+/// A FreeRDP client with everything it needs to connect.
+#[derive(Debug, Clone)]
+pub struct RdpCommand {
+    pub client: RdpClient,
+    pub args: Vec<String>,
+    password: String,
+}
+
+impl RdpCommand {
+    /// Starts the client and writes the password to its stdin, its output is discarded.
+    pub async fn spawn(&self, stderr: Stdio) -> std::io::Result<Child> {
+        let mut child = Command::new(&self.client.path)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(stderr)
+            .spawn()?;
+
+        // Dropping stdin closes it, FreeRDP reads nothing else from it
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(self.stdin().as_bytes()).await?;
+        }
+
+        Ok(child)
+    }
+
+    /// What FreeRDP reads for `/from-stdin`, only the password as the rest is passed.
+    fn stdin(&self) -> String {
+        format!("{}\n", self.password)
+    }
+}
+
+/// Whether FreeRDP ended the way a user closing the session ends it.
+pub fn clean_exit(status: ExitStatus) -> bool {
+    status.code().is_some_and(|c| CLEAN_EXIT_CODES.contains(&c))
+}
+
+fn run_session(key: SessionKey, command: RdpCommand, killed: oneshot::Receiver<()>) -> AppTask {
+    AppTask::run(
+        iced::stream::channel(4, move |mut output| async move {
+            let msg = |event| AppMsg::RdpSessionEvent(key.clone(), event);
+            let kind = command.client.kind;
+
+            let mut child = match command.spawn(Stdio::piped()).await {
+                Ok(child) => child,
+                Err(err) => {
+                    let error = Some(format!("Failed to start {}: {err}", kind));
+                    let _ = output.send(msg(SessionEvent::Exited { error })).await;
+                    return;
+                }
+            };
+
+            let _ = output.send(msg(SessionEvent::Started)).await;
+
+            // Only the last error FreeRDP logged is worth showing
+            let stderr = child.stderr.take();
+            let last_error = async move {
+                let mut last_error = None;
+                let Some(stderr) = stderr else {
+                    return last_error;
+                };
+
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("{}: {line}", kind);
+
+                    if line.contains("ERROR") {
+                        last_error = Some(line);
+                    }
+                }
+
+                last_error
+            };
+
+            let exit = async {
+                tokio::select! {
+                    status = child.wait() => Some(status),
+                    _ = killed => {
+                        let _ = child.kill().await;
+                        None
+                    }
+                }
+            };
+
+            let (status, last_error) = tokio::join!(exit, last_error);
+
+            let error = match status {
+                None => None,
+                Some(Ok(status)) if clean_exit(status) => None,
+                Some(Ok(status)) => {
+                    Some(last_error.unwrap_or_else(|| format!("{} exited with {status}", kind)))
+                }
+                Some(Err(err)) => Some(format!("Failed to wait for {}: {err}", kind)),
+            };
+
+            let _ = output.send(msg(SessionEvent::Exited { error })).await;
+        }),
+        |msg| msg,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> RdpTarget {
+        RdpTarget {
+            host: "127.0.0.1".into(),
+            port: 3389,
+            username: "Docker".into(),
+            password: "se cret".into(),
+        }
+    }
+
+    #[test]
+    fn passes_only_the_password_through_stdin() {
+        let options = RdpOptions::default();
+        let command = RdpCommand {
+            client: RdpClient {
+                kind: RdpClientKind::Sdl,
+                path: "sdl-freerdp3".into(),
+            },
+            args: options.desktop_args(&target()),
+            password: target().password,
+        };
+
+        assert_eq!(
+            command.args,
+            [
+                "/v:127.0.0.1:3389",
+                "/u:Docker",
+                "/d:",
+                "/from-stdin:force",
+                "/cert:ignore",
+                "+clipboard",
+                "-home-drive",
+                "/dynamic-resolution",
+            ]
+        );
+        assert!(!command.args.iter().any(|arg| arg.contains("se cret")));
+        assert_eq!(command.stdin().as_bytes(), b"se cret\n");
+    }
+}
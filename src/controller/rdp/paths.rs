@@ -0,0 +1,261 @@
+use std::path::{Component, Path, PathBuf};
+
+use color_eyre::{Result, eyre::bail};
+
+use crate::controller::state::DockerServiceState;
+
+/// Folders `dockurr/windows` shares with the guest over Samba, as `\\host.lan\Data`.
+const SHARED_TARGETS: [&str; 2] = ["/shared", "/data"];
+const SHARED_UNC: &str = r"\\host.lan\Data";
+/// FreeRDP's `+home-drive` redirect.
+const HOME_DRIVE_UNC: &str = r"\\tsclient\home";
+
+/// Characters Windows doesn't allow in file names, and the comma FreeRDP splits `/app:` on.
+const FORBIDDEN: [char; 9] = ['<', '>', ':', '"', '\\', '|', '?', '*', ','];
+
+/// Where the guest sees the host's folders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMap {
+    container_name: String,
+    remote: bool,
+    /// Most specific host folder first.
+    mappings: Vec<(PathBuf, &'static str)>,
+}
+
+impl PathMap {
+    /// Built from the bind mounts of the shared folder and the RDP home drive redirect.
+    ///
+    /// The shared folders of a container on a `remote` engine are on the engine's machine, only
+    /// the home drive is redirected from this one.
+    pub fn new(service: &DockerServiceState, remote: bool) -> Self {
+        let mut mappings = service
+            .volumes
+            .iter()
+            .filter(|_| !remote)
+            .filter_map(|volume| {
+                let mut parts = volume.split(':');
+                let (host, target) = (parts.next()?, parts.next()?);
+
+                // Named volumes have no host path to map
+                (host.starts_with('/') && SHARED_TARGETS.contains(&target))
+                    .then(|| (canonical(Path::new(host)), SHARED_UNC))
+            })
+            .collect::<Vec<_>>();
+
+        if service.rdp.home_drive
+            && let Some(home) = std::env::home_dir()
+        {
+            mappings.push((canonical(&home), HOME_DRIVE_UNC));
+        }
+
+        mappings.sort_by_key(|(host, _)| std::cmp::Reverse(host.components().count()));
+
+        Self {
+            container_name: service.container_name.clone(),
+            remote,
+            mappings,
+        }
+    }
+
+    /// The host folders the guest sees as `\\host.lan\Data`.
+    pub fn shared_folders(&self) -> impl Iterator<Item = &Path> {
+        self.mappings
+            .iter()
+            .filter(|(_, unc)| *unc == SHARED_UNC)
+            .map(|(host, _)| host.as_path())
+    }
+
+    /// The guest path of a host file, an error if the guest can't reach it.
+    pub fn translate(&self, path: &Path) -> Result<String> {
+        // Symlinks could point out of the shared folders
+        let path = canonical(path);
+
+        let Some((relative, unc)) = self
+            .mappings
+            .iter()
+            .find_map(|(host, unc)| Some((path.strip_prefix(host).ok()?, unc)))
+        else {
+            let remedy = match self.remote {
+                true => "enable the home drive",
+                false => "bind its folder to /shared or enable the home drive",
+            };
+            bail!(
+                "{} isn't shared with {}, {remedy}",
+                path.display(),
+                self.container_name
+            );
+        };
+
+        let mut guest = unc.to_string();
+
+        for component in relative.components() {
+            let Component::Normal(name) = component else {
+                bail!("{} isn't a plain path", path.display());
+            };
+
+            let Some(name) = name.to_str() else {
+                bail!("{} isn't valid UTF-8", path.display());
+            };
+
+            if let Some(c) = name.chars().find(|c| FORBIDDEN.contains(c)) {
+                bail!(
+                    "Windows can't open {}, it has a '{c}' in it",
+                    path.display()
+                );
+            }
+
+            guest.push('\\');
+            guest.push_str(name);
+        }
+
+        Ok(guest)
+    }
+}
+
+/// Resolved like the files are, so a symlinked home still contains them.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::ScratchDir;
+
+    fn service(volumes: &[&str], home_drive: bool) -> DockerServiceState {
+        let mut service = DockerServiceState {
+            volumes: volumes.iter().map(|v| v.to_string()).collect(),
+            ..Default::default()
+        };
+        service.rdp.home_drive = home_drive;
+
+        service
+    }
+
+    fn map(volumes: &[&str], home_drive: bool) -> PathMap {
+        PathMap::new(&service(volumes, home_drive), false)
+    }
+
+    fn translate(map: &PathMap, path: impl AsRef<Path>) -> Result<String> {
+        map.translate(path.as_ref())
+    }
+
+    #[test]
+    fn maps_shared_folders() {
+        let map = map(
+            &[
+                "/srv/winjet/shared:/shared",
+                "/srv/winjet/data:/data:ro",
+                "/srv/winjet/storage:/storage",
+                "iso:/shared",
+            ],
+            false,
+        );
+
+        assert_eq!(
+            translate(&map, "/srv/winjet/shared/docs/report.docx").unwrap(),
+            r"\\host.lan\Data\docs\report.docx"
+        );
+        assert_eq!(
+            translate(&map, "/srv/winjet/data/notes.txt").unwrap(),
+            r"\\host.lan\Data\notes.txt"
+        );
+        assert_eq!(
+            translate(&map, "/srv/winjet/shared").unwrap(),
+            r"\\host.lan\Data"
+        );
+        // Only /shared and /data are shared with the guest
+        assert!(translate(&map, "/srv/winjet/storage/data.img").is_err());
+    }
+
+    #[test]
+    fn maps_the_home_drive() {
+        let home = std::env::home_dir().unwrap();
+        let file = home.join("winjet-missing/Documents/notes.txt");
+
+        assert_eq!(
+            translate(&map(&[], true), &file).unwrap(),
+            r"\\tsclient\home\winjet-missing\Documents\notes.txt"
+        );
+        assert!(translate(&map(&[], false), &file).is_err());
+    }
+
+    #[test]
+    fn prefers_the_most_specific_folder() {
+        let home = std::env::home_dir().unwrap();
+        let shared = home.join("winjet-missing/shared");
+        let map = map(&[&format!("{}:/shared", shared.display())], true);
+
+        assert_eq!(
+            translate(&map, shared.join("a.txt")).unwrap(),
+            r"\\host.lan\Data\a.txt"
+        );
+        assert_eq!(
+            translate(&map, home.join("winjet-missing/b.txt")).unwrap(),
+            r"\\tsclient\home\winjet-missing\b.txt"
+        );
+    }
+
+    #[test]
+    fn rejects_unmapped_paths() {
+        let map = map(&["/srv/winjet/shared:/shared"], false);
+
+        let err = translate(&map, "/srv/other/file.txt").unwrap_err();
+        assert!(err.to_string().contains("isn't shared with windows"));
+        // A sibling that only shares the prefix of the name
+        assert!(translate(&map, "/srv/winjet/shared2/file.txt").is_err());
+    }
+
+    #[test]
+    fn maps_only_the_home_drive_of_remote_containers() {
+        let home = std::env::home_dir().unwrap();
+        let shared = format!("{}:/shared", home.join("winjet-missing/shared").display());
+        let map = PathMap::new(
+            &service(&["/srv/winjet/shared:/shared", &shared], true),
+            true,
+        );
+
+        let err = translate(&map, "/srv/winjet/shared/report.docx").unwrap_err();
+        assert!(err.to_string().contains("isn't shared with windows"));
+        assert_eq!(map.shared_folders().count(), 0);
+        // The folder is on the engine's machine, this one's is redirected as part of the home
+        assert_eq!(
+            translate(&map, home.join("winjet-missing/shared/a.txt")).unwrap(),
+            r"\\tsclient\home\winjet-missing\shared\a.txt"
+        );
+    }
+
+    #[test]
+    fn keeps_spaces_and_rejects_what_windows_cant_open() {
+        let map = map(&["/srv/winjet/shared:/shared"], false);
+
+        assert_eq!(
+            translate(&map, "/srv/winjet/shared/My Documents/Q1 report.xlsx").unwrap(),
+            r"\\host.lan\Data\My Documents\Q1 report.xlsx"
+        );
+
+        for name in ["a,b.txt", "what?.txt", "a:b.txt", r"back\slash.txt"] {
+            assert!(
+                translate(&map, format!("/srv/winjet/shared/{name}")).is_err(),
+                "{name} translated"
+            );
+        }
+    }
+
+    #[test]
+    fn follows_symlinks_on_both_sides() {
+        let dir = ScratchDir::new("paths");
+        let real = dir.join("real");
+        std::fs::create_dir_all(real.join("inner")).unwrap();
+        std::fs::write(real.join("inner/doc.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&real, dir.join("link")).unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", real.join("escape")).unwrap();
+
+        let map = map(&[&format!("{}:/shared", dir.join("link").display())], false);
+        let inside = translate(&map, dir.join("link/inner/doc.txt"));
+        let escaped = translate(&map, dir.join("link/escape"));
+
+        assert_eq!(inside.unwrap(), r"\\host.lan\Data\inner\doc.txt");
+        assert!(escaped.is_err());
+    }
+}
@@ -1,33 +1,65 @@
-use std::sync::Arc;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
 use bollard::secret::{DeviceMapping, Port, PortTypeEnum, RestartPolicy, RestartPolicyNameEnum};
-use color_eyre::Result;
+use color_eyre::{Result, eyre::bail};
+use derive_more::Deref;
 use directories::ProjectDirs;
 use iced::futures::FutureExt;
+use rfd::AsyncFileDialog;
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
 use smart_default::SmartDefault;
 use surrealdb::{RecordId, Surreal, Uuid, engine::local::Db};
-use surrealdb_extras::{SurrealExt, SurrealQuery, SurrealTable};
+use surrealdb_extras::{SurrealExt, SurrealTable};
+use winjet_gt_proto::InstalledApp;
 
 use crate::{
     app::{AppMsg, AppTask},
-    controller::{Controller, ControllerModule},
-    util::Arced,
+    controller::{Controller, ControllerModule, docker::Connection, rdp::RdpOptions},
+    util::{Arced, parse_duration},
 };
 
+mod catalogue;
+mod compose;
+mod environment;
+mod history;
+mod profile;
+
+pub use catalogue::WindowsApp;
+pub use compose::{ComposeFile, ComposeService, Imported};
+pub use environment::{DockurrVar, EnvVar, Environment, Size};
+pub use history::{ShutdownOutcome, ShutdownRecord, ShutdownVia};
+pub use profile::EngineProfile;
+
 pub type DB = Surreal<Db>;
 
 pub type StateController = Controller<StateModule>;
 
+/// Where the [`StateSettings`] record lives.
+const SETTINGS: (&str, &str) = ("settings", "main");
+
 #[derive(Debug)]
 pub struct StateModule {
     db: DB,
 
-    pub service: Option<DockerServiceState>,
-    pub service_loading: bool,
-    pub service_updating: bool,
-    pub service_exists_db: bool,
+    pub services: Vec<DockerServiceState>,
+    pub selected: Option<RecordId>,
+    /// RemoteApp catalogue of all services.
+    pub apps: Vec<WindowsApp>,
+    /// How the services' containers were stopped, oldest first.
+    pub history: Vec<ShutdownRecord>,
+    /// Where to reach Docker or Podman, a well-known socket without one.
+    pub docker_endpoint: Option<String>,
+    pub profiles: Vec<EngineProfile>,
+    /// The remote engine in use, the local one without one.
+    pub profile: Option<RecordId>,
+    pub services_loading: bool,
+    pub services_updating: bool,
 }
 
 impl ControllerModule for StateModule {
@@ -36,72 +68,592 @@ impl ControllerModule for StateModule {
     type Init = ProjectDirs;
 
     async fn init_impl(dirs: ProjectDirs) -> Result<Self> {
-        let db_dir = dirs.data_local_dir().join("state");
+        Self::open(dirs.data_local_dir().join("state")).await
+    }
+}
 
+impl StateModule {
+    /// The database in `db_dir`, nothing is loaded from it yet.
+    async fn open(db_dir: PathBuf) -> Result<Self> {
         let db = DB::new(db_dir).await?;
         db.use_ns_db_checked("winjet", "state", vec![]).await?;
 
         Ok(Self {
             db,
 
-            service: None,
-            service_loading: false,
-            service_updating: false,
-            service_exists_db: false,
+            services: vec![],
+            selected: None,
+            apps: vec![],
+            history: vec![],
+            docker_endpoint: None,
+            profiles: vec![],
+            profile: None,
+            services_loading: false,
+            services_updating: false,
         })
     }
-}
 
-impl StateModule {
-    pub fn try_load_service(&mut self) -> AppTask {
-        self.service_loading = true;
+    /// The service the main screen is showing.
+    pub fn service(&self) -> Option<&DockerServiceState> {
+        self.selected.as_ref().and_then(|id| self.find_service(id))
+    }
+
+    pub fn find_service(&self, id: &RecordId) -> Option<&DockerServiceState> {
+        self.services.iter().find(|s| &s.id == id)
+    }
+
+    /// Whether some service already manages the named container.
+    pub fn manages(&self, container_name: &str) -> bool {
+        self.services
+            .iter()
+            .any(|s| s.container_name == container_name)
+    }
+
+    /// The catalogue of the given service.
+    pub fn apps_of<'a>(&'a self, service: &'a RecordId) -> impl Iterator<Item = &'a WindowsApp> {
+        self.apps.iter().filter(move |a| &a.service == service)
+    }
+
+    pub fn find_app(&self, id: &RecordId) -> Option<&WindowsApp> {
+        self.apps.iter().find(|a| &a.id == id)
+    }
+
+    pub fn last_shutdown(&self, service: &RecordId) -> Option<&ShutdownRecord> {
+        self.history.iter().rev().find(|r| &r.service == service)
+    }
+
+    pub fn find_profile(&self, id: &RecordId) -> Option<&EngineProfile> {
+        self.profiles.iter().find(|p| &p.id == id)
+    }
+
+    /// How to reach the engine, through the profile in use or the local endpoint.
+    pub fn connection(&self) -> Option<Connection> {
+        match self.profile.as_ref().and_then(|id| self.find_profile(id)) {
+            Some(profile) => Some(profile.connection()),
+            None => self.docker_endpoint.clone().map(Connection::new),
+        }
+    }
+
+    fn settings(&self) -> StateSettings {
+        StateSettings {
+            default_service: self.selected.clone(),
+            docker_endpoint: self.docker_endpoint.clone(),
+            engine_profile: self.profile.clone(),
+        }
+    }
+
+    pub fn load_services(&mut self) -> AppTask {
+        self.services_loading = true;
+
+        AppTask::perform(
+            fetch(self.db.clone()).map(Arced::arced),
+            AppMsg::LoadDockerServicesRes,
+        )
+    }
+
+    /// Loads the services right away, for callers outside the GUI.
+    pub async fn reload(&mut self) -> Result<()> {
+        let loaded = fetch(self.db.clone()).await?;
+        self.set_services(Ok(loaded).arced());
+
+        Ok(())
+    }
+
+    pub fn set_services(&mut self, res: Arc<Result<LoadedServices>>) {
+        self.services_loading = false;
+
+        match Arc::into_inner(res).expect("Logic error!") {
+            Ok(LoadedServices {
+                services,
+                apps,
+                mut history,
+                profiles,
+                settings,
+            }) => {
+                history.sort_by_key(|r| r.at);
+
+                self.services = services;
+                self.apps = apps;
+                self.history = history;
+                self.profiles = profiles;
+                self.docker_endpoint = settings.docker_endpoint;
+                self.profile = settings
+                    .engine_profile
+                    .filter(|id| self.find_profile(id).is_some());
+                self.selected = settings
+                    .default_service
+                    .filter(|id| self.find_service(id).is_some())
+                    .or_else(|| self.services.first().map(|s| s.id.clone()));
+            }
+            Err(err) => tracing::error!("Failed to load docker services: {err}"),
+        }
+    }
+
+    /// Adds the service or replaces the stored one with the same id, and selects it.
+    pub fn save_service(&mut self, service: DockerServiceState) -> AppTask {
+        self.services_updating = true;
+
         AppTask::perform(
-            GetDockerService.execute(self.db.clone()).map(Arced::arced),
-            AppMsg::LoadDockerServiceStateRes,
+            self.store_service(service).map(Arced::arced),
+            AppMsg::UpdateStateRes,
         )
     }
 
-    pub fn check_set_service(&mut self, service: Arc<Result<Option<DockerServiceState>>>) {
-        self.service_loading = false;
-        self.service_updating = false;
+    /// Like [`StateModule::save_service`], the returned future writes it to the database.
+    pub fn store_service(
+        &mut self,
+        mut service: DockerServiceState,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        if service.guest_token.is_empty() {
+            service.guest_token = guest_token();
+        }
+
+        match self.services.iter_mut().find(|s| s.id == service.id) {
+            Some(existing) => *existing = service.clone(),
+            None => self.services.push(service.clone()),
+        }
+        self.selected = Some(service.id.clone());
 
-        match Arc::into_inner(service).expect("Logic error!") {
-            Ok(val) => {
-                self.service = val;
-                self.service_exists_db = self.service.is_some();
+        let db = self.db.clone();
+        let settings = self.settings();
+
+        async move {
+            db.upsert::<Option<DockerServiceState>>(service.id.clone())
+                .content(service)
+                .await?;
+            db.upsert::<Option<StateSettings>>(SETTINGS)
+                .content(settings)
+                .await?;
+
+            Result::Ok(())
+        }
+    }
+
+    /// Adds the services of a compose file that no service manages yet.
+    pub fn save_imported(&mut self, services: Vec<DockerServiceState>) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.store_imported(services).map(Arced::arced),
+            AppMsg::ComposeImportedRes,
+        )
+    }
+
+    /// Like [`StateModule::save_imported`], the returned future writes them to the database.
+    pub fn store_imported(
+        &mut self,
+        services: Vec<DockerServiceState>,
+    ) -> impl Future<Output = Result<Vec<Imported>>> + Send + 'static {
+        let mut imported = vec![];
+        let mut stores = vec![];
+
+        for service in services {
+            let name = service.container_name.clone();
+            let skipped = self.manages(&name);
+
+            if !skipped {
+                stores.push(self.store_service(service));
             }
-            Err(err) => tracing::error!("Failed to load docker service: {err}"),
-        }
-    }
-
-    pub fn update_service_db(&mut self) -> AppTask {
-        match &self.service {
-            Some(service) => {
-                let exists = self.service_exists_db;
-                let db = self.db.clone();
-                let service = service.clone();
-
-                self.service_updating = true;
-
-                AppTask::perform(
-                    async move {
-                        match exists {
-                            true => {
-                                db.upsert::<Option<_>>(service.id.clone())
-                                    .content(service)
-                                    .await
-                            }
-                            false => db.create("container").content(service).await,
-                        }
-                        .map_err(color_eyre::Report::from)
-                        .arced()
-                    },
-                    AppMsg::LoadDockerServiceStateRes,
-                )
+            imported.push(Imported {
+                name,
+                imported: !skipped,
+            });
+        }
+
+        async move {
+            for store in stores {
+                store.await?;
             }
-            None => AppTask::none(),
+
+            Ok(imported)
         }
     }
+
+    /// Goes back to the local engine, `None` looks for a socket.
+    pub fn set_docker_endpoint(&mut self, endpoint: Option<String>) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.store_docker_endpoint(endpoint).map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    /// Like [`StateModule::set_docker_endpoint`], the returned future writes it to the database.
+    pub fn store_docker_endpoint(
+        &mut self,
+        endpoint: Option<String>,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        self.docker_endpoint = endpoint;
+        self.profile = None;
+
+        let db = self.db.clone();
+        let settings = self.settings();
+
+        async move {
+            db.upsert::<Option<StateSettings>>(SETTINGS)
+                .content(settings)
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Adds the profile or replaces the stored one with the same id, and switches to it.
+    pub fn save_profile(&mut self, profile: EngineProfile) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.store_profile(profile).map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    /// Like [`StateModule::save_profile`], the returned future writes it to the database.
+    pub fn store_profile(
+        &mut self,
+        profile: EngineProfile,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        match self.profiles.iter_mut().find(|p| p.id == profile.id) {
+            Some(existing) => *existing = profile.clone(),
+            None => self.profiles.push(profile.clone()),
+        }
+        self.profile = Some(profile.id.clone());
+
+        let db = self.db.clone();
+        let settings = self.settings();
+
+        async move {
+            db.upsert::<Option<EngineProfile>>(profile.id.clone())
+                .content(profile)
+                .await?;
+            db.upsert::<Option<StateSettings>>(SETTINGS)
+                .content(settings)
+                .await?;
+
+            Result::Ok(())
+        }
+    }
+
+    /// Switches to the remote engine of the profile.
+    pub fn select_profile(&mut self, id: RecordId) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.store_selected_profile(id).map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    /// Like [`StateModule::select_profile`], the returned future writes it to the database.
+    pub fn store_selected_profile(
+        &mut self,
+        id: RecordId,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        self.profile = Some(id);
+
+        let db = self.db.clone();
+        let settings = self.settings();
+
+        async move {
+            db.upsert::<Option<StateSettings>>(SETTINGS)
+                .content(settings)
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Forgets the profile, going back to the local engine if it was in use.
+    pub fn remove_profile(&mut self, id: RecordId) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.delete_profile(id).map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    /// Like [`StateModule::remove_profile`], the returned future deletes it from the database.
+    pub fn delete_profile(
+        &mut self,
+        id: RecordId,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        self.profiles.retain(|p| p.id != id);
+        if self.profile.as_ref() == Some(&id) {
+            self.profile = None;
+        }
+
+        let db = self.db.clone();
+        let settings = self.settings();
+
+        async move {
+            db.delete::<Option<EngineProfile>>(id).await?;
+            db.upsert::<Option<StateSettings>>(SETTINGS)
+                .content(settings)
+                .await?;
+
+            Result::Ok(())
+        }
+    }
+
+    /// Selects the service and remembers it as the default for the next start.
+    pub fn select_service(&mut self, id: RecordId) -> AppTask {
+        self.selected = Some(id);
+        self.update_settings_db()
+    }
+
+    /// Stops managing the service and forgets its apps and history, the container is left alone.
+    pub fn remove_service(&mut self, id: RecordId) -> AppTask {
+        self.services.retain(|s| s.id != id);
+
+        let apps: Vec<_> = self
+            .apps
+            .extract_if(.., |a| a.service == id)
+            .map(|a| a.id)
+            .collect();
+        let history: Vec<_> = self
+            .history
+            .extract_if(.., |r| r.service == id)
+            .map(|r| r.id)
+            .collect();
+
+        if self.selected.as_ref() == Some(&id) {
+            self.selected = self.services.first().map(|s| s.id.clone());
+        }
+
+        let db = self.db.clone();
+
+        AppTask::batch([
+            AppTask::perform(
+                async move {
+                    for app in apps {
+                        db.delete::<Option<WindowsApp>>(app).await?;
+                    }
+                    for record in history {
+                        db.delete::<Option<ShutdownRecord>>(record).await?;
+                    }
+                    db.delete::<Option<DockerServiceState>>(id).await?;
+                    Result::Ok(())
+                }
+                .map(Arced::arced),
+                AppMsg::UpdateStateRes,
+            ),
+            self.update_settings_db(),
+        ])
+    }
+
+    /// Adds the app to the catalogue or replaces the one with the same id.
+    pub fn save_app(&mut self, app: WindowsApp) -> AppTask {
+        match self.apps.iter_mut().find(|a| a.id == app.id) {
+            Some(existing) => *existing = app.clone(),
+            None => self.apps.push(app.clone()),
+        }
+        self.services_updating = true;
+
+        let db = self.db.clone();
+
+        AppTask::perform(
+            async move {
+                db.upsert::<Option<WindowsApp>>(app.id.clone())
+                    .content(app)
+                    .await?;
+                Result::Ok(())
+            }
+            .map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    /// Adds the apps the guest tools found that the service doesn't have yet.
+    pub fn save_discovered(&mut self, service: RecordId, found: Vec<InstalledApp>) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.store_discovered(service, found).map(Arced::arced),
+            AppMsg::DiscoverWindowsAppsRes,
+        )
+    }
+
+    /// Like [`StateModule::save_discovered`], the returned future writes them to the database
+    /// and resolves to the names of the added apps.
+    ///
+    /// Apps FreeRDP can't launch are skipped.
+    pub fn store_discovered(
+        &mut self,
+        service: RecordId,
+        found: Vec<InstalledApp>,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send + 'static {
+        let mut added = vec![];
+
+        for installed in found {
+            if self
+                .apps_of(&service)
+                .any(|app| app.same_launch(&installed.program, installed.args.as_deref()))
+            {
+                continue;
+            }
+
+            let name = installed.name.clone();
+            match WindowsApp::from_installed(service.clone(), installed) {
+                Ok(app) => {
+                    self.apps.push(app.clone());
+                    added.push(app);
+                }
+                Err(err) => tracing::warn!("Skipped the discovered application {name}: {err}"),
+            }
+        }
+
+        let db = self.db.clone();
+
+        async move {
+            let mut names = vec![];
+
+            for app in added {
+                names.push(app.name.clone());
+                db.upsert::<Option<WindowsApp>>(app.id.clone())
+                    .content(app)
+                    .await?;
+            }
+
+            Result::Ok(names)
+        }
+    }
+
+    pub fn record_shutdown(&mut self, record: ShutdownRecord) -> AppTask {
+        self.services_updating = true;
+
+        AppTask::perform(
+            self.store_shutdown(record).map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    /// Like [`StateModule::record_shutdown`], the returned future writes it to the database.
+    pub fn store_shutdown(
+        &mut self,
+        record: ShutdownRecord,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        self.history.push(record.clone());
+
+        let db = self.db.clone();
+
+        async move {
+            db.upsert::<Option<ShutdownRecord>>(record.id.clone())
+                .content(record)
+                .await?;
+            Result::Ok(())
+        }
+    }
+
+    pub fn remove_app(&mut self, id: RecordId) -> AppTask {
+        self.apps.retain(|a| a.id != id);
+        self.services_updating = true;
+
+        let db = self.db.clone();
+
+        AppTask::perform(
+            async move {
+                db.delete::<Option<WindowsApp>>(id).await?;
+                Result::Ok(())
+            }
+            .map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    fn update_settings_db(&mut self) -> AppTask {
+        let db = self.db.clone();
+        let settings = self.settings();
+
+        self.services_updating = true;
+
+        AppTask::perform(
+            async move {
+                db.upsert::<Option<StateSettings>>(SETTINGS)
+                    .content(settings)
+                    .await?;
+                Result::Ok(())
+            }
+            .map(Arced::arced),
+            AppMsg::UpdateStateRes,
+        )
+    }
+
+    pub fn updated<T>(&mut self, res: &Result<T>) {
+        self.services_updating = false;
+
+        if let Err(err) = res {
+            tracing::error!("Failed to update state: {err}");
+        }
+    }
+
+    /// Asks for a compose file and reads its services, `None` if the user canceled.
+    pub fn import_compose(&self) -> AppTask {
+        AppTask::perform(
+            async move {
+                let Some(file) = compose_dialog().pick_file().await else {
+                    return Result::Ok(None);
+                };
+
+                let yaml = String::from_utf8(file.read().await)?;
+                let dir = file.path().parent().unwrap_or(Path::new("/"));
+
+                ComposeFile::parse(&yaml)?.into_services(dir).map(Some)
+            }
+            .map(Arced::arced),
+            AppMsg::ImportComposeRes,
+        )
+    }
+
+    /// Writes all services out as one compose file, `None` if the user canceled.
+    pub fn export_compose(&self) -> AppTask {
+        let compose = ComposeFile::from_services(&self.services);
+
+        AppTask::perform(
+            async move {
+                let Some(file) = compose_dialog()
+                    .set_file_name("compose.yml")
+                    .save_file()
+                    .await
+                else {
+                    return Result::Ok(None);
+                };
+
+                tokio::fs::write(file.path(), compose.to_yaml()?).await?;
+
+                Ok(Some(file.path().to_path_buf()))
+            }
+            .map(Arced::arced),
+            AppMsg::ExportComposeRes,
+        )
+    }
+}
+
+async fn fetch(db: DB) -> Result<LoadedServices> {
+    let mut services: Vec<DockerServiceState> = db.select("container").await?;
+    let apps = db.select("app").await?;
+    let history = db.select("shutdown").await?;
+    let profiles = db.select("engine").await?;
+    let settings = db.select(SETTINGS).await?;
+
+    // Services saved before the guest tools took a token get one
+    for service in services.iter_mut().filter(|s| s.guest_token.is_empty()) {
+        service.guest_token = guest_token();
+        db.upsert::<Option<DockerServiceState>>(service.id.clone())
+            .content(service.clone())
+            .await?;
+    }
+
+    Ok(LoadedServices {
+        services,
+        apps,
+        history,
+        profiles,
+        settings: settings.unwrap_or_default(),
+    })
+}
+
+fn compose_dialog() -> AsyncFileDialog {
+    AsyncFileDialog::new().add_filter("Compose file", &["yml", "yaml"])
 }
 
 #[derive(SmartDefault, Debug, Clone, Serialize, Deserialize, SurrealTable)]
@@ -114,10 +666,8 @@ pub struct DockerServiceState {
     pub image: String,
     #[default = "windows"]
     pub container_name: String,
-    #[default(Map::from_iter([
-        ("VERSION".into(), "11".into())
-    ]))]
-    pub environment: Map<String, Value>,
+    #[default(Environment::from_iter([("VERSION", "11")]))]
+    pub environment: Environment,
     #[default(
         ["/dev/kvm", "/dev/net/tun"]
             .map(|x| DeviceMapping {
@@ -164,14 +714,179 @@ pub struct DockerServiceState {
         ..Default::default()
     })]
     pub restart: RestartPolicy,
-    #[default = "2m"]
-    pub stop_grace_period: String,
+    #[default(GracePeriod::from_secs(120))]
+    pub stop_grace_period: GracePeriod,
+    pub stop_signal: Option<String>,
+    pub rdp: RdpOptions,
+    /// The secret the guest tools of the service expect in their hello, handed out when the
+    /// service is stored.
+    pub guest_token: String,
+}
+
+/// Random enough that a peer on the network can't guess it.
+fn guest_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+#[derive(Debug)]
+pub struct LoadedServices {
+    pub services: Vec<DockerServiceState>,
+    pub apps: Vec<WindowsApp>,
+    pub history: Vec<ShutdownRecord>,
+    pub profiles: Vec<EngineProfile>,
+    pub settings: StateSettings,
+}
+
+/// Preferences that aren't tied to a single service.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateSettings {
+    /// Selected on startup.
+    pub default_service: Option<RecordId>,
+    pub docker_endpoint: Option<String>,
+    pub engine_profile: Option<RecordId>,
 }
 
-#[derive(SurrealQuery)]
-#[query(
-    output = "Option<DockerServiceState>",
-    error = "color_eyre::Report",
-    sql = "SELECT * FROM ONLY container"
-)]
-struct GetDockerService;
+/// Compose-style `stop_grace_period`, kept as a string (`2m`, `90s`) when stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deref, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct GracePeriod(Duration);
+
+impl GracePeriod {
+    /// What docker falls back to when a container doesn't set a `StopTimeout`.
+    pub const DOCKER_DEFAULT: Self = Self::from_secs(10);
+
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(Duration::from_secs(secs))
+    }
+
+    /// The timeout docker expects, it only deals in whole seconds and caps at `i32::MAX`.
+    pub fn secs(&self) -> i32 {
+        i32::try_from(self.0.as_secs()).unwrap_or(i32::MAX)
+    }
+}
+
+impl FromStr for GracePeriod {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let duration = parse_duration(s)?;
+
+        if duration.subsec_nanos() != 0 {
+            bail!("Grace period '{s}' has to be a whole number of seconds");
+        }
+
+        if duration.as_secs() > i32::MAX as u64 {
+            bail!("Grace period '{s}' is too long");
+        }
+
+        Ok(Self(duration))
+    }
+}
+
+impl TryFrom<String> for GracePeriod {
+    type Error = color_eyre::Report;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<GracePeriod> for String {
+    fn from(value: GracePeriod) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for GracePeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.0.as_secs();
+
+        if secs == 0 {
+            return f.write_str("0s");
+        }
+
+        let (hours, minutes, secs) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+        for (value, unit) in [(hours, "h"), (minutes, "m"), (secs, "s")] {
+            if value != 0 {
+                write!(f, "{value}{unit}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winjet_gt_proto::AppSource;
+
+    use super::*;
+    use crate::util::ScratchDir;
+
+    fn installed(name: &str, program: &str, args: Option<&str>) -> InstalledApp {
+        InstalledApp {
+            name: name.into(),
+            program: program.into(),
+            args: args.map(Into::into),
+            icon: None,
+            source: AppSource::StartMenu,
+        }
+    }
+
+    #[tokio::test]
+    async fn catalogues_discovered_apps_once() {
+        let dir = ScratchDir::new("state");
+        let service = DockerServiceState::default().id;
+        let other = DockerServiceState::default().id;
+
+        let found = vec![
+            installed("Notepad", r"C:\Windows\System32\notepad.exe", None),
+            // The Start Menu and the registry both know it
+            installed("Notepad", r"C:\WINDOWS\system32\NOTEPAD.EXE", None),
+            installed(
+                "Calculator",
+                r"C:\Windows\explorer.exe",
+                Some(r"shell:AppsFolder\Microsoft.WindowsCalculator_8wekyb3d8bbwe!App"),
+            ),
+            installed("Explorer", r"C:\Windows\explorer.exe", None),
+            // FreeRDP splits `/app:` on the comma
+            installed("Tools, Misc", r"C:\Tools\misc.exe", None),
+            installed("", r"C:\Tools\nameless.exe", None),
+        ];
+
+        let mut state = StateModule::open(dir.join("db")).await.unwrap();
+
+        let added = state.store_discovered(service.clone(), found.clone());
+        assert_eq!(added.await.unwrap(), ["Notepad", "Calculator", "Explorer"]);
+        let again = state.store_discovered(service.clone(), found.clone());
+        assert!(again.await.unwrap().is_empty());
+        let elsewhere = state.store_discovered(other.clone(), found);
+        assert_eq!(elsewhere.await.unwrap().len(), 3);
+
+        state.reload().await.unwrap();
+        assert_eq!(state.apps.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn hands_out_guest_tokens_once() {
+        let dir = ScratchDir::new("tokens");
+        let mut state = StateModule::open(dir.join("db")).await.unwrap();
+
+        state
+            .store_service(DockerServiceState::default())
+            .await
+            .unwrap();
+        state.reload().await.unwrap();
+        let token = state.services[0].guest_token.clone();
+        assert_eq!(token.len(), 32);
+
+        state
+            .store_service(state.services[0].clone())
+            .await
+            .unwrap();
+        state.reload().await.unwrap();
+        assert_eq!(state.services[0].guest_token, token);
+    }
+}
@@ -0,0 +1,96 @@
+use color_eyre::{Result, eyre::bail};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use surrealdb::{RecordId, Uuid};
+use surrealdb_extras::SurrealTable;
+use winjet_gt_proto::InstalledApp;
+
+/// A Windows program that can be launched on its own through RemoteApp.
+#[derive(SmartDefault, Debug, Clone, PartialEq, Serialize, Deserialize, SurrealTable)]
+#[table(db = app)]
+#[serde(default)]
+pub struct WindowsApp {
+    #[default(RecordId::from_table_key("app", Uuid::now_v7()))]
+    pub id: RecordId,
+    /// The [`DockerServiceState`](super::DockerServiceState) whose guest has the program.
+    #[default(RecordId::from_table_key("container", Uuid::nil()))]
+    pub service: RecordId,
+    pub name: String,
+    /// Path of the executable inside the guest, `C:\Windows\System32\notepad.exe`.
+    pub program: String,
+    /// Command line passed to the program.
+    pub args: Option<String>,
+    /// Raw ICO, PNG or BMP, converted for the desktop entry.
+    pub icon: Option<Vec<u8>>,
+    /// File types the desktop entry says the program opens.
+    pub mime_types: Vec<String>,
+}
+
+impl WindowsApp {
+    pub fn new(service: RecordId, name: String, program: String, args: Option<String>) -> Self {
+        Self {
+            service,
+            name,
+            program,
+            args,
+            ..Default::default()
+        }
+    }
+
+    /// Catalogues a program the guest tools found, if FreeRDP can launch it.
+    pub fn from_installed(service: RecordId, app: InstalledApp) -> Result<Self> {
+        let app = Self {
+            icon: app.icon,
+            ..Self::new(service, app.name, app.program, app.args)
+        };
+        app.validate()?;
+
+        Ok(app)
+    }
+
+    /// Whether both start the same program the same way, Windows paths ignore case.
+    pub fn same_launch(&self, program: &str, args: Option<&str>) -> bool {
+        self.program.eq_ignore_ascii_case(program) && self.args.as_deref() == args
+    }
+
+    /// Id key that is safe to use in file names.
+    pub fn slug(&self) -> String {
+        self.id
+            .key()
+            .to_string()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect()
+    }
+
+    /// FreeRDP splits `/app:` options on commas, so none of the values can have one.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("The application needs a name");
+        }
+
+        if self.program.trim().is_empty() {
+            bail!("The application needs a program to run");
+        }
+
+        for (field, value) in [
+            ("name", Some(&self.name)),
+            ("program", Some(&self.program)),
+            ("arguments", self.args.as_ref()),
+        ] {
+            if value.is_some_and(|x| x.contains(',')) {
+                bail!("The {field} of an application can't contain a comma");
+            }
+        }
+
+        if let Some(mime) = self
+            .mime_types
+            .iter()
+            .find(|x| x.split('/').filter(|x| !x.is_empty()).count() != 2 || x.contains(';'))
+        {
+            bail!("'{mime}' is not a MIME type");
+        }
+
+        Ok(())
+    }
+}
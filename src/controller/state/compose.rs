@@ -0,0 +1,490 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+};
+
+use bollard::secret::{DeviceMapping, Port, PortTypeEnum, RestartPolicy, RestartPolicyNameEnum};
+use color_eyre::{
+    Result,
+    eyre::{WrapErr, bail, eyre},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::controller::{
+    docker::is_windows_image,
+    state::{DockerServiceState, EnvVar, Environment, GracePeriod},
+};
+
+/// The parts of a `compose.yml` winjet understands, anything else is ignored on import.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ComposeFile {
+    #[serde(default)]
+    pub services: BTreeMap<String, ComposeService>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComposeService {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+    #[serde(skip_serializing_if = "<[EnvVar]>::is_empty")]
+    pub environment: Environment,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cap_add: Vec<ComposeValue>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<ComposeValue>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_grace_period: Option<GracePeriod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_signal: Option<String>,
+}
+
+/// What importing a service of a compose file did.
+#[derive(Debug, Clone, Serialize)]
+pub struct Imported {
+    pub name: String,
+    /// `false` if a service already manages the container.
+    pub imported: bool,
+}
+
+/// Compose lets short-syntax entries be bare numbers (`- 8006`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ComposeValue {
+    Text(String),
+    Number(u64),
+}
+
+impl Display for ComposeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(text) => f.write_str(text),
+            Self::Number(number) => write!(f, "{number}"),
+        }
+    }
+}
+
+impl ComposeFile {
+    pub fn parse(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str(yaml).wrap_err("Invalid compose file")
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Services are keyed by their container name.
+    pub fn from_services<'a>(services: impl IntoIterator<Item = &'a DockerServiceState>) -> Self {
+        Self {
+            services: services
+                .into_iter()
+                .map(|service| (service.container_name.clone(), service.into()))
+                .collect(),
+        }
+    }
+
+    /// The `dockurr/windows` services of the file, the others aren't something winjet manages.
+    ///
+    /// Relative host paths are resolved against `dir`, the directory of the file, like compose
+    /// does.
+    pub fn into_services(self, dir: &Path) -> Result<Vec<DockerServiceState>> {
+        if self.services.is_empty() {
+            bail!("The compose file has no services");
+        }
+
+        let services = self
+            .services
+            .into_iter()
+            .filter(|(name, service)| {
+                let windows = service.image.as_deref().is_some_and(is_windows_image);
+                if !windows {
+                    tracing::info!("Skipping service '{name}', it doesn't run dockurr/windows");
+                }
+                windows
+            })
+            .map(|(name, service)| {
+                service
+                    .into_service(&name, dir)
+                    .wrap_err_with(|| format!("Invalid service '{name}'"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if services.is_empty() {
+            bail!("The compose file has no dockurr/windows services");
+        }
+
+        Ok(services)
+    }
+}
+
+impl From<&DockerServiceState> for ComposeService {
+    fn from(service: &DockerServiceState) -> Self {
+        Self {
+            image: Some(service.image.clone()),
+            container_name: Some(service.container_name.clone()),
+            environment: service.environment.clone(),
+            devices: service.devices.iter().flat_map(device_spec).collect(),
+            cap_add: service
+                .cap_add
+                .iter()
+                .cloned()
+                .map(ComposeValue::Text)
+                .collect(),
+            ports: service
+                .ports
+                .iter()
+                .map(|port| ComposeValue::Text(port_spec(port)))
+                .collect(),
+            volumes: service.volumes.clone(),
+            restart: service.restart.name.map(|name| match name {
+                RestartPolicyNameEnum::ON_FAILURE => match service.restart.maximum_retry_count {
+                    Some(count) if count > 0 => format!("{name}:{count}"),
+                    _ => name.to_string(),
+                },
+                name => name.to_string(),
+            }),
+            stop_grace_period: Some(service.stop_grace_period),
+            stop_signal: service.stop_signal.clone(),
+        }
+    }
+}
+
+impl ComposeService {
+    /// Fields the file leaves out get what compose would use, not the defaults of a new
+    /// [`DockerServiceState`].
+    pub fn into_service(self, name: &str, dir: &Path) -> Result<DockerServiceState> {
+        Ok(DockerServiceState {
+            image: self
+                .image
+                .ok_or_else(|| eyre!("Only services with an image are supported"))?,
+            container_name: self.container_name.unwrap_or_else(|| name.into()),
+            environment: self.environment,
+            devices: self
+                .devices
+                .iter()
+                .map(|device| parse_device(device))
+                .collect::<Result<_>>()?,
+            cap_add: self.cap_add.iter().map(ToString::to_string).collect(),
+            ports: self
+                .ports
+                .iter()
+                .map(|port| parse_port(&port.to_string()))
+                .collect::<Result<_>>()?,
+            volumes: self
+                .volumes
+                .iter()
+                .map(|volume| resolve_volume(volume, dir))
+                .collect(),
+            restart: match self.restart {
+                Some(restart) => parse_restart(&restart)?,
+                None => RestartPolicy {
+                    name: Some(RestartPolicyNameEnum::NO),
+                    ..Default::default()
+                },
+            },
+            stop_grace_period: self
+                .stop_grace_period
+                .unwrap_or(GracePeriod::DOCKER_DEFAULT),
+            stop_signal: self.stop_signal,
+            ..Default::default()
+        })
+    }
+}
+
+/// Makes the host side of a bind absolute, docker only takes absolute paths or volume names.
+fn resolve_volume(spec: &str, dir: &Path) -> String {
+    // Without a colon it's only the path in the container, an anonymous volume
+    let Some((host, rest)) = spec.split_once(':') else {
+        return spec.into();
+    };
+
+    let path = if host == "~" || host.starts_with("~/") {
+        let Some(home) = std::env::var_os("HOME") else {
+            return spec.into();
+        };
+        PathBuf::from(home).join(host.trim_start_matches('~').trim_start_matches('/'))
+    } else if host.starts_with('.') {
+        dir.join(host)
+    } else {
+        // Absolute already, or the name of a volume
+        return spec.into();
+    };
+
+    format!("{}:{rest}", normalize(&path).display())
+}
+
+/// Drops `.` and resolves `..` without touching the filesystem, the path may not exist yet.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .fold(PathBuf::new(), |mut normalized, component| {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+            normalized
+        })
+}
+
+/// `HOST[:CONTAINER[:PERMISSIONS]]`
+fn parse_device(spec: &str) -> Result<DeviceMapping> {
+    let mut parts = spec.split(':');
+
+    let path_on_host = parts
+        .next()
+        .filter(|x| !x.is_empty())
+        .ok_or_else(|| eyre!("Invalid device '{spec}'"))?;
+    let path_in_container = parts.next();
+    let cgroup_permissions = parts.next();
+
+    if parts.next().is_some() {
+        bail!("Invalid device '{spec}'");
+    }
+
+    Ok(DeviceMapping {
+        path_on_host: Some(path_on_host.into()),
+        path_in_container: path_in_container.map(String::from),
+        cgroup_permissions: cgroup_permissions.map(String::from),
+    })
+}
+
+fn device_spec(device: &DeviceMapping) -> Option<String> {
+    let host = device.path_on_host.as_deref()?;
+
+    Some(
+        match (
+            device.path_in_container.as_deref(),
+            device.cgroup_permissions.as_deref(),
+        ) {
+            (None, None) => host.into(),
+            (container, None) => format!("{host}:{}", container.unwrap_or(host)),
+            (container, Some(permissions)) => {
+                format!("{host}:{}:{permissions}", container.unwrap_or(host))
+            }
+        },
+    )
+}
+
+/// Short syntax, `[[IP:]HOST:]CONTAINER[/PROTOCOL]`, without ranges.
+fn parse_port(spec: &str) -> Result<Port> {
+    let (address, typ) = match spec.split_once('/') {
+        Some((address, typ)) => (
+            address,
+            typ.parse::<PortTypeEnum>()
+                .map_err(|_| eyre!("Unknown protocol in port '{spec}'"))?,
+        ),
+        None => (spec, PortTypeEnum::TCP),
+    };
+
+    let number = |port: &str| {
+        port.parse::<u16>()
+            .map_err(|_| eyre!("Unsupported port '{spec}', only single ports are supported"))
+    };
+
+    let (ip, public, private) = match address.rsplitn(3, ':').collect::<Vec<_>>()[..] {
+        [private] => (None, None, private),
+        [private, public] => (None, Some(public), private),
+        [private, public, ip] => (Some(ip), Some(public), private),
+        _ => unreachable!(),
+    };
+
+    Ok(Port {
+        ip: ip
+            .map(|ip| ip.trim_start_matches('[').trim_end_matches(']'))
+            .filter(|ip| !ip.is_empty())
+            .map(String::from),
+        private_port: number(private)?,
+        public_port: public.filter(|x| !x.is_empty()).map(number).transpose()?,
+        typ: Some(typ),
+    })
+}
+
+fn port_spec(port: &Port) -> String {
+    let mut spec = match (port.ip.as_deref(), port.public_port) {
+        (Some(ip), Some(public)) if ip.contains(':') => format!("[{ip}]:{public}:"),
+        (Some(ip), Some(public)) => format!("{ip}:{public}:"),
+        (None, Some(public)) => format!("{public}:"),
+        (_, None) => String::new(),
+    };
+    spec.push_str(&port.private_port.to_string());
+
+    if let Some(typ) = port.typ.filter(|typ| *typ != PortTypeEnum::TCP) {
+        spec.push_str(&format!("/{typ}"));
+    }
+
+    spec
+}
+
+/// `no`, `always`, `unless-stopped` or `on-failure[:MAX_RETRIES]`.
+fn parse_restart(spec: &str) -> Result<RestartPolicy> {
+    let (name, retries) = match spec.split_once(':') {
+        Some((name, retries)) => (
+            name,
+            Some(
+                retries
+                    .parse::<i64>()
+                    .map_err(|_| eyre!("Invalid restart policy '{spec}'"))?,
+            ),
+        ),
+        None => (spec, None),
+    };
+
+    let name = name
+        .parse::<RestartPolicyNameEnum>()
+        .map_err(|_| eyre!("Invalid restart policy '{spec}'"))?;
+
+    if retries.is_some() && name != RestartPolicyNameEnum::ON_FAILURE {
+        bail!("Only on-failure takes a retry count, got '{spec}'");
+    }
+
+    Ok(RestartPolicy {
+        name: Some(name),
+        maximum_retry_count: retries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> DockerServiceState {
+        DockerServiceState {
+            image: "dockurr/windows:4.14".into(),
+            container_name: "office".into(),
+            environment: Environment::from_iter([("VERSION", "11"), ("RAM_SIZE", "8G")]),
+            ports: vec![
+                parse_port("127.0.0.1:8006:8006").unwrap(),
+                parse_port("3389:3389/udp").unwrap(),
+            ],
+            volumes: vec!["/srv/office:/storage".into(), "shared:/shared".into()],
+            restart: parse_restart("on-failure:3").unwrap(),
+            stop_grace_period: GracePeriod::from_secs(90),
+            stop_signal: Some("SIGINT".into()),
+            ..Default::default()
+        }
+    }
+
+    fn reimport(services: &[DockerServiceState]) -> Vec<DockerServiceState> {
+        let yaml = ComposeFile::from_services(services).to_yaml().unwrap();
+        ComposeFile::parse(&yaml)
+            .unwrap()
+            .into_services(Path::new("/elsewhere"))
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_a_service() {
+        let original = service();
+        let [imported] = &reimport(std::slice::from_ref(&original))[..] else {
+            panic!("expected one service");
+        };
+
+        assert_eq!(imported.image, original.image);
+        assert_eq!(imported.container_name, original.container_name);
+        assert_eq!(imported.environment, original.environment);
+        assert_eq!(imported.devices, original.devices);
+        assert_eq!(imported.cap_add, original.cap_add);
+        assert_eq!(imported.ports, original.ports);
+        assert_eq!(imported.volumes, original.volumes);
+        assert_eq!(imported.restart, original.restart);
+        assert_eq!(imported.stop_grace_period, original.stop_grace_period);
+        assert_eq!(imported.stop_signal, original.stop_signal);
+    }
+
+    #[test]
+    fn round_trips_every_service() {
+        let other = DockerServiceState {
+            container_name: "games".into(),
+            volumes: vec!["/srv/games:/storage".into()],
+            ..service()
+        };
+        let original = [service(), other];
+
+        let yaml = ComposeFile::from_services(&original).to_yaml().unwrap();
+        let again = ComposeFile::from_services(&reimport(&original))
+            .to_yaml()
+            .unwrap();
+
+        assert_eq!(again, yaml);
+    }
+
+    #[test]
+    fn resolves_relative_host_paths() {
+        let yaml = "
+services:
+  windows:
+    image: dockurr/windows
+    volumes:
+      - ./windows:/storage
+      - ../shared/:/shared:ro
+      - .:/data
+      - /srv/iso/win11.iso:/boot.iso
+      - storage:/oem
+      - /tmp/anonymous
+";
+        let [service] = &ComposeFile::parse(yaml)
+            .unwrap()
+            .into_services(Path::new("/home/me/vms"))
+            .unwrap()[..]
+        else {
+            panic!("expected one service");
+        };
+
+        assert_eq!(
+            service.volumes,
+            [
+                "/home/me/vms/windows:/storage",
+                "/home/me/shared:/shared:ro",
+                "/home/me/vms:/data",
+                "/srv/iso/win11.iso:/boot.iso",
+                "storage:/oem",
+                "/tmp/anonymous",
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_only_windows_services() {
+        let yaml = "
+services:
+  windows:
+    image: docker.io/dockurr/windows:latest
+  proxy:
+    image: caddy:2
+  builder:
+    build: .
+";
+        let services = ComposeFile::parse(yaml)
+            .unwrap()
+            .into_services(Path::new("/"))
+            .unwrap();
+
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].container_name, "windows");
+        assert_eq!(services[0].restart.name, Some(RestartPolicyNameEnum::NO));
+        assert_eq!(services[0].stop_grace_period, GracePeriod::DOCKER_DEFAULT);
+
+        let yaml = "
+services:
+  proxy:
+    image: caddy:2
+";
+        assert!(
+            ComposeFile::parse(yaml)
+                .unwrap()
+                .into_services(Path::new("/"))
+                .is_err()
+        );
+    }
+}
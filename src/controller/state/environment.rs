@@ -0,0 +1,656 @@
+use std::{fmt::Display, str::FromStr};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use derive_more::{Deref, DerefMut};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{MapAccess, Visitor},
+};
+use serde_json::Value;
+
+use crate::controller::host::HostResources;
+
+/// Container environment, kept exactly as docker reports it (`KEY=VALUE`, in order).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deref, DerefMut, Serialize, Deserialize)]
+#[serde(into = "Vec<String>", from = "EnvironmentRepr")]
+pub struct Environment(Vec<EnvVar>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub key: String,
+    /// `None` for a bare `KEY`, which docker resolves from its own environment.
+    pub value: Option<String>,
+}
+
+impl EnvVar {
+    pub fn parse(entry: &str) -> Self {
+        match entry.split_once('=') {
+            Some((key, value)) => Self {
+                key: key.into(),
+                value: Some(value.into()),
+            },
+            None => Self {
+                key: entry.into(),
+                value: None,
+            },
+        }
+    }
+
+    pub fn entry(&self) -> String {
+        match &self.value {
+            Some(value) => format!("{}={value}", self.key),
+            None => self.key.clone(),
+        }
+    }
+}
+
+/// Older records stored the environment as a JSON object, compose files allow either form.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnvironmentRepr {
+    Entries(Vec<String>),
+    Map(OrderedMap),
+}
+
+/// Map entries in the order they were written.
+struct OrderedMap(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for OrderedMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedMapVisitor;
+
+        impl<'de> Visitor<'de> for OrderedMapVisitor {
+            type Value = OrderedMap;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a map of environment variables")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OrderedMap, A::Error> {
+                let mut entries = vec![];
+
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+
+                Ok(OrderedMap(entries))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedMapVisitor)
+    }
+}
+
+impl From<EnvironmentRepr> for Environment {
+    fn from(repr: EnvironmentRepr) -> Self {
+        match repr {
+            EnvironmentRepr::Entries(entries) => Self::from_entries(entries),
+            EnvironmentRepr::Map(OrderedMap(map)) => Self(
+                map.into_iter()
+                    .map(|(key, value)| {
+                        // Compose leaves the value out (`KEY:`) to pass the variable through
+                        let value = match value {
+                            Value::Null => None,
+                            Value::String(value) => Some(value),
+                            value => Some(value.to_string()),
+                        };
+
+                        EnvVar { key, value }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Environment> for Vec<String> {
+    fn from(env: Environment) -> Self {
+        env.entries()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Environment
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut env = Self::default();
+
+        for (key, value) in iter {
+            env.set(key, value);
+        }
+
+        env
+    }
+}
+
+impl Environment {
+    pub fn from_entries(entries: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self(
+            entries
+                .into_iter()
+                .map(|entry| EnvVar::parse(entry.as_ref()))
+                .collect(),
+        )
+    }
+
+    pub fn entries(&self) -> Vec<String> {
+        self.0.iter().map(EnvVar::entry).collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find(|var| var.key == key)
+            .and_then(|var| var.value.as_deref())
+    }
+
+    /// Replaces the value in place if the key is already there, appends it otherwise.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+        let value = Some(value.into());
+
+        match self.0.iter_mut().rev().find(|var| var.key == key) {
+            Some(var) => var.value = value,
+            None => self.0.push(EnvVar { key, value }),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let ind = self.0.iter().rposition(|var| var.key == key)?;
+        self.0.remove(ind).value
+    }
+
+    fn parsed<T>(&self, var: DockurrVar) -> Option<Result<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.get(var.key()).map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|err| eyre!("Invalid {}: '{value}' ({err})", var.key()))
+        })
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.get(DockurrVar::Version.key())
+    }
+
+    pub fn ram_size(&self) -> Option<Result<Size>> {
+        self.parsed(DockurrVar::RamSize)
+    }
+
+    pub fn cpu_cores(&self) -> Option<Result<u32>> {
+        self.parsed(DockurrVar::CpuCores)
+    }
+
+    pub fn disk_size(&self) -> Option<Result<Size>> {
+        self.parsed(DockurrVar::DiskSize)
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.get(DockurrVar::Username.key())
+    }
+
+    pub fn password(&self) -> Option<&str> {
+        self.get(DockurrVar::Password.key())
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.get(DockurrVar::Language.key())
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.get(DockurrVar::Region.key())
+    }
+
+    pub fn keyboard(&self) -> Option<&str> {
+        self.get(DockurrVar::Keyboard.key())
+    }
+
+    /// Checks every `dockurr/windows` variable that is set.
+    pub fn validate(&self, host: &HostResources) -> Result<()> {
+        DockurrVar::ALL
+            .into_iter()
+            .try_for_each(|var| match self.get(var.key()) {
+                Some(value) => var.validate(value, host),
+                None => Ok(()),
+            })
+    }
+}
+
+/// Variables `dockurr/windows` understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DockurrVar {
+    Version,
+    RamSize,
+    CpuCores,
+    DiskSize,
+    Username,
+    Password,
+    Language,
+    Region,
+    Keyboard,
+}
+
+impl DockurrVar {
+    pub const ALL: [Self; 9] = [
+        Self::Version,
+        Self::RamSize,
+        Self::CpuCores,
+        Self::DiskSize,
+        Self::Username,
+        Self::Password,
+        Self::Language,
+        Self::Region,
+        Self::Keyboard,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Version => "VERSION",
+            Self::RamSize => "RAM_SIZE",
+            Self::CpuCores => "CPU_CORES",
+            Self::DiskSize => "DISK_SIZE",
+            Self::Username => "USERNAME",
+            Self::Password => "PASSWORD",
+            Self::Language => "LANGUAGE",
+            Self::Region => "REGION",
+            Self::Keyboard => "KEYBOARD",
+        }
+    }
+}
+
+/// Editions `VERSION` accepts, as `(value, description)`.
+pub const VERSIONS: &[(&str, &str)] = &[
+    ("11", "Windows 11 Pro"),
+    ("11l", "Windows 11 LTSC"),
+    ("11e", "Windows 11 Enterprise"),
+    ("10", "Windows 10 Pro"),
+    ("10l", "Windows 10 LTSC"),
+    ("10e", "Windows 10 Enterprise"),
+    ("ltsc", "Windows 10 LTSC"),
+    ("8e", "Windows 8.1 Enterprise"),
+    ("7u", "Windows 7 Ultimate"),
+    ("vu", "Windows Vista Ultimate"),
+    ("xp", "Windows XP Professional"),
+    ("2k", "Windows 2000 Professional"),
+    ("2025", "Windows Server 2025"),
+    ("2022", "Windows Server 2022"),
+    ("2019", "Windows Server 2019"),
+    ("2016", "Windows Server 2016"),
+    ("2012", "Windows Server 2012"),
+    ("2008", "Windows Server 2008"),
+    ("2003", "Windows Server 2003"),
+    ("tiny11", "Tiny11"),
+    ("tiny10", "Tiny10"),
+];
+
+pub const LANGUAGES: &[&str] = &[
+    "Arabic",
+    "Bulgarian",
+    "Chinese",
+    "Croatian",
+    "Czech",
+    "Danish",
+    "Dutch",
+    "English",
+    "Estonian",
+    "Finnish",
+    "French",
+    "German",
+    "Greek",
+    "Hebrew",
+    "Hungarian",
+    "Italian",
+    "Japanese",
+    "Korean",
+    "Latvian",
+    "Lithuanian",
+    "Norwegian",
+    "Polish",
+    "Portuguese",
+    "Romanian",
+    "Russian",
+    "Serbian",
+    "Slovak",
+    "Slovenian",
+    "Spanish",
+    "Swedish",
+    "Thai",
+    "Turkish",
+    "Ukrainian",
+];
+
+/// Locale codes offered for `REGION` and `KEYBOARD`.
+pub const LOCALES: &[&str] = &[
+    "ar-SA", "bg-BG", "cs-CZ", "da-DK", "de-DE", "el-GR", "en-GB", "en-US", "es-ES", "es-MX",
+    "et-EE", "fi-FI", "fr-CA", "fr-FR", "he-IL", "hr-HR", "hu-HU", "it-IT", "ja-JP", "ko-KR",
+    "lt-LT", "lv-LV", "nb-NO", "nl-NL", "pl-PL", "pt-BR", "pt-PT", "ro-RO", "ru-RU", "sk-SK",
+    "sl-SI", "sr-RS", "sv-SE", "th-TH", "tr-TR", "uk-UA", "zh-CN", "zh-TW",
+];
+
+impl DockurrVar {
+    /// What the image uses when the variable isn't set.
+    pub fn image_default(&self) -> Option<&'static str> {
+        match self {
+            Self::Version => Some("11"),
+            Self::RamSize => Some("4G"),
+            Self::CpuCores => Some("2"),
+            Self::DiskSize => Some("64G"),
+            Self::Username => Some("Docker"),
+            Self::Password => Some("admin"),
+            Self::Language => Some("English"),
+            Self::Region | Self::Keyboard => Some("en-US"),
+        }
+    }
+
+    pub fn validate(&self, value: &str, host: &HostResources) -> Result<()> {
+        let value = value.trim();
+        let key = self.key();
+
+        match self {
+            Self::Version => {
+                let known = VERSIONS.iter().any(|(version, _)| *version == value);
+                // The image also takes a link to a custom ISO
+                let url = value.starts_with("http://") || value.starts_with("https://");
+
+                if !known && !url {
+                    bail!("{key}: unknown version '{value}'");
+                }
+            }
+            Self::RamSize => {
+                let size = value.parse::<Size>()?;
+
+                if size < Size::from_gib(1) {
+                    bail!("{key}: Windows needs at least 1G of RAM");
+                }
+
+                if size > host.memory {
+                    bail!("{key}: {size} is more than the host has ({})", host.memory);
+                }
+            }
+            Self::CpuCores => {
+                let cores = value
+                    .parse::<u32>()
+                    .map_err(|err| eyre!("{key}: invalid number '{value}' ({err})"))?;
+
+                if !(1..=host.cpus).contains(&cores) {
+                    bail!("{key}: has to be between 1 and {}", host.cpus);
+                }
+            }
+            Self::DiskSize => {
+                if value.parse::<Size>()? == Size::from_bytes(0) {
+                    bail!("{key}: the disk can't be empty");
+                }
+            }
+            Self::Username => {
+                if value.is_empty() {
+                    bail!("{key}: can't be empty");
+                }
+
+                if let Some(c) = value.chars().find(|c| r#""/\[]:;|=,+*?<>@"#.contains(*c)) {
+                    bail!("{key}: '{c}' is not allowed in a Windows user name");
+                }
+            }
+            Self::Password => {}
+            Self::Language => {
+                if !LANGUAGES.iter().any(|l| l.eq_ignore_ascii_case(value)) {
+                    bail!("{key}: unknown language '{value}'");
+                }
+            }
+            Self::Region | Self::Keyboard => {
+                let valid = value.split_once('-').is_some_and(|(lang, country)| {
+                    (2..=3).contains(&lang.len())
+                        && lang.chars().all(|c| c.is_ascii_lowercase())
+                        && country.len() == 2
+                        && country.chars().all(|c| c.is_ascii_uppercase())
+                });
+
+                if !valid {
+                    bail!("{key}: '{value}' is not a locale code like 'en-US'");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Size in the `8G`/`512M` notation the image uses for `RAM_SIZE` and `DISK_SIZE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Size(u64);
+
+impl Size {
+    const UNITS: [(char, u64); 4] = [
+        ('T', 1 << 40),
+        ('G', 1 << 30),
+        ('M', 1 << 20),
+        ('K', 1 << 10),
+    ];
+
+    pub const fn from_bytes(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn from_mib(mib: u64) -> Self {
+        Self(mib << 20)
+    }
+
+    pub const fn from_gib(gib: u64) -> Self {
+        Self(gib << 30)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.0
+    }
+
+    pub fn mib(&self) -> u64 {
+        self.0 >> 20
+    }
+
+    pub fn gib(&self) -> u64 {
+        self.0 >> 30
+    }
+}
+
+impl FromStr for Size {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let upper = s.trim().to_ascii_uppercase();
+        let trimmed = upper
+            .strip_suffix("IB")
+            .or_else(|| upper.strip_suffix('B'))
+            .unwrap_or(&upper);
+
+        let (number, multiplier) = match trimmed.chars().last() {
+            Some(c) if c.is_ascii_digit() => (trimmed, 1),
+            Some(c) => match Self::UNITS.iter().find(|(unit, _)| *unit == c) {
+                Some((_, multiplier)) => (&trimmed[..trimmed.len() - 1], *multiplier),
+                None => bail!("Unknown size unit '{c}' in '{s}'"),
+            },
+            None => bail!("Empty size"),
+        };
+
+        let number = number
+            .trim()
+            .parse::<u64>()
+            .map_err(|err| eyre!("Invalid size '{s}': {err}"))?;
+
+        number
+            .checked_mul(multiplier)
+            .map(Self)
+            .ok_or_else(|| eyre!("Size '{s}' is too large"))
+    }
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match Self::UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 != 0 && self.0.is_multiple_of(*multiplier))
+        {
+            Some((unit, multiplier)) => write!(f, "{}{unit}", self.0 / multiplier),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(entries: &[&str]) -> Vec<String> {
+        let env = Environment::from_entries(entries);
+        let json = serde_json::to_string(&env).unwrap();
+
+        serde_json::from_str::<Environment>(&json)
+            .unwrap()
+            .entries()
+    }
+
+    #[test]
+    fn keeps_everything_after_the_first_equals_sign() {
+        let env = Environment::from_entries(["ARGUMENTS=-device usb-host,vendorid=0x1234"]);
+
+        assert_eq!(env[0].key, "ARGUMENTS");
+        assert_eq!(
+            env[0].value.as_deref(),
+            Some("-device usb-host,vendorid=0x1234")
+        );
+        assert_eq!(round_trip(&["A=b=c"]), ["A=b=c"]);
+        assert_eq!(round_trip(&["EMPTY="]), ["EMPTY="]);
+    }
+
+    #[test]
+    fn keeps_bare_keys() {
+        let env = Environment::from_entries(["PASSTHROUGH"]);
+
+        assert_eq!(env[0].value, None);
+        assert_eq!(env.get("PASSTHROUGH"), None);
+        assert_eq!(round_trip(&["PASSTHROUGH"]), ["PASSTHROUGH"]);
+    }
+
+    #[test]
+    fn keeps_values_as_strings() {
+        let env = Environment::from_entries(["VERSION=11", "CPU_CORES=04"]);
+
+        assert_eq!(env.version(), Some("11"));
+        assert_eq!(
+            serde_json::to_value(&env).unwrap(),
+            serde_json::json!(["VERSION=11", "CPU_CORES=04"])
+        );
+        assert_eq!(round_trip(&["CPU_CORES=04"]), ["CPU_CORES=04"]);
+    }
+
+    #[test]
+    fn keeps_the_order_of_entries() {
+        let entries = [
+            "VERSION=11",
+            "USERNAME=alice",
+            "RAM_SIZE=8G",
+            "ARGUMENTS=-qmp tcp:0.0.0.0:7101,server,wait=off",
+            "BARE",
+            "CPU_CORES=4",
+        ];
+        assert_eq!(round_trip(&entries), entries);
+
+        // Setting a key that's there already doesn't move it
+        let mut env = Environment::from_entries(entries);
+        env.set("RAM_SIZE", "16G");
+        env.set("DISK_SIZE", "128G");
+        assert_eq!(env.entries()[2], "RAM_SIZE=16G");
+        assert_eq!(env.entries().last().unwrap(), "DISK_SIZE=128G");
+
+        assert_eq!(env.remove("USERNAME").as_deref(), Some("alice"));
+        assert_eq!(env.entries()[1], "RAM_SIZE=16G");
+    }
+
+    #[test]
+    fn loads_maps_of_older_records() {
+        let env = serde_json::from_str::<Environment>(
+            r#"{"VERSION": "11", "RAM_SIZE": "8G", "CPU_CORES": 4, "KVM": true, "PASSTHROUGH": null}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            env.entries(),
+            [
+                "VERSION=11",
+                "RAM_SIZE=8G",
+                "CPU_CORES=4",
+                "KVM=true",
+                "PASSTHROUGH",
+            ]
+        );
+        assert!(serde_json::from_str::<Environment>("42").is_err());
+    }
+
+    #[test]
+    fn parses_sizes() {
+        for (input, bytes) in [
+            ("512M", 512 << 20),
+            ("4G", 4 << 30),
+            ("4g", 4 << 30),
+            ("4GB", 4 << 30),
+            ("4GiB", 4 << 30),
+            (" 64G ", 64 << 30),
+            ("1T", 1 << 40),
+            ("2048", 2048),
+        ] {
+            assert_eq!(input.parse::<Size>().unwrap().bytes(), bytes, "{input:?}");
+        }
+
+        for input in ["", "G", "4X", "-4G", "4.5G", "four", "99999999999T"] {
+            assert!(input.parse::<Size>().is_err(), "{input:?} parsed");
+        }
+    }
+
+    #[test]
+    fn displays_sizes_in_the_largest_whole_unit() {
+        for (input, shown) in [
+            ("512M", "512M"),
+            ("4G", "4G"),
+            ("4096M", "4G"),
+            ("1536M", "1536M"),
+            ("1024G", "1T"),
+            ("1000", "1000"),
+            ("0", "0"),
+        ] {
+            assert_eq!(input.parse::<Size>().unwrap().to_string(), shown);
+        }
+    }
+
+    #[test]
+    fn keeps_sizes_in_mib() {
+        assert_eq!("512M".parse::<Size>().unwrap().mib(), 512);
+        assert_eq!("512M".parse::<Size>().unwrap().gib(), 0);
+        assert_eq!(Size::from_mib(1536).to_string(), "1536M");
+        assert_eq!(Size::from_mib(8192).to_string(), "8G");
+    }
+
+    #[test]
+    fn validates_against_the_host() {
+        let host = HostResources {
+            memory: Size::from_gib(16),
+            cpus: 8,
+        };
+        let valid = |entries: &[&str]| Environment::from_entries(entries).validate(&host);
+
+        assert!(valid(&["VERSION=11", "RAM_SIZE=8G", "CPU_CORES=8", "BARE"]).is_ok());
+        assert!(valid(&["RAM_SIZE=32G"]).is_err());
+        assert!(valid(&["RAM_SIZE=512M"]).is_err());
+        assert!(valid(&["CPU_CORES=0"]).is_err());
+        assert!(valid(&["CPU_CORES=9"]).is_err());
+        assert!(valid(&["VERSION=12"]).is_err());
+        assert!(valid(&["VERSION=https://example.com/win.iso"]).is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use surrealdb::{RecordId, Uuid};
+use surrealdb_extras::SurrealTable;
+
+/// How the Windows of a service went down when its container was stopped.
+#[derive(SmartDefault, Debug, Clone, PartialEq, Serialize, Deserialize, SurrealTable)]
+#[table(db = shutdown)]
+#[serde(default)]
+pub struct ShutdownRecord {
+    #[default(RecordId::from_table_key("shutdown", Uuid::now_v7()))]
+    pub id: RecordId,
+    #[default(RecordId::from_table_key("container", Uuid::nil()))]
+    pub service: RecordId,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    /// `None` if nothing took the shutdown request.
+    pub via: Option<ShutdownVia>,
+    pub outcome: ShutdownOutcome,
+    /// Until the container was down.
+    pub took_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownVia {
+    GuestTools,
+    /// The power button QEMU emulates.
+    Acpi,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownOutcome {
+    /// Windows shut down within the grace period.
+    #[default]
+    Clean,
+    /// Windows couldn't be asked to shut down, Docker stopped the container.
+    Forced,
+    /// Windows was still up after the grace period, the container was stopped anyway.
+    TimedOut,
+}
+
+impl ShutdownRecord {
+    pub fn new(
+        service: RecordId,
+        via: Option<ShutdownVia>,
+        outcome: ShutdownOutcome,
+        took: Duration,
+    ) -> Self {
+        Self {
+            service,
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            via,
+            outcome,
+            took_secs: took.as_secs(),
+            ..Default::default()
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.outcome {
+            ShutdownOutcome::Clean => format!("Windows shut down in {}s", self.took_secs),
+            ShutdownOutcome::Forced => {
+                "Windows couldn't be asked to shut down, the container was stopped".into()
+            }
+            ShutdownOutcome::TimedOut => format!(
+                "Windows didn't shut down in time, the container was stopped after {}s",
+                self.took_secs
+            ),
+        }
+    }
+}
+
+impl Display for ShutdownVia {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::GuestTools => "the guest tools",
+            Self::Acpi => "ACPI",
+        })
+    }
+}
+
+impl Display for ShutdownOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Clean => "clean",
+            Self::Forced => "forced",
+            Self::TimedOut => "timed out",
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use color_eyre::{Result, eyre::bail};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use surrealdb::{RecordId, Uuid};
+use surrealdb_extras::SurrealTable;
+
+use crate::controller::docker::Connection;
+
+/// A named engine on another machine, Windows runs there while winjet runs here.
+#[derive(SmartDefault, Debug, Clone, PartialEq, Serialize, Deserialize, SurrealTable)]
+#[table(db = engine)]
+#[serde(default)]
+pub struct EngineProfile {
+    #[default(RecordId::from_table_key("engine", Uuid::now_v7()))]
+    pub id: RecordId,
+    pub name: String,
+    /// `tcp://workstation:2376` or `ssh://user@workstation`.
+    pub endpoint: String,
+    /// Folder with the `ca.pem`, `cert.pem` and `key.pem` of TLS over `tcp://`.
+    pub cert_path: Option<PathBuf>,
+}
+
+impl EngineProfile {
+    pub fn new(name: String, endpoint: String, cert_path: Option<PathBuf>) -> Self {
+        Self {
+            name,
+            endpoint,
+            cert_path,
+            ..Default::default()
+        }
+    }
+
+    pub fn connection(&self) -> Connection {
+        Connection {
+            endpoint: self.endpoint.clone(),
+            cert_path: self.cert_path.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("The profile needs a name");
+        }
+
+        match self.endpoint.split_once("://") {
+            Some(("tcp" | "http" | "https" | "ssh" | "unix", rest)) if !rest.is_empty() => {}
+            _ => bail!(
+                "The endpoint has to look like tcp://host:2376, ssh://user@host or \
+                 unix:///path/to/engine.sock"
+            ),
+        }
+
+        if let Some(certs) = &self.cert_path {
+            for file in ["ca.pem", "cert.pem", "key.pem"] {
+                if !certs.join(file).is_file() {
+                    bail!("{} is missing {file}", certs.display());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+use std::time::Duration;
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use iced::{
+    futures::{SinkExt, StreamExt, channel::mpsc},
+    widget::image,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::oneshot,
+};
+
+use crate::{
+    app::{AppMsg, AppTask},
+    controller::docker::{ContainerData, DockerContainerExt},
+};
+
+/// QEMU's VNC server inside the `dockurr/windows` container.
+pub const VNC_PORT: u16 = 5900;
+/// The noVNC web viewer of `dockurr/windows`.
+pub const WEB_PORT: u16 = 8006;
+
+/// Watching an installation doesn't need more than this.
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// RFB encodings, the pseudo-encoding tells us when the guest changes resolution.
+const ENCODING_RAW: i32 = 0;
+const ENCODING_COPY_RECT: i32 = 1;
+const ENCODING_DESKTOP_SIZE: i32 = -223;
+
+/// Desktop names and refusal reasons are far shorter, anything longer isn't worth allocating.
+const MAX_STRING: u32 = 64 * 1024;
+
+/// The web viewer of the container, through the port it's published on.
+pub fn console_url(container: &ContainerData) -> Result<String> {
+    let (host, port) = container
+        .published_tcp(WEB_PORT)
+        .ok_or_else(|| eyre!("Port {WEB_PORT}/tcp isn't published on the host"))?;
+
+    Ok(format!("http://{host}:{port}"))
+}
+
+/// The published VNC port if there is one, the container's own address otherwise.
+pub fn vnc_address(container: &ContainerData) -> Result<String> {
+    if let Some((host, port)) = container.published_tcp(VNC_PORT) {
+        return Ok(format!("{host}:{port}"));
+    }
+    if container.remote_host().is_some() {
+        bail!("Publish port {VNC_PORT}/tcp to reach VNC on a remote engine");
+    }
+
+    container
+        .ip_address()
+        .map(|ip| format!("{ip}:{VNC_PORT}"))
+        .ok_or_else(|| eyre!("The container has no address to reach VNC on"))
+}
+
+/// A view-only VNC connection, input stays with the browser console.
+#[derive(Debug)]
+pub struct VncSession {
+    pub container_name: String,
+    /// Desktop name the server announced.
+    pub name: Option<String>,
+    pub frame: Option<image::Handle>,
+    pub error: Option<String>,
+    kill: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum VncEvent {
+    Connected { name: String },
+    Frame(image::Handle),
+    Closed { error: Option<String> },
+}
+
+impl VncSession {
+    pub fn connect(container: &ContainerData) -> (Self, AppTask) {
+        let container_name = container.name();
+        let mut session = Self {
+            container_name: container_name.clone(),
+            name: None,
+            frame: None,
+            error: None,
+            kill: None,
+        };
+
+        let address = match vnc_address(container) {
+            Ok(address) => address,
+            Err(err) => {
+                session.error = Some(err.to_string());
+                return (session, AppTask::none());
+            }
+        };
+
+        let (kill, killed) = oneshot::channel();
+        session.kill = Some(kill);
+
+        let task = AppTask::run(
+            iced::stream::channel(4, move |mut output| async move {
+                let (frames, mut events) = mpsc::channel(2);
+
+                let error = tokio::select! {
+                    res = watch(&address, frames) => res.err().map(|err| format!("{err}")),
+                    _ = killed => None,
+                    _ = async {
+                        while let Some(event) = events.next().await {
+                            let _ = output
+                                .send(AppMsg::VncEvent(container_name.clone(), event))
+                                .await;
+                        }
+                    } => None,
+                };
+
+                let _ = output
+                    .send(AppMsg::VncEvent(container_name, VncEvent::Closed { error }))
+                    .await;
+            }),
+            |m| m,
+        );
+
+        (session, task)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.kill.is_some()
+    }
+
+    pub fn close(&mut self) {
+        if let Some(kill) = self.kill.take() {
+            let _ = kill.send(());
+        }
+    }
+
+    pub fn apply_event(&mut self, event: VncEvent) {
+        match event {
+            VncEvent::Connected { name } => self.name = Some(name),
+            VncEvent::Frame(frame) => self.frame = Some(frame),
+            VncEvent::Closed { error } => {
+                if let Some(err) = &error {
+                    tracing::error!("VNC view of {} failed: {err}", self.container_name);
+                }
+
+                self.kill = None;
+                self.error = error;
+            }
+        }
+    }
+}
+
+impl Drop for VncSession {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Speaks just enough RFB 3.3-3.8 to keep receiving the framebuffer.
+async fn watch(address: &str, mut events: mpsc::Sender<VncEvent>) -> Result<()> {
+    let stream = TcpStream::connect(address).await?;
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+
+    let minor = handshake(&mut read, &mut write).await?;
+    tracing::debug!("Connected to VNC at {address} with RFB 3.{minor}");
+
+    // ClientInit, sharing the screen with the web viewer
+    write.write_u8(1).await?;
+
+    let mut width = read.read_u16().await?;
+    let mut height = read.read_u16().await?;
+    let mut server_format = [0; 16];
+    read.read_exact(&mut server_format).await?;
+    let name = read_string(&mut read).await?;
+
+    let _ = events.send(VncEvent::Connected { name }).await;
+
+    // 32 bit little endian with red in the lowest byte, RGBX in memory
+    let mut set_format = vec![0, 0, 0, 0, 32, 24, 0, 1, 0, 255, 0, 255, 0, 255, 0, 8, 16];
+    set_format.extend([0; 3]);
+    write.write_all(&set_format).await?;
+
+    let encodings = [ENCODING_RAW, ENCODING_COPY_RECT, ENCODING_DESKTOP_SIZE];
+    let mut set_encodings = vec![2, 0];
+    set_encodings.extend((encodings.len() as u16).to_be_bytes());
+    set_encodings.extend(encodings.iter().flat_map(|e| e.to_be_bytes()));
+    write.write_all(&set_encodings).await?;
+
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    request_update(&mut write, false, width, height).await?;
+
+    loop {
+        match read.read_u8().await? {
+            // FramebufferUpdate
+            0 => {
+                read.read_u8().await?;
+                let rects = read.read_u16().await?;
+                let mut resized = false;
+
+                for _ in 0..rects {
+                    let x = read.read_u16().await? as usize;
+                    let y = read.read_u16().await? as usize;
+                    let w = read.read_u16().await? as usize;
+                    let h = read.read_u16().await? as usize;
+                    let stride = width as usize * 4;
+                    let (screen_w, screen_h) = (width as usize, height as usize);
+                    let inside = move |x: usize, y: usize| x + w <= screen_w && y + h <= screen_h;
+
+                    match read.read_i32().await? {
+                        ENCODING_RAW => {
+                            if !inside(x, y) {
+                                bail!("VNC sent a rectangle outside the screen");
+                            }
+
+                            let mut row = vec![0; w * 4];
+                            for line in y..y + h {
+                                read.read_exact(&mut row).await?;
+                                let start = line * stride + x * 4;
+                                pixels[start..start + w * 4].copy_from_slice(&row);
+                            }
+                        }
+                        ENCODING_COPY_RECT => {
+                            let src_x = read.read_u16().await? as usize;
+                            let src_y = read.read_u16().await? as usize;
+
+                            if !inside(x, y) || !inside(src_x, src_y) {
+                                bail!("VNC copied a rectangle outside the screen");
+                            }
+
+                            let source = (src_y..src_y + h)
+                                .map(|line| {
+                                    let start = line * stride + src_x * 4;
+                                    pixels[start..start + w * 4].to_vec()
+                                })
+                                .collect::<Vec<_>>();
+
+                            for (line, row) in (y..y + h).zip(source) {
+                                let start = line * stride + x * 4;
+                                pixels[start..start + w * 4].copy_from_slice(&row);
+                            }
+                        }
+                        ENCODING_DESKTOP_SIZE => {
+                            (width, height) = (w as u16, h as u16);
+                            pixels = vec![0; w * h * 4];
+                            resized = true;
+                        }
+                        encoding => bail!("VNC sent unrequested encoding {encoding}"),
+                    }
+                }
+
+                // The padding byte is whatever the server sent, the image has to be opaque
+                let frame = pixels
+                    .chunks_exact(4)
+                    .flat_map(|px| [px[0], px[1], px[2], 255])
+                    .collect::<Vec<_>>();
+                let _ = events
+                    .send(VncEvent::Frame(image::Handle::from_rgba(
+                        width as u32,
+                        height as u32,
+                        frame,
+                    )))
+                    .await;
+
+                tokio::time::sleep(FRAME_INTERVAL).await;
+                request_update(&mut write, !resized, width, height).await?;
+            }
+            // SetColourMapEntries, we asked for true color
+            1 => {
+                read.read_u8().await?;
+                read.read_u16().await?;
+                let colors = read.read_u16().await?;
+                let mut skip = vec![0; colors as usize * 6];
+                read.read_exact(&mut skip).await?;
+            }
+            // Bell
+            2 => {}
+            // ServerCutText
+            3 => {
+                let mut pad = [0; 3];
+                read.read_exact(&mut pad).await?;
+                // Clipboard text can be long and we don't use it
+                skip_string(&mut read).await?;
+            }
+            msg => bail!("VNC sent unknown message {msg}"),
+        }
+    }
+}
+
+/// Agrees on the protocol version and the "None" security type, returns the minor version.
+async fn handshake(
+    read: &mut (impl AsyncReadExt + Unpin),
+    write: &mut (impl AsyncWriteExt + Unpin),
+) -> Result<u8> {
+    let mut version = [0; 12];
+    read.read_exact(&mut version).await?;
+
+    let minor = match &version {
+        b"RFB 003.003\n" => 3,
+        b"RFB 003.007\n" => 7,
+        // Later and vendor versions speak 3.8
+        v if v.starts_with(b"RFB 003.") || v.starts_with(b"RFB 004.") => 8,
+        _ => bail!("The server doesn't speak VNC"),
+    };
+    write
+        .write_all(format!("RFB 003.00{minor}\n").as_bytes())
+        .await?;
+
+    if minor == 3 {
+        return match read.read_u32().await? {
+            0 => bail!("VNC refused: {}", read_string(read).await?),
+            1 => Ok(minor),
+            _ => bail!("VNC asks for a password, only unprotected servers are supported"),
+        };
+    }
+
+    let count = read.read_u8().await?;
+    if count == 0 {
+        bail!("VNC refused: {}", read_string(read).await?);
+    }
+
+    let mut types = vec![0; count as usize];
+    read.read_exact(&mut types).await?;
+
+    if !types.contains(&1) {
+        bail!("VNC asks for a password, only unprotected servers are supported");
+    }
+    write.write_u8(1).await?;
+
+    if minor == 8 && read.read_u32().await? != 0 {
+        bail!("VNC refused: {}", read_string(read).await?);
+    }
+
+    Ok(minor)
+}
+
+async fn request_update(
+    write: &mut (impl AsyncWriteExt + Unpin),
+    incremental: bool,
+    width: u16,
+    height: u16,
+) -> Result<()> {
+    let mut request = vec![3, incremental as u8, 0, 0, 0, 0];
+    request.extend(width.to_be_bytes());
+    request.extend(height.to_be_bytes());
+    write.write_all(&request).await?;
+
+    Ok(())
+}
+
+async fn read_string(read: &mut (impl AsyncReadExt + Unpin)) -> Result<String> {
+    let len = read.read_u32().await?;
+    if len > MAX_STRING {
+        bail!("VNC sent a string of {len} bytes, more than the {MAX_STRING} we accept");
+    }
+
+    let mut bytes = vec![0; len as usize];
+    read.read_exact(&mut bytes).await?;
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+async fn skip_string(read: &mut (impl AsyncReadExt + Unpin)) -> Result<()> {
+    let len = read.read_u32().await? as u64;
+    let skipped = tokio::io::copy(&mut read.take(len), &mut tokio::io::sink()).await?;
+
+    if skipped < len {
+        bail!("VNC closed the connection in the middle of a message");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(len: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = len.to_be_bytes().to_vec();
+        bytes.extend(body);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn reads_strings() {
+        let bytes = string(7, b"windows");
+        assert_eq!(read_string(&mut &bytes[..]).await.unwrap(), "windows");
+
+        let bytes = string(0, b"");
+        assert_eq!(read_string(&mut &bytes[..]).await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn refuses_huge_strings_before_reading_them() {
+        let bytes = string(u32::MAX, b"short");
+        let err = read_string(&mut &bytes[..]).await.unwrap_err();
+
+        assert!(err.to_string().contains("more than"));
+    }
+
+    #[tokio::test]
+    async fn skips_long_clipboard_text() {
+        let mut bytes = string(MAX_STRING * 2, &vec![b'a'; MAX_STRING as usize * 2]);
+        bytes.push(2);
+        let mut read = &bytes[..];
+
+        skip_string(&mut read).await.unwrap();
+        assert_eq!(read.read_u8().await.unwrap(), 2);
+
+        let bytes = string(10, b"cut");
+        assert!(skip_string(&mut &bytes[..]).await.is_err());
+    }
+}
@@ -1,23 +1,30 @@
 mod app;
+mod cli;
 mod controller;
 mod util;
 
+use clap::Parser;
 use color_eyre::{Result, eyre::OptionExt};
 use directories::ProjectDirs;
 use iced_fonts::NERD_FONT_BYTES;
 use tracing_subscriber::prelude::*;
 
-use crate::app::App;
+use crate::{app::App, cli::Cli};
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
+    let cli = Cli::parse();
+
     init_tracing()?;
 
+    if let Some(command) = cli.command {
+        return command.run(project_dirs()?, cli.json);
+    }
+
     iced::application(
         || {
-            let dirs = ProjectDirs::from("com", "tukanoid", "winjet")
-                .ok_or_eyre("Failed to initialize project directories")
+            let dirs = project_dirs()
                 .inspect_err(|err| tracing::error!("{err}"))
                 .unwrap();
 
@@ -34,6 +41,11 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("com", "tukanoid", "winjet")
+        .ok_or_eyre("Failed to initialize project directories")
+}
+
 fn init_tracing() -> Result<()> {
     let level = match cfg!(debug_assertions) {
         true => "debug",
@@ -41,7 +53,12 @@ fn init_tracing() -> Result<()> {
     };
 
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer().pretty())
+        // Keeps stdout for the CLI's output
+        .with(
+            tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_writer(std::io::stderr),
+        )
         .with(tracing_subscriber::EnvFilter::new(level))
         .try_init()?;
 
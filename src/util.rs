@@ -1,4 +1,9 @@
-use std::sync::Arc;
+use std::{os::unix::fs::PermissionsExt, path::PathBuf, sync::Arc, time::Duration};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
 
 pub trait Arced {
     fn arced(self) -> Arc<Self>
@@ -10,3 +15,150 @@ pub trait Arced {
 }
 
 impl<T> Arced for T {}
+
+/// Parses compose-style durations such as `2m`, `90s` or `1m30s`.
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let input = input.trim();
+
+    if input.is_empty() {
+        bail!("Empty duration");
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(|| eyre!("Missing unit in duration '{input}'"))?;
+        let (value, tail) = rest.split_at(split);
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| eyre!("Invalid number in duration '{input}'"))?;
+        let secs = match unit {
+            "h" => value * 3600.0,
+            "m" => value * 60.0,
+            "s" => value,
+            "ms" => value / 1000.0,
+            "us" | "µs" => value / 1_000_000.0,
+            "ns" => value / 1_000_000_000.0,
+            _ => bail!("Unknown unit '{unit}' in duration '{input}'"),
+        };
+
+        total = Duration::try_from_secs_f64(secs)
+            .ok()
+            .and_then(|value| total.checked_add(value))
+            .ok_or_else(|| eyre!("Duration '{input}' is too long"))?;
+        rest = tail;
+    }
+
+    Ok(total)
+}
+
+/// Hands the URL to the desktop's browser.
+pub async fn open_url(url: &str) -> Result<()> {
+    let opener = find_executable("xdg-open").ok_or_else(|| eyre!("xdg-open is not installed"))?;
+
+    let status = tokio::process::Command::new(opener)
+        .arg(url)
+        .status()
+        .await?;
+    if !status.success() {
+        bail!("xdg-open failed to open {url} ({status})");
+    }
+
+    Ok(())
+}
+
+/// Looks an executable up on `PATH`, like `which`.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| {
+            path.metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+}
+
+/// A folder in the temporary directory for a test, removed with everything in it once dropped,
+/// also when the test panics.
+#[cfg(test)]
+pub struct ScratchDir(PathBuf);
+
+#[cfg(test)]
+impl ScratchDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("winjet-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for ScratchDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        // Tests lock folders to make them unwritable
+        fn unlock(dir: &std::path::Path) {
+            let _ = std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755));
+
+            for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    unlock(&entry.path());
+                }
+            }
+        }
+
+        unlock(&self.0);
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_compose_durations() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(
+            parse_duration(" 1h30m ").unwrap(),
+            Duration::from_secs(5400)
+        );
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("10us").unwrap(), Duration::from_micros(10));
+        assert_eq!(parse_duration("10µs").unwrap(), Duration::from_micros(10));
+        assert_eq!(parse_duration("5ns").unwrap(), Duration::from_nanos(5));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        for input in ["", "  ", "90", "s", "1x", "-5s", "1..5s", "m5"] {
+            assert!(parse_duration(input).is_err(), "{input:?} parsed");
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert!(parse_duration("99999999999999999999999s").is_err());
+        assert!(parse_duration("99999999999999999999h").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+    }
+}
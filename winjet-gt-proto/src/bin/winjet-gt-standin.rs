@@ -0,0 +1,155 @@
+//! Answers like the guest tools of a fresh Windows install, so the host can be tried without one.
+//!
+//! `winjet-gt-standin [address] [apps.json]` listens on `127.0.0.1:7148` by default and exits once
+//! the host shuts it down. The JSON file replaces the canned [`InstalledApp`]s. Hosts have to
+//! present the token in `WINJET_GT_TOKEN`, `standin` without it.
+
+use std::{
+    io::{self, BufReader},
+    net::TcpListener,
+    path::PathBuf,
+};
+
+use winjet_gt_proto::{
+    AppSource, DEFAULT_PORT, InstalledApp, SessionInfo,
+    guest::{Guest, Served, serve},
+};
+
+struct StandIn {
+    apps: Option<PathBuf>,
+    next_pid: u32,
+}
+
+impl Guest for StandIn {
+    fn agent(&self) -> String {
+        format!("winjet-gt-standin {}", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn list_apps(&mut self) -> Result<Vec<InstalledApp>, String> {
+        if let Some(path) = &self.apps {
+            let json = std::fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+            return serde_json::from_slice(&json)
+                .map_err(|err| format!("{}: {err}", path.display()));
+        }
+
+        let app = |name: &str, program: &str, args: Option<&str>, color, source| InstalledApp {
+            name: name.into(),
+            program: program.into(),
+            args: args.map(Into::into),
+            icon: Some(square_bmp(color)),
+            source,
+        };
+
+        Ok(vec![
+            app(
+                "Notepad",
+                r"C:\Windows\System32\notepad.exe",
+                None,
+                [0x3b, 0x82, 0xf6],
+                AppSource::StartMenu,
+            ),
+            app(
+                "Paint",
+                r"C:\Windows\System32\mspaint.exe",
+                None,
+                [0xf5, 0x9e, 0x0b],
+                AppSource::StartMenu,
+            ),
+            app(
+                "7-Zip",
+                r"C:\Program Files\7-Zip\7zFM.exe",
+                None,
+                [0x10, 0xb9, 0x81],
+                AppSource::Registry,
+            ),
+            app(
+                "Calculator",
+                r"C:\Windows\explorer.exe",
+                Some(r"shell:AppsFolder\Microsoft.WindowsCalculator_8wekyb3d8bbwe!App"),
+                [0x6b, 0x72, 0x80],
+                AppSource::Uwp,
+            ),
+        ])
+    }
+
+    fn launch_app(&mut self, program: &str, args: Option<&str>) -> Result<u32, String> {
+        if !program.to_ascii_lowercase().ends_with(".exe") {
+            return Err(format!("{program} isn't an executable"));
+        }
+
+        let pid = self.next_pid;
+        self.next_pid += 4;
+
+        eprintln!("Launching {program} {} as {pid}", args.unwrap_or_default());
+        Ok(pid)
+    }
+
+    fn sessions(&mut self) -> Result<Vec<SessionInfo>, String> {
+        Ok(vec![SessionInfo {
+            user: "Docker".into(),
+            name: Some("console".into()),
+            active: true,
+        }])
+    }
+
+    fn shutdown(&mut self, force: bool) -> Result<(), String> {
+        eprintln!("Shutting down{}", if force { ", forced" } else { "" });
+        Ok(())
+    }
+}
+
+/// A 16x16 icon of one color, 24 bit BMP rows are stored bottom up as BGR.
+fn square_bmp([r, g, b]: [u8; 3]) -> Vec<u8> {
+    const SIZE: u32 = 16;
+    let pixels = SIZE * SIZE * 3;
+
+    let mut bmp = b"BM".to_vec();
+    bmp.extend((54 + pixels).to_le_bytes());
+    bmp.extend([0; 4]);
+    bmp.extend(54u32.to_le_bytes());
+    // BITMAPINFOHEADER
+    bmp.extend(40u32.to_le_bytes());
+    bmp.extend(SIZE.to_le_bytes());
+    bmp.extend(SIZE.to_le_bytes());
+    bmp.extend(1u16.to_le_bytes());
+    bmp.extend(24u16.to_le_bytes());
+    bmp.extend([0; 24]);
+    bmp.extend([b, g, r].repeat(pixels as usize / 3));
+
+    bmp
+}
+
+fn main() -> io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let address = args
+        .next()
+        .unwrap_or_else(|| format!("127.0.0.1:{DEFAULT_PORT}"));
+    let apps = args.next().map(PathBuf::from);
+    let token = std::env::var("WINJET_GT_TOKEN").unwrap_or_else(|_| "standin".into());
+
+    let listener = TcpListener::bind(&address)?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+
+    let mut guest = StandIn {
+        apps,
+        next_pid: 4312,
+    };
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+
+        match serve(
+            &mut guest,
+            &token,
+            BufReader::new(stream.try_clone()?),
+            stream,
+        ) {
+            Ok(Served::Shutdown) => break,
+            Ok(Served::Closed) => eprintln!("{peer} disconnected"),
+            Err(err) => eprintln!("{peer} failed: {err}"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! The guest side of a connection, the platform specifics live behind [`Guest`].
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    Frame, InstalledApp, PROTOCOL_VERSION, Request, Response, SessionInfo, read_frame, write_frame,
+};
+
+/// What the guest tools do on their platform, errors go back to the host as text.
+pub trait Guest {
+    /// Reported to the host when it says hello.
+    fn agent(&self) -> String;
+
+    fn list_apps(&mut self) -> Result<Vec<InstalledApp>, String>;
+    /// The id of the started process.
+    fn launch_app(&mut self, program: &str, args: Option<&str>) -> Result<u32, String>;
+    fn sessions(&mut self) -> Result<Vec<SessionInfo>, String>;
+    /// Begins shutting down, the answer is sent before the guest goes away.
+    fn shutdown(&mut self, force: bool) -> Result<(), String>;
+}
+
+/// How a connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Served {
+    Closed,
+    /// The host asked the guest to shut down.
+    Shutdown,
+}
+
+/// Answers the requests of one host until it disconnects or shuts the guest down.
+///
+/// Nothing is answered before the host said hello with `token`.
+pub fn serve(
+    guest: &mut impl Guest,
+    token: &str,
+    mut read: impl BufRead,
+    mut write: impl Write,
+) -> io::Result<Served> {
+    let mut greeted = false;
+
+    while let Some(Frame { id, body }) = read_frame::<Request>(&mut read)? {
+        let (response, served) = match body {
+            Request::Hello { token: sent, .. } if !same_token(&sent, token) => {
+                let message = "The host didn't present the token of this guest".to_string();
+                (Response::Error { message }, Some(Served::Closed))
+            }
+            Request::Hello { version, .. } if version == PROTOCOL_VERSION => {
+                greeted = true;
+
+                let response = Response::Hello {
+                    version: PROTOCOL_VERSION,
+                    agent: guest.agent(),
+                };
+                (response, None)
+            }
+            Request::Hello { version, .. } => {
+                let message =
+                    format!("Host speaks protocol {version}, the guest speaks {PROTOCOL_VERSION}");
+                (Response::Error { message }, Some(Served::Closed))
+            }
+            _ if !greeted => {
+                let message = "The host has to say hello first".to_string();
+                (Response::Error { message }, Some(Served::Closed))
+            }
+            Request::Ping => (Response::Pong, None),
+            Request::ListApps => (
+                answer(guest.list_apps(), |apps| Response::Apps { apps }),
+                None,
+            ),
+            Request::LaunchApp { program, args } => (
+                answer(guest.launch_app(&program, args.as_deref()), |pid| {
+                    Response::Launched { pid }
+                }),
+                None,
+            ),
+            Request::QuerySession => (
+                answer(guest.sessions(), |sessions| Response::Session { sessions }),
+                None,
+            ),
+            Request::Shutdown { force } => match guest.shutdown(force) {
+                Ok(()) => (Response::ShuttingDown, Some(Served::Shutdown)),
+                Err(message) => (Response::Error { message }, None),
+            },
+        };
+
+        write_frame(&mut write, &Frame { id, body: response })?;
+
+        if let Some(served) = served {
+            return Ok(served);
+        }
+    }
+
+    Ok(Served::Closed)
+}
+
+/// Compares every byte, so the time it takes doesn't tell how much of the token was right.
+/// An empty token lets nobody in.
+fn same_token(sent: &str, token: &str) -> bool {
+    !token.is_empty()
+        && sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn answer<T>(res: Result<T, String>, ok: impl FnOnce(T) -> Response) -> Response {
+    match res {
+        Ok(value) => ok(value),
+        Err(message) => Response::Error { message },
+    }
+}
@@ -0,0 +1,124 @@
+//! What winjet and the guest tools (`winjet-gt`) say to each other.
+//!
+//! Every frame is one line of JSON, so any byte stream can carry it. The host opens with
+//! [`Request::Hello`] and the guest refuses to talk to hosts of another [`PROTOCOL_VERSION`], or
+//! to ones that don't know its token.
+
+pub mod guest;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// Bumped whenever a message changes shape.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Where the guest tools listen inside Windows, `dockurr/windows` forwards it with `USER_PORTS`.
+pub const DEFAULT_PORT: u16 = 7148;
+
+/// The file with the token the host provisions, next to the guest tools or in the shared folder.
+pub const TOKEN_FILE: &str = "winjet-gt.token";
+
+/// A message with the id that pairs a response with its request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame<T> {
+    pub id: u64,
+    #[serde(flatten)]
+    pub body: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello {
+        version: u32,
+        /// The secret the host provisioned the guest with, see [`TOKEN_FILE`].
+        token: String,
+    },
+    Ping,
+    ListApps,
+    /// Starts the program in the guest, outside of any RDP session.
+    LaunchApp {
+        program: String,
+        args: Option<String>,
+    },
+    QuerySession,
+    /// `force` closes programs without letting them save.
+    Shutdown {
+        force: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Hello { version: u32, agent: String },
+    Pong,
+    Apps { apps: Vec<InstalledApp> },
+    Launched { pid: u32 },
+    Session { sessions: Vec<SessionInfo> },
+    ShuttingDown,
+    Error { message: String },
+}
+
+/// A program installed in the guest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledApp {
+    pub name: String,
+    /// Executable path in the guest.
+    pub program: String,
+    pub args: Option<String>,
+    /// Raw ICO, PNG or BMP.
+    pub icon: Option<Vec<u8>>,
+    pub source: AppSource,
+}
+
+/// Where the guest found an [`InstalledApp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppSource {
+    /// A shortcut in the Start Menu.
+    StartMenu,
+    /// An uninstall entry in the registry.
+    Registry,
+    /// A packaged app, launched through `explorer.exe shell:AppsFolder\...`.
+    Uwp,
+}
+
+/// A Windows logon session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub user: String,
+    /// `rdp-tcp#0`, `console`...
+    pub name: Option<String>,
+    pub active: bool,
+}
+
+pub fn encode<T: Serialize>(frame: &Frame<T>) -> io::Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(frame)?;
+    line.push(b'\n');
+
+    Ok(line)
+}
+
+pub fn decode<T: DeserializeOwned>(line: &[u8]) -> io::Result<Frame<T>> {
+    Ok(serde_json::from_slice(line)?)
+}
+
+pub fn write_frame<T: Serialize>(write: &mut impl Write, frame: &Frame<T>) -> io::Result<()> {
+    write.write_all(&encode(frame)?)?;
+    write.flush()
+}
+
+/// `None` once the other side closed the stream.
+pub fn read_frame<T: DeserializeOwned>(read: &mut impl BufRead) -> io::Result<Option<Frame<T>>> {
+    let mut line = vec![];
+
+    match read.read_until(b'\n', &mut line)? {
+        0 => Ok(None),
+        _ => decode(&line).map(Some),
+    }
+}
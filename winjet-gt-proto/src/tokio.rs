@@ -0,0 +1,28 @@
+//! The framing of the crate root for async streams.
+
+use std::io;
+
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Frame, decode, encode};
+
+pub async fn write_frame<T: Serialize>(
+    write: &mut (impl AsyncWrite + Unpin),
+    frame: &Frame<T>,
+) -> io::Result<()> {
+    write.write_all(&encode(frame)?).await?;
+    write.flush().await
+}
+
+/// `None` once the other side closed the stream.
+pub async fn read_frame<T: DeserializeOwned>(
+    read: &mut (impl AsyncBufRead + Unpin),
+) -> io::Result<Option<Frame<T>>> {
+    let mut line = vec![];
+
+    match read.read_until(b'\n', &mut line).await? {
+        0 => Ok(None),
+        _ => decode(&line).map(Some),
+    }
+}
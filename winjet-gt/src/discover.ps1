@@ -0,0 +1,108 @@
+# Prints what can be launched in this Windows as one JSON array of `winjet_gt_proto::InstalledApp`.
+# Runs as the service, so it looks at the Start Menu of every profile instead of its own.
+
+$ErrorActionPreference = 'SilentlyContinue'
+[Console]::OutputEncoding = New-Object System.Text.UTF8Encoding $false
+Add-Type -AssemblyName System.Drawing
+
+function Get-IconBytes([string] $Program) {
+    if (-not $Program -or -not (Test-Path -LiteralPath $Program)) { return $null }
+
+    $icon = [System.Drawing.Icon]::ExtractAssociatedIcon($Program)
+    if (-not $icon) { return $null }
+
+    $png = New-Object System.IO.MemoryStream
+    $icon.ToBitmap().Save($png, [System.Drawing.Imaging.ImageFormat]::Png)
+
+    # The comma keeps PowerShell from unrolling the bytes
+    , $png.ToArray()
+}
+
+function Get-LogoBytes([string] $Root, [string] $Logo) {
+    if (-not $Logo) { return $null }
+
+    $path = Join-Path $Root $Logo
+    if (-not (Test-Path -LiteralPath $path)) {
+        # Packages usually only ship the scaled variants, Logo.scale-100.png
+        $name = [System.IO.Path]::GetFileNameWithoutExtension($path)
+        $extension = [System.IO.Path]::GetExtension($path)
+        $scaled = Get-ChildItem -LiteralPath (Split-Path $path) -Filter "$name.scale-*$extension" |
+            Sort-Object Name | Select-Object -First 1
+
+        if (-not $scaled) { return $null }
+        $path = $scaled.FullName
+    }
+
+    , [System.IO.File]::ReadAllBytes($path)
+}
+
+function New-App([string] $Name, [string] $Program, [string] $Arguments, $Icon, [string] $Source) {
+    [pscustomobject]@{
+        name    = $Name
+        program = $Program
+        args    = if ($Arguments) { $Arguments } else { $null }
+        icon    = $Icon
+        source  = $Source
+    }
+}
+
+$apps = @()
+
+$shell = New-Object -ComObject WScript.Shell
+$startMenus = @("$env:ProgramData\Microsoft\Windows\Start Menu\Programs") +
+    (Get-ChildItem -LiteralPath (Split-Path $env:PUBLIC) -Directory | ForEach-Object {
+        Join-Path $_.FullName 'AppData\Roaming\Microsoft\Windows\Start Menu\Programs'
+    })
+
+foreach ($link in Get-ChildItem -LiteralPath $startMenus -Recurse -Filter *.lnk) {
+    $shortcut = $shell.CreateShortcut($link.FullName)
+    $program = $shortcut.TargetPath
+
+    # Shortcuts to documents, websites and uninstallers aren't apps
+    if ($program -notlike '*.exe' -or $program -like '*unins*' -or -not (Test-Path -LiteralPath $program)) {
+        continue
+    }
+
+    $apps += New-App $link.BaseName $program $shortcut.Arguments (Get-IconBytes $program) 'start_menu'
+}
+
+$uninstall = @(
+    'HKLM:\Software\Microsoft\Windows\CurrentVersion\Uninstall\*',
+    'HKLM:\Software\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\*'
+)
+
+foreach ($entry in Get-ItemProperty $uninstall) {
+    if (-not $entry.DisplayName -or $entry.SystemComponent -eq 1 -or -not $entry.DisplayIcon) {
+        continue
+    }
+
+    # `"C:\...\app.exe",0`, the icon of an installed program usually is the program itself
+    $program = ($entry.DisplayIcon -split ',')[0].Trim('"')
+
+    if ($program -notlike '*.exe' -or $program -like '*unins*' -or -not (Test-Path -LiteralPath $program)) {
+        continue
+    }
+
+    $apps += New-App $entry.DisplayName $program $null (Get-IconBytes $program) 'registry'
+}
+
+foreach ($package in Get-AppxPackage -AllUsers -PackageTypeFilter Main) {
+    if ($package.IsFramework -or $package.SignatureKind -eq 'System') { continue }
+
+    $manifestPath = Join-Path $package.InstallLocation 'AppxManifest.xml'
+    if (-not (Test-Path -LiteralPath $manifestPath)) { continue }
+
+    $manifest = [xml](Get-Content -LiteralPath $manifestPath -Raw)
+    $logo = Get-LogoBytes $package.InstallLocation $manifest.Package.Properties.Logo
+
+    foreach ($app in $manifest.Package.Applications.Application) {
+        # Localized names are resource references, fall back to something readable
+        $name = @($app.VisualElements.DisplayName, $manifest.Package.Properties.DisplayName, $package.Name) |
+            Where-Object { $_ -and $_ -notlike 'ms-resource:*' } | Select-Object -First 1
+
+        $target = "shell:AppsFolder\$($package.PackageFamilyName)!$($app.Id)"
+        $apps += New-App $name "$env:SystemRoot\explorer.exe" $target $logo 'uwp'
+    }
+}
+
+ConvertTo-Json -InputObject @($apps) -Depth 3 -Compress
@@ -0,0 +1,54 @@
+use std::process::Command;
+
+use winjet_gt_proto::InstalledApp;
+
+/// Walking the Start Menu, the registry and the packages is a lot easier from PowerShell.
+const SCRIPT: &str = include_str!("discover.ps1");
+
+/// Everything the Start Menu, the uninstall keys and the packages know about, duplicates included.
+pub fn installed_apps() -> Result<Vec<InstalledApp>, String> {
+    // Passed on the command line, a script file could be swapped out before PowerShell reads it
+    let output = Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-EncodedCommand"])
+        .arg(encoded_command(SCRIPT))
+        .output()
+        .map_err(|err| format!("Failed to start PowerShell: {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Listing applications failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let json = String::from_utf8_lossy(&output.stdout);
+
+    serde_json::from_str(json.trim_start_matches('\u{feff}'))
+        .map_err(|err| format!("PowerShell listed applications as invalid JSON: {err}"))
+}
+
+/// What `-EncodedCommand` takes, the script as UTF-16LE in base64.
+fn encoded_command(script: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let bytes = script
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<_>>();
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0, |group, (i, byte)| {
+            group | (u32::from(*byte) << (16 - 8 * i))
+        });
+
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+
+    encoded
+}
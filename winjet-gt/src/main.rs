@@ -1,7 +1,238 @@
-use windows_service::{Result, define_windows_service, service_dispatcher};
+//! The guest tools, a Windows service that answers winjet over [`winjet_gt_proto`].
+
+mod discover;
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, BufReader},
+    net::{IpAddr, Ipv4Addr, TcpListener, TcpStream, UdpSocket},
+    os::windows::process::CommandExt,
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use windows_service::{
+    Result, define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+};
+use winjet_gt_proto::{
+    DEFAULT_PORT, InstalledApp, SessionInfo, TOKEN_FILE,
+    guest::{Guest, serve},
+};
+
+const SERVICE_NAME: &str = "winjet-gt";
+/// How often the listener looks out for the service being stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// The host's folder `dockurr/windows` shares with Windows.
+const SHARED_FOLDER: &str = r"\\host.lan\Data";
+
+define_windows_service!(ffi_service_main, service_main);
 
 fn main() -> Result<()> {
-    println!("Hello, world!");
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+fn service_main(_args: Vec<OsString>) {
+    // Nobody reads the output of a service, Windows logs that it stopped
+    let _ = run_service();
+}
+
+fn run_service() -> Result<()> {
+    let (stop, stopped) = mpsc::channel();
+
+    let status = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = stop.send(());
+            ServiceControlHandlerResult::NoError
+        }
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    status.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: ServiceState::Running,
+        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })?;
+
+    let exit_code = match listen(stopped) {
+        Ok(()) => 0,
+        Err(err) => err.raw_os_error().unwrap_or(1) as u32,
+    };
+
+    status.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: ServiceState::Stopped,
+        controls_accepted: ServiceControlAccept::empty(),
+        exit_code: ServiceExitCode::Win32(exit_code),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })
+}
+
+/// Serves every host connection on its own thread until the service is stopped.
+fn listen(stopped: mpsc::Receiver<()>) -> io::Result<()> {
+    let token = token()?;
+
+    // The network may still be coming up when Windows starts the service
+    let interface = loop {
+        match stopped.try_recv() {
+            Err(mpsc::TryRecvError::Empty) => {}
+            _ => return Ok(()),
+        }
+
+        match host_interface() {
+            Ok(interface) => break interface,
+            Err(_) => thread::sleep(POLL_INTERVAL),
+        }
+    };
+
+    let listener = TcpListener::bind((interface, DEFAULT_PORT))?;
+    listener.set_nonblocking(true)?;
+
+    loop {
+        match stopped.try_recv() {
+            Err(mpsc::TryRecvError::Empty) => {}
+            _ => return Ok(()),
+        }
+
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let token = token.clone();
+                thread::spawn(move || connection(stream, &token));
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The token next to the executable, or the one the host left in the shared folder.
+fn token() -> io::Result<String> {
+    let beside = std::env::current_exe()?.with_file_name(TOKEN_FILE);
+    let token = fs::read_to_string(beside)
+        .or_else(|_| fs::read_to_string(format!(r"{SHARED_FOLDER}\{TOKEN_FILE}")))?;
 
+    match token.trim() {
+        "" => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{TOKEN_FILE} is empty"),
+        )),
+        token => Ok(token.to_string()),
+    }
+}
+
+/// The address of the interface the host reaches Windows through, the one routing to `host.lan`.
+fn host_interface() -> io::Result<IpAddr> {
+    // Connecting a UDP socket only picks the route, nothing is sent
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(("host.lan", DEFAULT_PORT))?;
+
+    Ok(socket.local_addr()?.ip())
+}
+
+fn connection(stream: TcpStream, token: &str) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+
+    serve(
+        &mut Windows,
+        token,
+        BufReader::new(stream.try_clone()?),
+        stream,
+    )?;
     Ok(())
 }
+
+struct Windows;
+
+impl Guest for Windows {
+    fn agent(&self) -> String {
+        format!("winjet-gt {}", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn list_apps(&mut self) -> std::result::Result<Vec<InstalledApp>, String> {
+        discover::installed_apps()
+    }
+
+    /// Runs in the session of the service, not in the one of the logged in user.
+    fn launch_app(
+        &mut self,
+        program: &str,
+        args: Option<&str>,
+    ) -> std::result::Result<u32, String> {
+        let mut command = Command::new(program);
+
+        // Windows programs parse their command line themselves
+        if let Some(args) = args {
+            command.raw_arg(args);
+        }
+
+        command
+            .spawn()
+            .map(|child| child.id())
+            .map_err(|err| format!("Failed to start {program}: {err}"))
+    }
+
+    fn sessions(&mut self) -> std::result::Result<Vec<SessionInfo>, String> {
+        // Exits with 1 when nobody is logged in
+        let output = Command::new("query")
+            .arg("user")
+            .output()
+            .map_err(|err| format!("Failed to query the sessions: {err}"))?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .skip(1)
+            .filter_map(parse_session)
+            .collect())
+    }
+
+    fn shutdown(&mut self, force: bool) -> std::result::Result<(), String> {
+        let mut command = Command::new("shutdown");
+        command.args(["/s", "/t", "0"]);
+
+        if force {
+            command.arg("/f");
+        }
+
+        command
+            .spawn()
+            .map(|_| ())
+            .map_err(|err| format!("Failed to shut down: {err}"))
+    }
+}
+
+/// A line of `query user`, disconnected sessions have no session name.
+///
+/// ` USERNAME   SESSIONNAME   ID  STATE   IDLE TIME  LOGON TIME`
+fn parse_session(line: &str) -> Option<SessionInfo> {
+    let fields = line
+        .trim_start_matches(['>', ' '])
+        .split_whitespace()
+        .collect::<Vec<_>>();
+
+    let (user, rest) = fields.split_first()?;
+    let (name, rest) = match rest.first()?.parse::<u32>() {
+        Ok(_) => (None, rest),
+        Err(_) => (Some(rest[0].to_string()), &rest[1..]),
+    };
+
+    Some(SessionInfo {
+        user: user.to_string(),
+        name,
+        active: rest.get(1) == Some(&"Active"),
+    })
+}